@@ -0,0 +1,78 @@
+use rusqlite::Connection;
+use std::time::Duration;
+
+/// Tables of this app, kept in the library database next to the ones of the lens
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS saved_view (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS entry_grade (
+        path TEXT PRIMARY KEY,
+        grade INTEGER
+    );
+
+    CREATE TABLE IF NOT EXISTS scan_location (
+        id INTEGER PRIMARY KEY,
+        last_scan INTEGER
+    );
+
+    CREATE TABLE IF NOT EXISTS scan_stamp (
+        location_id INTEGER NOT NULL,
+        path TEXT NOT NULL,
+        modified INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        PRIMARY KEY (location_id, path)
+    );
+
+    CREATE TABLE IF NOT EXISTS scan_entry (
+        path TEXT PRIMARY KEY,
+        location_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        size INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS location_options (
+        location_id INTEGER PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS scan_file (
+        entry_path TEXT NOT NULL,
+        location_id INTEGER NOT NULL,
+        path TEXT NOT NULL,
+        name TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        PRIMARY KEY (entry_path, path)
+    );
+";
+
+/// Opens the library database and creates the app tables that are missing
+pub fn open(db_path: &str) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(db_path)?;
+    // The lens writes to the same file
+    conn.busy_timeout(Duration::from_secs(5))?;
+    conn.execute_batch(SCHEMA)?;
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    #[test]
+    fn test_open_twice() {
+        let path = temp_dir("app_db_open").join("Library.sqlite3");
+        let path = path.to_string_lossy();
+
+        open(&path).unwrap();
+        let conn = open(&path).unwrap();
+        let views: i64 = conn
+            .query_row("SELECT COUNT(*) FROM saved_view", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(views, 0);
+    }
+}
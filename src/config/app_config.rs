@@ -0,0 +1,231 @@
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::library::MAX_RECENT;
+use crate::table_utils::{TableStyle, VIDEO_FORMATS};
+use crate::trash::DeleteMode;
+
+pub const LOG_LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+
+/// User settings read from config.toml on startup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    /// Database file, the default one in the data dir when not set.
+    /// Set to the library last opened from the File menu.
+    pub db_path: Option<String>,
+    /// Libraries opened before, most recent first
+    pub recent_libraries: Vec<String>,
+    pub log_level: String,
+    /// Log to this file as well as to the console
+    pub log_file: Option<String>,
+    pub font_size: i32,
+    pub row_height: i32,
+    /// Offer the trash before permanent delete
+    pub delete_to_trash: bool,
+    /// File endings shown in the video colour in the file table
+    pub video_extensions: Vec<String>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        let style = TableStyle::default();
+        AppConfig {
+            db_path: None,
+            recent_libraries: Vec::new(),
+            log_level: "info".to_string(),
+            log_file: None,
+            font_size: style.font_size,
+            row_height: style.row_height,
+            delete_to_trash: true,
+            video_extensions: VIDEO_FORMATS.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+}
+
+/// Config file given with `--config <path>` or `--config=<path>`
+pub fn config_path_from_args(args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    let mut args = args.skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// Extensions from a comma or space separated list, lower case with a leading dot
+pub fn parse_extensions(text: &str) -> Vec<String> {
+    let mut extensions: Vec<String> = Vec::new();
+    for ext in text.split(|c: char| c == ',' || c.is_whitespace()) {
+        let ext = ext.trim().trim_start_matches('.').to_lowercase();
+        if ext.is_empty() {
+            continue;
+        }
+
+        let ext = format!(".{}", ext);
+        if !extensions.contains(&ext) {
+            extensions.push(ext);
+        }
+    }
+    extensions
+}
+
+impl AppConfig {
+    /// Reads the config, the defaults are used when it is missing or broken
+    pub fn load(path: &Path) -> AppConfig {
+        let config = match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).unwrap_or_else(|err| {
+                println!("Failed to parse config {:?}: {}", path, err);
+                AppConfig::default()
+            }),
+            Err(_) => AppConfig::default(),
+        };
+
+        config.sanitized()
+    }
+
+    /// What delete confirmations offer first
+    pub fn delete_mode(&self) -> DeleteMode {
+        if self.delete_to_trash {
+            DeleteMode::Trash
+        } else {
+            DeleteMode::Permanent
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = toml::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, text)
+    }
+
+    /// Keeps sizes readable and drops empty paths
+    pub fn sanitized(mut self) -> AppConfig {
+        self.font_size = self.font_size.clamp(8, 32);
+        self.row_height = self.row_height.clamp(self.font_size + 2, 64);
+        self.log_level = self.log_level.trim().to_lowercase();
+        self.db_path = self.db_path.filter(|p| !p.trim().is_empty());
+        self.log_file = self.log_file.filter(|p| !p.trim().is_empty());
+
+        let mut recent: Vec<String> = Vec::new();
+        for path in self.recent_libraries.drain(..) {
+            if !path.trim().is_empty() && !recent.contains(&path) {
+                recent.push(path);
+            }
+        }
+        recent.truncate(MAX_RECENT);
+        self.recent_libraries = recent;
+
+        self.video_extensions = parse_extensions(&self.video_extensions.join(","));
+        self
+    }
+
+    /// The configured level, info when it is not a known level
+    pub fn log_level(&self) -> LevelFilter {
+        LevelFilter::from_str(&self.log_level).unwrap_or(LevelFilter::Info)
+    }
+
+    pub fn table_style(&self) -> TableStyle {
+        TableStyle {
+            font_size: self.font_size,
+            row_height: self.row_height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    fn args(list: &[&str]) -> impl Iterator<Item = String> {
+        list.iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_config_path_from_args() {
+        assert_eq!(
+            config_path_from_args(args(&["so", "--config", "/tmp/a.toml"])),
+            Some(PathBuf::from("/tmp/a.toml"))
+        );
+        assert_eq!(
+            config_path_from_args(args(&["so", "--config=b.toml"])),
+            Some(PathBuf::from("b.toml"))
+        );
+        assert_eq!(config_path_from_args(args(&["so", "--config"])), None);
+        assert_eq!(config_path_from_args(args(&["--config=x"])), None);
+    }
+
+    #[test]
+    fn test_parse_extensions() {
+        assert_eq!(
+            parse_extensions("mkv, .MP4 avi,,mkv"),
+            vec![".mkv", ".mp4", ".avi"]
+        );
+        assert!(parse_extensions(" , ").is_empty());
+    }
+
+    #[test]
+    fn test_partial_and_broken_values() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            log_level = "DEBUG"
+            font_size = 200
+            row_height = 1
+            db_path = ""
+            recent_libraries = ["/a.sqlite3", "", "/b.sqlite3", "/a.sqlite3"]
+            video_extensions = ["MKV", "mp4"]
+            "#,
+        )
+        .unwrap();
+        let config = config.sanitized();
+
+        assert_eq!(config.log_level(), LevelFilter::Debug);
+        assert_eq!(config.font_size, 32);
+        assert_eq!(config.row_height, 34);
+        assert_eq!(config.db_path, None);
+        assert_eq!(config.recent_libraries, vec!["/a.sqlite3", "/b.sqlite3"]);
+        assert!(config.delete_to_trash);
+        assert_eq!(config.delete_mode(), DeleteMode::Trash);
+        assert_eq!(config.video_extensions, vec![".mkv", ".mp4"]);
+
+        let config = AppConfig {
+            log_level: "loud".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(config.log_level(), LevelFilter::Info);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = temp_dir("app_config");
+        let path = dir.join("config.toml");
+
+        let config = AppConfig {
+            db_path: Some("/data/library.sqlite3".to_string()),
+            recent_libraries: vec!["/data/library.sqlite3".to_string()],
+            log_file: Some("/tmp/so.log".to_string()),
+            delete_to_trash: false,
+            ..Default::default()
+        };
+        config.save(&path).unwrap();
+
+        assert_eq!(AppConfig::load(&path), config);
+        assert_eq!(config.delete_mode(), DeleteMode::Permanent);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(AppConfig::load(&path), AppConfig::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+use fltk::app::channel;
+use fltk::dialog::{NativeFileChooser, NativeFileChooserType};
+use fltk::enums::{Align, Color};
+use fltk::frame::Frame;
+use fltk::input::{Input, IntInput};
+use fltk::menu::Choice;
+use fltk::{button::*, window::*};
+
+use fltk::prelude::*;
+
+use std::path::PathBuf;
+
+use crate::config::app_config::{parse_extensions, AppConfig, LOG_LEVELS};
+use crate::error_dialog::ErrorDialog;
+
+#[derive(Clone, Debug)]
+pub enum ConfigMessage {
+    BrowseDatabase,
+    BrowseLogFile,
+    Save,
+    ExitDialog,
+}
+
+/// Edits the config file, the saved config is returned from `show`
+pub struct ConfigDialog {
+    config: AppConfig,
+    path: PathBuf,
+}
+
+fn browse(input: &mut Input, title: &str, kind: NativeFileChooserType) {
+    let mut chooser = NativeFileChooser::new(kind);
+    chooser.set_title(title);
+
+    let current = input.value();
+    if !current.trim().is_empty() {
+        let _ = chooser.set_directory(&current);
+    }
+
+    chooser.show();
+    let path = chooser.filename();
+    if !path.as_os_str().is_empty() {
+        input.set_value(&path.to_string_lossy());
+    }
+}
+
+fn optional(text: String) -> Option<String> {
+    Some(text.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+impl ConfigDialog {
+    pub fn new(config: AppConfig, path: PathBuf) -> Self {
+        ConfigDialog { config, path }
+    }
+
+    pub fn show(&self) -> Option<AppConfig> {
+        let (sender, reciever) = channel::<ConfigMessage>();
+
+        let mut dialog = Window::new(300, 100, 500, 330, "Settings");
+        dialog.make_modal(true);
+
+        let mut input_db = Input::new(120, 10, 290, 25, "Database");
+        input_db.set_value(self.config.db_path.as_deref().unwrap_or(""));
+        input_db.set_tooltip("Empty uses the database in the data dir");
+        let mut but_db = Button::new(415, 10, 75, 25, "Browse...");
+
+        let mut choice_level = Choice::new(120, 45, 120, 25, "Log level");
+        for level in LOG_LEVELS.iter() {
+            choice_level.add_choice(level);
+        }
+        let level_ix = LOG_LEVELS
+            .iter()
+            .position(|l| *l == self.config.log_level)
+            .unwrap_or(3);
+        choice_level.set_value(level_ix as i32);
+
+        let mut input_log = Input::new(120, 80, 290, 25, "Log file");
+        input_log.set_value(self.config.log_file.as_deref().unwrap_or(""));
+        let mut but_log = Button::new(415, 80, 75, 25, "Browse...");
+
+        let mut input_font = IntInput::new(120, 115, 60, 25, "Font size");
+        input_font.set_value(&self.config.font_size.to_string());
+        let mut input_row = IntInput::new(330, 115, 60, 25, "Row height");
+        input_row.set_value(&self.config.row_height.to_string());
+
+        let mut check_trash = CheckButton::new(120, 150, 250, 25, "Delete to trash by default");
+        check_trash.set_checked(self.config.delete_to_trash);
+
+        let mut input_video = Input::new(120, 185, 370, 25, "Video files");
+        input_video.set_value(&self.config.video_extensions.join(", "));
+        input_video.set_tooltip("File endings shown in blue, e.g. .mkv, .mp4");
+
+        let mut frame_note = Frame::new(10, 220, 480, 50, None);
+        frame_note.set_label(&format!(
+            "Logging changes apply after a restart.\nConfig file: {}",
+            self.path.to_string_lossy()
+        ));
+        frame_note.set_label_size(11);
+        frame_note.set_label_color(Color::Inactive);
+        frame_note.set_align(Align::Left | Align::Inside | Align::Wrap);
+
+        let mut but_save = Button::new(10, 295, 70, 25, "Save");
+        let mut but_cancel = Button::new(90, 295, 70, 25, "Cancel");
+
+        dialog.end();
+        dialog.show();
+
+        but_db.emit(sender.clone(), ConfigMessage::BrowseDatabase);
+        but_log.emit(sender.clone(), ConfigMessage::BrowseLogFile);
+        but_save.emit(sender.clone(), ConfigMessage::Save);
+        but_cancel.emit(sender, ConfigMessage::ExitDialog);
+
+        let mut saved = None;
+
+        while dialog.shown() {
+            while fltk::app::wait() {
+                if let Some(msg) = reciever.recv() {
+                    match msg {
+                        ConfigMessage::BrowseDatabase => browse(
+                            &mut input_db,
+                            "Select database",
+                            NativeFileChooserType::BrowseSaveFile,
+                        ),
+                        ConfigMessage::BrowseLogFile => browse(
+                            &mut input_log,
+                            "Select log file",
+                            NativeFileChooserType::BrowseSaveFile,
+                        ),
+                        ConfigMessage::Save => {
+                            let config = AppConfig {
+                                db_path: optional(input_db.value()),
+                                recent_libraries: self.config.recent_libraries.clone(),
+                                log_level: LOG_LEVELS
+                                    .get(choice_level.value().max(0) as usize)
+                                    .unwrap_or(&"info")
+                                    .to_string(),
+                                log_file: optional(input_log.value()),
+                                font_size: input_font
+                                    .value()
+                                    .parse()
+                                    .unwrap_or(self.config.font_size),
+                                row_height: input_row
+                                    .value()
+                                    .parse()
+                                    .unwrap_or(self.config.row_height),
+                                delete_to_trash: check_trash.is_checked(),
+                                video_extensions: parse_extensions(&input_video.value()),
+                            }
+                            .sanitized();
+
+                            if let Err(err) = config.save(&self.path) {
+                                let err_dialog = ErrorDialog::new(format!(
+                                    "Failed to save config {}: {}",
+                                    self.path.to_string_lossy(),
+                                    err
+                                ));
+                                err_dialog.show();
+                                continue;
+                            }
+
+                            saved = Some(config);
+                            dialog.hide();
+                            break;
+                        }
+                        ConfigMessage::ExitDialog => {
+                            dialog.hide();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        println!("Exit settings dialog");
+        saved
+    }
+}
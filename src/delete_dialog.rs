@@ -0,0 +1,188 @@
+use fltk::app::Sender;
+use parking_lot::Mutex;
+use serious_organizer_lib::lens::Lens;
+use serious_organizer_lib::models::{Entry, File};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+use crate::choice_dialog::ChoiceDialog;
+use crate::history::journal::{History, Operation};
+use crate::model::message::Message;
+use crate::scan::scanner::Scanner;
+use crate::trash::{self, failure_summary, DeleteMode, TrashedItem};
+
+/// What the delete confirmation was asked for
+#[derive(Debug, Clone)]
+pub enum DeleteTarget {
+    Entries(Vec<Entry>),
+    Files(Vec<File>),
+}
+
+impl DeleteTarget {
+    fn what(&self) -> &'static str {
+        match self {
+            DeleteTarget::Entries(_) => "entries",
+            DeleteTarget::Files(_) => "files",
+        }
+    }
+
+    fn paths(&self) -> Vec<String> {
+        match self {
+            DeleteTarget::Entries(entries) => entries.iter().map(|e| e.path.clone()).collect(),
+            DeleteTarget::Files(files) => files.iter().map(|f| f.path.clone()).collect(),
+        }
+    }
+
+    fn trashed(&self, items: Vec<TrashedItem>) -> Operation {
+        match self {
+            DeleteTarget::Entries(_) => Operation::TrashEntries { items },
+            DeleteTarget::Files(_) => Operation::TrashFiles { items },
+        }
+    }
+
+    fn deleted(&self, paths: Vec<String>) -> Operation {
+        match self {
+            DeleteTarget::Entries(_) => Operation::DeleteEntries { paths },
+            DeleteTarget::Files(_) => Operation::DeleteFiles { paths },
+        }
+    }
+
+    /// Deletes through the lens, it keeps its own state in step
+    fn remove(&self, lens: &Arc<Mutex<Lens>>) -> Vec<Result<String, String>> {
+        let mut lens = lens.lock();
+        match self {
+            DeleteTarget::Entries(entries) => entries
+                .iter()
+                .map(|entry| match lens.remove_entry(entry) {
+                    Ok(_) => Ok(entry.path.clone()),
+                    Err(err) => Err(format!("{}: {}", entry.path, err)),
+                })
+                .collect(),
+            DeleteTarget::Files(files) => files
+                .iter()
+                .map(|file| match lens.remove_file(file) {
+                    Ok(_) => Ok(file.path.clone()),
+                    Err(err) => Err(format!("{}: {}", file.path, err)),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// What a delete did, sent once it is done
+#[derive(Debug, Clone)]
+pub struct DeleteSummary {
+    pub what: &'static str,
+    pub total: usize,
+    pub errors: Vec<String>,
+}
+
+impl DeleteSummary {
+    /// Text for an error dialog, None when everything was deleted
+    pub fn failure_text(&self) -> Option<String> {
+        if self.errors.is_empty() {
+            None
+        } else {
+            Some(failure_summary(self.what, self.total, &self.errors))
+        }
+    }
+}
+
+/// Asks how to delete the target, offering `default` first, and then trashes or deletes it.
+/// The paths are rescanned after either, so the scan index drops them too. The trash
+/// runs on a thread without holding the lens, like a transfer, as moving to a trash on
+/// another device copies. `Message::DeleteFinished` is sent when done.
+pub fn delete(
+    target: DeleteTarget,
+    lens: Arc<Mutex<Lens>>,
+    history: &Arc<Mutex<History>>,
+    scanner: &Scanner,
+    sender: &Sender<Message>,
+    default: DeleteMode,
+) {
+    let paths = target.paths();
+    let mode = match show_delete_confirmation_dialog(paths.len(), target.what(), default) {
+        Some(mode) => mode,
+        None => return,
+    };
+
+    println!("Delete the {} {:?} {:?}", target.what(), mode, paths);
+
+    match mode {
+        DeleteMode::Trash => {
+            let history = history.clone();
+            let scanner = scanner.clone();
+            let sender = sender.clone();
+            thread::spawn(move || {
+                let mut trashed = Vec::new();
+                let mut errors = Vec::new();
+                for path in paths.iter() {
+                    match trash::trash(Path::new(path)) {
+                        Ok(item) => trashed.push(item),
+                        Err(err) => errors.push(format!("{}: {}", path, err)),
+                    }
+                }
+
+                if !trashed.is_empty() {
+                    scanner.rescan_paths(trashed.iter().map(|item| item.original_path.as_path()));
+                    history.lock().push(target.trashed(trashed));
+                }
+                sender.send(Message::DeleteFinished(DeleteSummary {
+                    what: target.what(),
+                    total: paths.len(),
+                    errors,
+                }));
+            });
+        }
+        DeleteMode::Permanent => {
+            let mut deleted = Vec::new();
+            let mut errors = Vec::new();
+            for result in target.remove(&lens) {
+                match result {
+                    Ok(path) => deleted.push(path),
+                    Err(err) => errors.push(err),
+                }
+            }
+
+            if !deleted.is_empty() {
+                scanner.rescan_paths(deleted.iter().map(|path| Path::new(path.as_str())));
+                history.lock().push(target.deleted(deleted));
+            }
+            sender.send(Message::DeleteFinished(DeleteSummary {
+                what: target.what(),
+                total: paths.len(),
+                errors,
+            }));
+        }
+    }
+}
+
+fn show_delete_confirmation_dialog(
+    count: usize,
+    what: &str,
+    default: DeleteMode,
+) -> Option<DeleteMode> {
+    let modes = default.offered();
+    let mut choices: Vec<String> = modes.iter().map(|m| m.label().to_string()).collect();
+    choices.push("Cancel".to_string());
+
+    let text = match default {
+        DeleteMode::Trash => format!(
+            "Move {} {} to the trash? Delete removes them permanently.",
+            count, what
+        ),
+        DeleteMode::Permanent => format!(
+            "Delete {} {} permanently? Trash moves them to the trash instead.",
+            count, what
+        ),
+    };
+
+    let dialog = ChoiceDialog::new(text, choices);
+    dialog.show();
+
+    usize::try_from(dialog.result())
+        .ok()
+        .and_then(|ix| modes.get(ix))
+        .copied()
+}
@@ -0,0 +1,446 @@
+use fltk::app::{channel, Sender};
+use fltk::enums::{Align, Color};
+use fltk::frame::Frame;
+use fltk::menu::Choice;
+use fltk::{button::*, window::*};
+
+use fltk::prelude::*;
+
+use parking_lot::Mutex;
+use rusqlite::Connection;
+use serious_organizer_lib::lens::Lens;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::choice_dialog::ChoiceDialog;
+use crate::duplicates::duplicate_finder::{
+    self, Candidate, DuplicateGroup, FindProgress, FindResult,
+};
+use crate::error_dialog::ErrorDialog;
+use crate::get_selected_index;
+use crate::history::journal::{History, Operation};
+use crate::location::location_table::LocationTable;
+use crate::scan::scan_index;
+use crate::scan::scanner::Scanner;
+use crate::table_utils::pretty_size;
+use crate::trash::{self, failure_summary, TrashedItem};
+
+#[derive(Clone, Debug)]
+pub enum DuplicateMessage {
+    Progress(FindProgress),
+    Finished(Option<FindResult>),
+    PreferChanged,
+    TrashGroup,
+    TrashAll,
+    TrashFinished,
+    ExitDialog,
+}
+
+/// What the worker thread got done when trashing duplicates
+struct TrashOutcome {
+    total: usize,
+    trashed: Vec<(String, TrashedItem)>,
+    errors: Vec<String>,
+}
+
+/// A line in the result table, a group header or one of its files
+#[derive(Clone, Copy, Debug)]
+enum DuplicateRow {
+    Group(usize),
+    File(usize, usize),
+}
+
+impl DuplicateRow {
+    fn group(&self) -> usize {
+        match self {
+            DuplicateRow::Group(group) => *group,
+            DuplicateRow::File(group, _) => *group,
+        }
+    }
+}
+
+/// Finds files with the same content among the scanned files of all locations
+pub struct DuplicateDialog {
+    history: Arc<Mutex<History>>,
+    scanner: Scanner,
+    candidates: Vec<Candidate>,
+    locations: Vec<String>,
+    groups: Arc<Mutex<Vec<DuplicateGroup>>>,
+    rows: Arc<Mutex<Vec<DuplicateRow>>>,
+    prefer: Arc<Mutex<Option<String>>>,
+}
+
+impl DuplicateDialog {
+    pub fn new(
+        lens: Arc<Mutex<Lens>>,
+        db: Arc<Mutex<Connection>>,
+        history: Arc<Mutex<History>>,
+        scanner: Scanner,
+    ) -> Self {
+        // The view only holds what the filters let through, the stored scan has everything
+        let location_names: HashMap<i32, String> = lens
+            .lock()
+            .get_locations()
+            .iter()
+            .map(|l| (l.id, l.name.clone()))
+            .collect();
+        let location_ids: Vec<i32> = location_names.keys().copied().collect();
+
+        let entries = match scan_index::load_entries(&db.lock(), &location_ids) {
+            Ok(entries) => entries,
+            Err(err) => {
+                println!("Failed to load scanned files: {}", err);
+                Vec::new()
+            }
+        };
+
+        let mut candidates = Vec::new();
+        for (_, entry) in entries.iter() {
+            let location = location_names
+                .get(&entry.location_id)
+                .cloned()
+                .unwrap_or_default();
+
+            candidates.extend(entry.files.iter().map(|file| Candidate {
+                path: file.path.clone(),
+                size: file.size.max(0) as u64,
+                location: location.clone(),
+            }));
+        }
+
+        let mut locations: Vec<String> = location_names.into_values().collect();
+        locations.sort();
+
+        DuplicateDialog {
+            history,
+            scanner,
+            candidates,
+            locations,
+            groups: Arc::new(Mutex::new(Vec::new())),
+            rows: Arc::new(Mutex::new(Vec::new())),
+            prefer: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn show(&self) {
+        let (sender, reciever) = channel::<DuplicateMessage>();
+
+        let mut dialog = Window::new(300, 100, 700, 520, "Find Duplicates");
+        dialog.make_modal(true);
+
+        let mut choice_prefer = Choice::new(110, 10, 200, 25, "Prefer location");
+        choice_prefer.set_tooltip("Keep the copy in this location when there is one");
+        choice_prefer.add_choice("Any");
+        for location in self.locations.iter() {
+            choice_prefer.add_choice(&location.replace('/', "\\/"));
+        }
+        choice_prefer.set_value(0);
+
+        let mut frame_status = Frame::new(10, 40, 680, 20, None);
+        frame_status.set_label_size(12);
+        frame_status.set_align(Align::Left | Align::Inside | Align::Clip);
+
+        let headers = vec![
+            "Path".to_string(),
+            "Location".to_string(),
+            "Size".to_string(),
+            "Action".to_string(),
+        ];
+        let groups_c = self.groups.clone();
+        let rows_c = self.rows.clone();
+        let prefer_c = self.prefer.clone();
+        let mut table = LocationTable::new(
+            5,
+            65,
+            690,
+            410,
+            headers,
+            0,
+            Box::new(move |row, col| {
+                let groups = groups_c.lock();
+                let row = rows_c.lock().get(row as usize).copied();
+
+                match row {
+                    Some(DuplicateRow::Group(g)) => {
+                        let group = &groups[g];
+                        let text = match col {
+                            0 => format!("{} copies", group.files.len()),
+                            2 => pretty_size(group.size as i64),
+                            3 => format!("Reclaim {}", pretty_size(group.reclaimable() as i64)),
+                            _ => String::new(),
+                        };
+                        (text, Align::Left, Color::DarkBlue)
+                    }
+                    Some(DuplicateRow::File(g, f)) => {
+                        let group = &groups[g];
+                        let file = &group.files[f];
+                        let keep = group.pick_keeper(prefer_c.lock().as_deref()) == f;
+                        let color = if keep { Color::Gray0 } else { Color::DarkRed };
+
+                        match col {
+                            0 => (format!("    {}", file.path), Align::Left, color),
+                            1 => (file.location.clone(), Align::Left, color),
+                            2 => (pretty_size(file.size as i64), Align::Right, color),
+                            _ => {
+                                let action = if keep { "Keep" } else { "Trash" };
+                                (action.to_string(), Align::Center, color)
+                            }
+                        }
+                    }
+                    None => (String::new(), Align::Left, Color::Gray0),
+                }
+            }),
+        );
+
+        for (col, width) in [390, 110, 80, 90].iter().enumerate() {
+            table.set_col_width(col as i32, *width);
+        }
+
+        let mut but_trash_group = Button::new(10, 485, 170, 25, "Keep One, Trash Rest");
+        but_trash_group.set_tooltip("Trash all but one file of the selected group");
+        let mut but_trash_all = Button::new(190, 485, 150, 25, "Apply to All Groups");
+        let mut but_close = Button::new(620, 485, 70, 25, "Close");
+        but_trash_group.deactivate();
+        but_trash_all.deactivate();
+
+        dialog.end();
+        dialog.show();
+
+        let sender_c = sender.clone();
+        choice_prefer.set_callback(move |_| sender_c.send(DuplicateMessage::PreferChanged));
+        but_trash_group.emit(sender.clone(), DuplicateMessage::TrashGroup);
+        but_trash_all.emit(sender.clone(), DuplicateMessage::TrashAll);
+        but_close.emit(sender.clone(), DuplicateMessage::ExitDialog);
+        let sender_trash = sender.clone();
+        let mut trashing: Option<JoinHandle<TrashOutcome>> = None;
+
+        // Hashing runs on a worker thread so the dialog stays responsive
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancel_c = cancel.clone();
+        let candidates = self.candidates.clone();
+        let count = candidates.len();
+        thread::spawn(move || {
+            let result = duplicate_finder::find_duplicates(candidates, &cancel_c, &mut |p| {
+                sender.send(DuplicateMessage::Progress(p))
+            });
+            sender.send(DuplicateMessage::Finished(result));
+        });
+        frame_status.set_label(&format!("Comparing sizes of {} files", count));
+
+        while dialog.shown() {
+            while fltk::app::wait() {
+                if let Some(msg) = reciever.recv() {
+                    match msg {
+                        DuplicateMessage::Progress(progress) => {
+                            frame_status.set_label(&format!(
+                                "{}: {} of {} files",
+                                progress.stage.label(),
+                                progress.done,
+                                progress.total
+                            ));
+                        }
+                        DuplicateMessage::Finished(result) => {
+                            let result = match result {
+                                Some(result) => result,
+                                None => continue,
+                            };
+
+                            for err in result.errors.iter() {
+                                println!("Failed to read file for duplicates {}", err);
+                            }
+
+                            *self.groups.lock() = result.groups;
+                            self.update_rows(&mut table, &mut frame_status, result.errors.len());
+
+                            but_trash_group.activate();
+                            but_trash_all.activate();
+                        }
+                        DuplicateMessage::PreferChanged => {
+                            let ix = choice_prefer.value();
+                            *self.prefer.lock() = if ix > 0 {
+                                self.locations.get(ix as usize - 1).cloned()
+                            } else {
+                                None
+                            };
+                            table.redraw();
+                        }
+                        DuplicateMessage::TrashGroup => {
+                            let selected = get_selected_index(&mut table.wid);
+                            let group = {
+                                let rows = self.rows.lock();
+                                selected
+                                    .first()
+                                    .and_then(|ix| rows.get(*ix as usize))
+                                    .map(|r| r.group())
+                            };
+
+                            if let Some(group) = group {
+                                trashing = self.trash_duplicates(&[group], &sender_trash);
+                            }
+                            if trashing.is_some() {
+                                but_trash_group.deactivate();
+                                but_trash_all.deactivate();
+                                frame_status.set_label("Moving duplicates to the trash");
+                            }
+                        }
+                        DuplicateMessage::TrashAll => {
+                            let all: Vec<usize> = (0..self.groups.lock().len()).collect();
+                            trashing = self.trash_duplicates(&all, &sender_trash);
+                            if trashing.is_some() {
+                                but_trash_group.deactivate();
+                                but_trash_all.deactivate();
+                                frame_status.set_label("Moving duplicates to the trash");
+                            }
+                        }
+                        DuplicateMessage::TrashFinished => {
+                            if let Some(handle) = trashing.take() {
+                                self.trashed(handle);
+                            }
+                            self.update_rows(&mut table, &mut frame_status, 0);
+                            but_trash_group.activate();
+                            but_trash_all.activate();
+                        }
+                        DuplicateMessage::ExitDialog => {
+                            dialog.hide();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        cancel.store(true, Ordering::SeqCst);
+
+        // What was trashed still goes into the history when the dialog closes early
+        if let Some(handle) = trashing.take() {
+            self.trashed(handle);
+        }
+        println!("Exit duplicate dialog");
+    }
+
+    /// Rebuilds the table rows from the groups and sums up the reclaimable space
+    fn update_rows(&self, table: &mut LocationTable, frame_status: &mut Frame, errors: usize) {
+        let groups = self.groups.lock();
+        let mut rows = self.rows.lock();
+
+        rows.clear();
+        for (g, group) in groups.iter().enumerate() {
+            rows.push(DuplicateRow::Group(g));
+            rows.extend((0..group.files.len()).map(|f| DuplicateRow::File(g, f)));
+        }
+
+        let mut status = format!(
+            "{} duplicate sets, {} reclaimable",
+            groups.len(),
+            pretty_size(duplicate_finder::total_reclaimable(&groups) as i64)
+        );
+        if errors > 0 {
+            status.push_str(&format!(", {} files could not be read", errors));
+        }
+        frame_status.set_label(&status);
+
+        table.set_rows(rows.len() as i32);
+        table.redraw();
+    }
+
+    /// Trashes every file but the keeper in the groups after asking first. A trash on
+    /// another device is a copy, so it runs on a worker thread that sends `TrashFinished`.
+    fn trash_duplicates(
+        &self,
+        group_ixs: &[usize],
+        sender: &Sender<DuplicateMessage>,
+    ) -> Option<JoinHandle<TrashOutcome>> {
+        let prefer = self.prefer.lock().clone();
+
+        // The table draws from the groups, so they are not locked while a dialog is up
+        let doomed: Vec<String> = {
+            let groups = self.groups.lock();
+            group_ixs
+                .iter()
+                .filter_map(|g| groups.get(*g))
+                .flat_map(|group| {
+                    let keeper = group.pick_keeper(prefer.as_deref());
+                    group
+                        .files
+                        .iter()
+                        .enumerate()
+                        .filter(move |(f, _)| *f != keeper)
+                        .map(|(_, file)| file.path.clone())
+                })
+                .collect()
+        };
+
+        if doomed.is_empty() {
+            return None;
+        }
+
+        let dialog = ChoiceDialog::new(
+            format!(
+                "Move {} files to the trash, keeping one copy of each?",
+                doomed.len()
+            ),
+            vec!["Trash".to_string(), "Cancel".to_string()],
+        );
+        dialog.show();
+        if dialog.result() != 0 {
+            return None;
+        }
+
+        let sender = sender.clone();
+        Some(thread::spawn(move || {
+            let mut outcome = TrashOutcome {
+                total: doomed.len(),
+                trashed: Vec::new(),
+                errors: Vec::new(),
+            };
+
+            for path in doomed.into_iter() {
+                println!("Trash duplicate {}", path);
+                match trash::trash(Path::new(&path)) {
+                    Ok(item) => outcome.trashed.push((path, item)),
+                    Err(err) => outcome.errors.push(format!("{}: {}", path, err)),
+                }
+            }
+
+            sender.send(DuplicateMessage::TrashFinished);
+            outcome
+        }))
+    }
+
+    /// Drops the trashed files from the groups, has them rescanned and adds them to the history
+    fn trashed(&self, handle: JoinHandle<TrashOutcome>) {
+        let outcome = match handle.join() {
+            Ok(outcome) => outcome,
+            Err(_) => {
+                println!("Trashing duplicates failed");
+                return;
+            }
+        };
+
+        let paths: HashSet<&str> = outcome.trashed.iter().map(|(p, _)| p.as_str()).collect();
+        {
+            let mut groups = self.groups.lock();
+            for group in groups.iter_mut() {
+                group.files.retain(|f| !paths.contains(f.path.as_str()));
+            }
+            groups.retain(|g| g.files.len() > 1);
+        }
+
+        if !outcome.trashed.is_empty() {
+            self.scanner
+                .rescan_paths(outcome.trashed.iter().map(|(p, _)| Path::new(p.as_str())));
+
+            let items = outcome.trashed.into_iter().map(|(_, item)| item).collect();
+            self.history.lock().push(Operation::TrashFiles { items });
+        }
+
+        if !outcome.errors.is_empty() {
+            let err_dialog =
+                ErrorDialog::new(failure_summary("files", outcome.total, &outcome.errors));
+            err_dialog.show();
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Bytes hashed from both the start and the end of a file in the partial pass
+const PARTIAL_SIZE: u64 = 64 * 1024;
+
+/// A file that might have copies elsewhere
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub path: String,
+    pub size: u64,
+    /// Name of the location the file was found in
+    pub location: String,
+}
+
+/// Files with identical content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub files: Vec<Candidate>,
+}
+
+impl DuplicateGroup {
+    /// Space freed by keeping only one of the files
+    pub fn reclaimable(&self) -> u64 {
+        self.size * (self.files.len() as u64).saturating_sub(1)
+    }
+
+    /// Index of the file to keep, the first one in the preferred location if there is one
+    pub fn pick_keeper(&self, prefer_location: Option<&str>) -> usize {
+        prefer_location
+            .and_then(|loc| self.files.iter().position(|f| f.location == loc))
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Partial,
+    Full,
+}
+
+impl Stage {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Stage::Partial => "Comparing file starts and ends",
+            Stage::Full => "Comparing full contents",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FindProgress {
+    pub stage: Stage,
+    pub done: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FindResult {
+    pub groups: Vec<DuplicateGroup>,
+    /// Files that could not be read, they are left out of the groups
+    pub errors: Vec<String>,
+}
+
+pub fn total_reclaimable(groups: &[DuplicateGroup]) -> u64 {
+    groups.iter().map(|g| g.reclaimable()).sum()
+}
+
+fn hash_reader(reader: &mut impl Read, hasher: &mut blake3::Hasher) -> io::Result<()> {
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            return Ok(());
+        }
+        hasher.update(&buf[..read]);
+    }
+}
+
+/// Hashes the whole file, or only its start and end when `partial`
+fn hash_file(path: &Path, partial: bool) -> io::Result<blake3::Hash> {
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&len.to_le_bytes());
+
+    if partial && len > 2 * PARTIAL_SIZE {
+        hash_reader(&mut (&mut file).take(PARTIAL_SIZE), &mut hasher)?;
+        file.seek(SeekFrom::End(-(PARTIAL_SIZE as i64)))?;
+        hash_reader(&mut file, &mut hasher)?;
+    } else {
+        hash_reader(&mut file, &mut hasher)?;
+    }
+
+    Ok(hasher.finalize())
+}
+
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Files sharing a size, unique sizes and empty files are dropped. Paths to the same
+/// file, like hard links, count once since trashing one would free nothing.
+fn group_by_size(candidates: Vec<Candidate>, errors: &mut Vec<String>) -> Vec<Vec<Candidate>> {
+    let mut seen_ids = HashSet::new();
+    let mut seen_paths = HashSet::new();
+    let mut by_size: HashMap<u64, Vec<Candidate>> = HashMap::new();
+
+    for candidate in candidates {
+        if candidate.size == 0 || !seen_paths.insert(candidate.path.clone()) {
+            continue;
+        }
+
+        match fs::metadata(&candidate.path) {
+            Ok(meta) => {
+                if file_id(&meta).map(|id| seen_ids.insert(id)) == Some(false) {
+                    continue;
+                }
+            }
+            Err(err) => {
+                errors.push(format!("{}: {}", candidate.path, err));
+                continue;
+            }
+        }
+
+        by_size.entry(candidate.size).or_default().push(candidate);
+    }
+
+    by_size.into_values().filter(|g| g.len() > 1).collect()
+}
+
+/// Splits every group further by content hash, None when cancelled
+fn split_by_hash(
+    groups: Vec<Vec<Candidate>>,
+    stage: Stage,
+    cancel: &AtomicBool,
+    progress: &mut dyn FnMut(FindProgress),
+    errors: &mut Vec<String>,
+) -> Option<Vec<Vec<Candidate>>> {
+    let total = groups.iter().map(|g| g.len()).sum();
+    let mut done = 0;
+    let mut result = Vec::new();
+
+    for group in groups {
+        let mut by_hash: HashMap<blake3::Hash, Vec<Candidate>> = HashMap::new();
+
+        for candidate in group {
+            if cancel.load(Ordering::SeqCst) {
+                return None;
+            }
+
+            match hash_file(Path::new(&candidate.path), stage == Stage::Partial) {
+                Ok(hash) => by_hash.entry(hash).or_default().push(candidate),
+                Err(err) => errors.push(format!("{}: {}", candidate.path, err)),
+            }
+
+            done += 1;
+            progress(FindProgress { stage, done, total });
+        }
+
+        result.extend(by_hash.into_values().filter(|g| g.len() > 1));
+    }
+
+    Some(result)
+}
+
+/// Groups files by size, then by a hash of their start and end, and last by a
+/// hash of the full content. Only files that still share a group are read in full.
+/// Returns None when cancelled.
+pub fn find_duplicates(
+    candidates: Vec<Candidate>,
+    cancel: &AtomicBool,
+    progress: &mut dyn FnMut(FindProgress),
+) -> Option<FindResult> {
+    let mut errors = Vec::new();
+
+    let by_size = group_by_size(candidates, &mut errors);
+    let by_partial = split_by_hash(by_size, Stage::Partial, cancel, progress, &mut errors)?;
+
+    // The partial hash already covered the whole of small files
+    let (small, large): (Vec<_>, Vec<_>) = by_partial
+        .into_iter()
+        .partition(|g| g[0].size <= 2 * PARTIAL_SIZE);
+    let mut by_full = split_by_hash(large, Stage::Full, cancel, progress, &mut errors)?;
+    by_full.extend(small);
+
+    let mut groups: Vec<DuplicateGroup> = by_full
+        .into_iter()
+        .map(|mut files| {
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            DuplicateGroup {
+                size: files[0].size,
+                files,
+            }
+        })
+        .collect();
+
+    groups.sort_by(|a, b| {
+        b.reclaimable()
+            .cmp(&a.reclaimable())
+            .then_with(|| a.files[0].path.cmp(&b.files[0].path))
+    });
+
+    Some(FindResult { groups, errors })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    fn candidate(dir: &Path, name: &str, location: &str, content: &[u8]) -> Candidate {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        Candidate {
+            path: path.to_string_lossy().to_string(),
+            size: content.len() as u64,
+            location: location.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let dir = temp_dir("duplicates_find");
+
+        // Large files with equal start and end that differ in the middle
+        let big_a = vec![7u8; 3 * PARTIAL_SIZE as usize];
+        let big_b = big_a.clone();
+        let mut big_c = big_a.clone();
+        big_c[PARTIAL_SIZE as usize + 10] = 1;
+
+        let candidates = vec![
+            candidate(&dir, "a.txt", "Movies", b"same content"),
+            candidate(&dir, "b.txt", "Backup", b"same content"),
+            candidate(&dir, "c.txt", "Backup", b"diff content"),
+            candidate(&dir, "unique.txt", "Movies", b"only one of this size"),
+            candidate(&dir, "empty1.txt", "Movies", b""),
+            candidate(&dir, "empty2.txt", "Backup", b""),
+            candidate(&dir, "big_a.bin", "Movies", &big_a),
+            candidate(&dir, "big_b.bin", "Backup", &big_b),
+            candidate(&dir, "big_c.bin", "Backup", &big_c),
+        ];
+
+        let mut stages = Vec::new();
+        let cancel = AtomicBool::new(false);
+        let result = find_duplicates(candidates, &cancel, &mut |p| stages.push(p.stage)).unwrap();
+
+        assert!(result.errors.is_empty());
+        assert_eq!(result.groups.len(), 2);
+
+        let big = &result.groups[0];
+        assert_eq!(big.files.len(), 2);
+        assert!(big.files[0].path.ends_with("big_a.bin"));
+        assert!(big.files[1].path.ends_with("big_b.bin"));
+        assert_eq!(big.reclaimable(), 3 * PARTIAL_SIZE);
+
+        let small = &result.groups[1];
+        assert!(small.files[0].path.ends_with("a.txt"));
+        assert!(small.files[1].path.ends_with("b.txt"));
+        assert_eq!(
+            total_reclaimable(&result.groups),
+            3 * PARTIAL_SIZE + "same content".len() as u64
+        );
+
+        // Only the three large files are read in full
+        assert_eq!(stages.iter().filter(|s| **s == Stage::Full).count(), 3);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_cancel_and_missing_files() {
+        let dir = temp_dir("duplicates_cancel");
+        let mut missing = candidate(&dir, "gone.txt", "Movies", b"12345");
+        missing.path = dir.join("not_there.txt").to_string_lossy().to_string();
+        let candidates = vec![
+            candidate(&dir, "a.txt", "Movies", b"12345"),
+            candidate(&dir, "b.txt", "Movies", b"12345"),
+            missing,
+        ];
+
+        let cancel = AtomicBool::new(true);
+        assert!(find_duplicates(candidates.clone(), &cancel, &mut |_| {}).is_none());
+
+        cancel.store(false, Ordering::SeqCst);
+        let result = find_duplicates(candidates, &cancel, &mut |_| {}).unwrap();
+        assert_eq!(result.groups.len(), 1);
+        assert_eq!(result.errors.len(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_links_count_once() {
+        let dir = temp_dir("duplicates_links");
+        let a = candidate(&dir, "a.txt", "Movies", b"12345");
+        fs::hard_link(dir.join("a.txt"), dir.join("link.txt")).unwrap();
+        let link = Candidate {
+            path: dir.join("link.txt").to_string_lossy().to_string(),
+            ..a.clone()
+        };
+
+        let cancel = AtomicBool::new(false);
+        let result = find_duplicates(vec![a.clone(), link.clone()], &cancel, &mut |_| {});
+        assert!(result.unwrap().groups.is_empty());
+
+        let copy = candidate(&dir, "copy.txt", "Backup", b"12345");
+        let result = find_duplicates(vec![a, link, copy], &cancel, &mut |_| {}).unwrap();
+        assert_eq!(result.groups.len(), 1);
+        assert_eq!(result.groups[0].files.len(), 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pick_keeper() {
+        let file = |path: &str, location: &str| Candidate {
+            path: path.to_string(),
+            size: 10,
+            location: location.to_string(),
+        };
+        let group = DuplicateGroup {
+            size: 10,
+            files: vec![
+                file("/a/x.mkv", "Backup"),
+                file("/b/x.mkv", "Movies"),
+                file("/c/x.mkv", "Movies"),
+            ],
+        };
+
+        assert_eq!(group.reclaimable(), 20);
+        assert_eq!(group.pick_keeper(None), 0);
+        assert_eq!(group.pick_keeper(Some("Movies")), 1);
+        assert_eq!(group.pick_keeper(Some("Elsewhere")), 0);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod duplicate_dialog;
+pub mod duplicate_finder;
+pub mod similar_dialog;
+pub mod similar_names;
@@ -0,0 +1,216 @@
+use fltk::app::{channel, Sender};
+use fltk::enums::{Align, Color};
+use fltk::frame::Frame;
+use fltk::{button::*, window::*};
+
+use fltk::prelude::*;
+
+use parking_lot::Mutex;
+use serious_organizer_lib::lens::Lens;
+use serious_organizer_lib::models::Entry;
+use std::sync::Arc;
+
+use crate::delete_dialog::{self, DeleteTarget};
+use crate::duplicates::similar_names::{self, SIMILARITY};
+use crate::get_selected_index;
+use crate::grade_store::GradeStore;
+use crate::history::journal::History;
+use crate::location::location_table::LocationTable;
+use crate::model::message::Message;
+use crate::scan::scanner::Scanner;
+use crate::search::entry_filter::Locations;
+use crate::table_utils::{pretty_grade, pretty_size};
+use crate::trash::DeleteMode;
+
+#[derive(Clone, Debug)]
+pub enum SimilarMessage {
+    DeleteSelected,
+    ExitDialog,
+}
+
+/// A line in the result table, a cluster header or one of its entries with its grade
+#[derive(Clone, Debug)]
+enum SimilarRow {
+    Cluster(String, usize),
+    Entry(Entry, Option<i32>),
+}
+
+/// Lists entries in the current view whose names suggest the same release
+pub struct SimilarDialog {
+    lens: Arc<Mutex<Lens>>,
+    grades: Arc<Mutex<GradeStore>>,
+    history: Arc<Mutex<History>>,
+    scanner: Scanner,
+    app_sender: Sender<Message>,
+    delete_mode: DeleteMode,
+    rows: Arc<Mutex<Vec<SimilarRow>>>,
+}
+
+impl SimilarDialog {
+    pub fn new(
+        lens: Arc<Mutex<Lens>>,
+        grades: Arc<Mutex<GradeStore>>,
+        history: Arc<Mutex<History>>,
+        scanner: Scanner,
+        app_sender: Sender<Message>,
+        delete_mode: DeleteMode,
+    ) -> Self {
+        SimilarDialog {
+            lens,
+            grades,
+            history,
+            scanner,
+            app_sender,
+            delete_mode,
+            rows: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn show(&self) {
+        let (sender, reciever) = channel::<SimilarMessage>();
+
+        let mut dialog = Window::new(300, 100, 700, 480, "Similar Names");
+        dialog.make_modal(true);
+
+        let mut frame_status = Frame::new(10, 10, 680, 20, None);
+        frame_status.set_label_size(12);
+        frame_status.set_align(Align::Left | Align::Inside | Align::Clip);
+
+        let headers = vec![
+            "Name".to_string(),
+            "Location".to_string(),
+            "Size".to_string(),
+            "Grade".to_string(),
+            "Path".to_string(),
+        ];
+        let locations = Locations::new(&self.lens.lock());
+        let rows_c = self.rows.clone();
+        let mut table = LocationTable::new(
+            5,
+            35,
+            690,
+            400,
+            headers,
+            0,
+            Box::new(move |row, col| {
+                let rows = rows_c.lock();
+
+                match rows.get(row as usize) {
+                    Some(SimilarRow::Cluster(name, count)) => {
+                        let text = match col {
+                            0 => format!("{} ({} entries)", name, count),
+                            _ => String::new(),
+                        };
+                        (text, Align::Left, Color::DarkBlue)
+                    }
+                    Some(SimilarRow::Entry(entry, grade)) => match col {
+                        0 => (format!("    {}", entry.name), Align::Left, Color::Gray0),
+                        1 => (
+                            locations.name_of(&entry.path).to_string(),
+                            Align::Left,
+                            Color::Gray0,
+                        ),
+                        2 => (pretty_size(entry.size), Align::Right, Color::Gray0),
+                        3 => (pretty_grade(*grade), Align::Center, Color::Gray0),
+                        _ => (entry.path.clone(), Align::Left, Color::Gray0),
+                    },
+                    None => (String::new(), Align::Left, Color::Gray0),
+                }
+            }),
+        );
+
+        for (col, width) in [250, 90, 70, 45, 220].iter().enumerate() {
+            table.set_col_width(col as i32, *width);
+        }
+
+        let mut but_delete = Button::new(10, 445, 130, 25, "Delete Selected");
+        but_delete.set_tooltip("Trash or delete the selected entries");
+        let mut but_close = Button::new(620, 445, 70, 25, "Close");
+
+        dialog.end();
+        dialog.show();
+
+        but_delete.emit(sender.clone(), SimilarMessage::DeleteSelected);
+        but_close.emit(sender, SimilarMessage::ExitDialog);
+
+        self.update_rows(&mut table, &mut frame_status);
+
+        while dialog.shown() {
+            while fltk::app::wait() {
+                if let Some(msg) = reciever.recv() {
+                    match msg {
+                        SimilarMessage::DeleteSelected => {
+                            let selected = get_selected_index(&mut table.wid);
+                            let entries: Vec<Entry> = {
+                                let rows = self.rows.lock();
+                                selected
+                                    .iter()
+                                    .filter_map(|ix| match rows.get(*ix as usize) {
+                                        Some(SimilarRow::Entry(entry, _)) => Some(entry.clone()),
+                                        _ => None,
+                                    })
+                                    .collect()
+                            };
+
+                            if entries.is_empty() {
+                                continue;
+                            }
+
+                            delete_dialog::delete(
+                                DeleteTarget::Entries(entries),
+                                self.lens.clone(),
+                                &self.history,
+                                &self.scanner,
+                                &self.app_sender,
+                                self.delete_mode,
+                            );
+                            self.update_rows(&mut table, &mut frame_status);
+                        }
+                        SimilarMessage::ExitDialog => {
+                            dialog.hide();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        println!("Exit similar names dialog");
+    }
+
+    /// Clusters the names of the entries in the current view again
+    fn update_rows(&self, table: &mut LocationTable, frame_status: &mut Frame) {
+        let entries: Vec<(Entry, Option<i32>)> = {
+            let lens = self.lens.lock();
+            let grades = self.grades.lock();
+            (0..lens.get_dir_count())
+                .filter_map(|ix| lens.get_dir_entry(ix))
+                .map(|e| (e.clone(), grades.grade(e)))
+                .collect()
+        };
+
+        let names: Vec<&str> = entries.iter().map(|(e, _)| e.name.as_str()).collect();
+        let clusters = similar_names::clusters(&names, SIMILARITY);
+
+        let mut rows = self.rows.lock();
+        rows.clear();
+        for cluster in clusters.iter() {
+            let key = similar_names::normalize(&entries[cluster[0]].0.name);
+            rows.push(SimilarRow::Cluster(key.display(), cluster.len()));
+            rows.extend(
+                cluster
+                    .iter()
+                    .map(|ix| SimilarRow::Entry(entries[*ix].0.clone(), entries[*ix].1)),
+            );
+        }
+
+        frame_status.set_label(&format!(
+            "{} groups of similar names among {} entries",
+            clusters.len(),
+            entries.len()
+        ));
+
+        table.set_rows(rows.len() as i32);
+        table.redraw();
+    }
+}
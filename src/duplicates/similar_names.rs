@@ -0,0 +1,246 @@
+use std::collections::{BTreeSet, HashMap};
+
+/// Names at least this similar are listed as likely duplicates
+pub const SIMILARITY: f64 = 0.75;
+
+/// Words that only describe a release, everything from the first one on is dropped
+const RELEASE_TAGS: [&str; 52] = [
+    "480p", "576p", "720p", "1080p", "1080i", "2160p", "4k", "uhd", "x264", "x265", "h264", "h265",
+    "hevc", "avc", "xvid", "divx", "bluray", "bdrip", "brrip", "webrip", "web", "webdl", "hdtv",
+    "dvdrip", "dvd", "remux", "hdr", "hdr10", "10bit", "8bit", "aac", "ac3", "dts", "ddp5", "dd5",
+    "atmos", "truehd", "proper", "repack", "extended", "unrated", "internal", "limited", "multi",
+    "mkv", "mp4", "avi", "m4v", "wmv", "mov", "mpg", "webm",
+];
+
+/// Articles are left out so "The Film" and "Film" compare equal
+const ARTICLES: [&str; 3] = ["the", "a", "an"];
+
+/// Comparable form of an entry name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameKey {
+    pub words: BTreeSet<String>,
+    pub year: Option<u32>,
+}
+
+fn as_year(token: &str) -> Option<u32> {
+    if token.len() != 4 {
+        return None;
+    }
+    token
+        .parse()
+        .ok()
+        .filter(|year| (1900..=2099).contains(year))
+}
+
+/// Removes [bracketed] parts, they hold release groups and tags
+fn strip_brackets(name: &str) -> String {
+    let mut depth = 0;
+    name.chars()
+        .filter_map(|c| match c {
+            '[' => {
+                depth += 1;
+                Some(' ')
+            }
+            ']' if depth > 0 => {
+                depth -= 1;
+                Some(' ')
+            }
+            c if depth == 0 => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Lower cased words with separators, brackets, articles and release tags removed.
+/// A year after the title is kept apart from the words.
+pub fn normalize(name: &str) -> NameKey {
+    let stripped = strip_brackets(name).to_lowercase();
+    let tokens: Vec<&str> = stripped
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let end = tokens
+        .iter()
+        .skip(1)
+        .position(|t| RELEASE_TAGS.contains(t))
+        .map(|ix| ix + 1)
+        .unwrap_or(tokens.len());
+
+    let mut words = BTreeSet::new();
+    let mut year = None;
+    for (ix, token) in tokens[..end].iter().enumerate() {
+        // A leading year is part of the title, as in "1917"
+        match as_year(token) {
+            Some(y) if ix > 0 && year.is_none() => year = Some(y),
+            _ if ARTICLES.contains(token) => (),
+            _ => {
+                words.insert(token.to_string());
+            }
+        }
+    }
+
+    // A title of only articles is still a title
+    if words.is_empty() {
+        words.extend(
+            tokens[..end]
+                .iter()
+                .filter(|t| ARTICLES.contains(t))
+                .map(|t| t.to_string()),
+        );
+    }
+
+    NameKey { words, year }
+}
+
+impl NameKey {
+    /// Shared words over all words, zero when both have a year and they differ
+    pub fn similarity(&self, other: &NameKey) -> f64 {
+        if let (Some(a), Some(b)) = (self.year, other.year) {
+            if a != b {
+                return 0.0;
+            }
+        }
+
+        let shared = self.words.intersection(&other.words).count();
+        let total = self.words.union(&other.words).count();
+        if total == 0 {
+            return 0.0;
+        }
+
+        shared as f64 / total as f64
+    }
+
+    /// Words and year joined, as shown for a cluster
+    pub fn display(&self) -> String {
+        let mut text = self.words.iter().cloned().collect::<Vec<_>>().join(" ");
+        if let Some(year) = self.year {
+            text.push_str(&format!(" ({})", year));
+        }
+        text
+    }
+}
+
+fn find_root(parents: &mut [usize], ix: usize) -> usize {
+    let mut root = ix;
+    while parents[root] != root {
+        root = parents[root];
+    }
+
+    // Point the whole chain at the root
+    let mut ix = ix;
+    while parents[ix] != root {
+        let next = parents[ix];
+        parents[ix] = root;
+        ix = next;
+    }
+    root
+}
+
+/// Groups of name indices that are at least `threshold` similar to another name
+/// in the group. Only names sharing a word are compared, so words that most names
+/// have, like "the", do not make every pair a candidate.
+pub fn clusters(names: &[&str], threshold: f64) -> Vec<Vec<usize>> {
+    const COMMON_WORD: usize = 256;
+
+    let keys: Vec<NameKey> = names.iter().map(|n| normalize(n)).collect();
+
+    let mut by_word: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (ix, key) in keys.iter().enumerate() {
+        for word in key.words.iter() {
+            by_word.entry(word.as_str()).or_default().push(ix);
+        }
+    }
+
+    let mut parents: Vec<usize> = (0..keys.len()).collect();
+    for ixs in by_word.values().filter(|ixs| ixs.len() < COMMON_WORD) {
+        for (n, a) in ixs.iter().enumerate() {
+            for b in ixs[n + 1..].iter() {
+                let (ra, rb) = (find_root(&mut parents, *a), find_root(&mut parents, *b));
+                if ra != rb && keys[*a].similarity(&keys[*b]) >= threshold {
+                    parents[rb] = ra;
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for ix in 0..keys.len() {
+        let root = find_root(&mut parents, ix);
+        groups.entry(root).or_default().push(ix);
+    }
+
+    let mut result: Vec<Vec<usize>> = groups.into_values().filter(|g| g.len() > 1).collect();
+    for group in result.iter_mut() {
+        group.sort_unstable();
+    }
+    result.sort_unstable_by_key(|g| g[0]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(key: &NameKey) -> Vec<&str> {
+        key.words.iter().map(|w| w.as_str()).collect()
+    }
+
+    #[test]
+    fn test_normalize() {
+        let key = normalize("Movie.Name.2019.1080p.BluRay.x264-GROUP");
+        assert_eq!(words(&key), vec!["movie", "name"]);
+        assert_eq!(key.year, Some(2019));
+
+        let key = normalize("Movie Name (2019)");
+        assert_eq!(words(&key), vec!["movie", "name"]);
+        assert_eq!(key.year, Some(2019));
+
+        let key = normalize("[Group] Some_Show - 01 [720p].mkv");
+        assert_eq!(words(&key), vec!["01", "show", "some"]);
+        assert_eq!(key.year, None);
+
+        // Leading years and tags are titles
+        let key = normalize("1917.2019.WEB-DL");
+        assert_eq!(words(&key), vec!["1917"]);
+        assert_eq!(key.year, Some(2019));
+        assert_eq!(words(&normalize("Web")), vec!["web"]);
+        assert_eq!(words(&normalize("The.Film")), vec!["film"]);
+        assert_eq!(words(&normalize("The The")), vec!["the"]);
+
+        assert_eq!(
+            normalize("Movie Name (2019)").display(),
+            "movie name (2019)"
+        );
+    }
+
+    #[test]
+    fn test_similarity() {
+        let a = normalize("Movie.Name.2019.1080p");
+        assert_eq!(a.similarity(&normalize("Movie Name (2019)")), 1.0);
+        assert_eq!(a.similarity(&normalize("Movie Name")), 1.0);
+        assert_eq!(a.similarity(&normalize("Movie Name (1984)")), 0.0);
+        assert_eq!(a.similarity(&normalize("Movie Game 2019")), 1.0 / 3.0);
+        assert_eq!(normalize("").similarity(&normalize("")), 0.0);
+    }
+
+    #[test]
+    fn test_clusters() {
+        let names = [
+            "Movie.Name.2019.1080p",
+            "Other Film",
+            "Movie Name (2019)",
+            "The.Other.Film.720p",
+            "Unrelated",
+            "[x] Movie Name 2019 [HEVC]",
+            "Movie Name (1984)",
+        ];
+
+        let found = clusters(&names, SIMILARITY);
+        assert_eq!(found, vec![vec![0, 2, 5], vec![1, 3]]);
+
+        assert_eq!(clusters(&names, 1.0), found);
+        assert!(clusters(&names[..2], SIMILARITY).is_empty());
+        assert!(clusters(&[], SIMILARITY).is_empty());
+    }
+}
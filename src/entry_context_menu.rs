@@ -3,6 +3,7 @@ use parking_lot::Mutex;
 use serious_organizer_lib::models::Entry;
 
 use std::fs::metadata;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use fltk::prelude::*;
@@ -13,13 +14,24 @@ use serious_organizer_lib::lens::Lens;
 use crate::model::message::Message;
 
 use crate::choice_dialog::ChoiceDialog;
+use crate::delete_dialog::DeleteTarget;
 use crate::error_dialog::ErrorDialog;
+use crate::grade_store::GradeStore;
+use crate::history::journal::{History, Operation};
 use crate::label::entry_label_dialog;
+use crate::rename::batch_rename_dialog::{BatchRenameDialog, RenameTarget};
 use crate::rename_dialog::RenameDialog;
+use crate::scan::scanner::Scanner;
+use crate::table_utils::Grade;
+use crate::transfer::entry_transfer::{self, entry_meta, TransferMode, TransferOrigin};
+use crate::transfer::transfer_dialog::ask_conflict;
 
 pub fn show_entry_context_menu(
     selection: Vec<u32>,
     lens: Arc<Mutex<Lens>>,
+    grades: Arc<Mutex<GradeStore>>,
+    history: Arc<Mutex<History>>,
+    scanner: Scanner,
     sender: Sender<Message>,
     wind: &mut Window,
 ) {
@@ -34,16 +46,7 @@ pub fn show_entry_context_menu(
                 .clone()
         };
 
-        let mut entries = Vec::new();
-        {
-            let lens = lens.lock();
-            // Get selected entries
-            for ix in selection.iter() {
-                if let Some(dir_entry) = lens.get_dir_entry(*ix as usize) {
-                    entries.push(dir_entry.clone());
-                }
-            }
-        }
+        let entries = get_selected_entries(&selection, &lens);
 
         let has_file = entries.iter().any(|e| {
             let meta = if let Ok(meta) = metadata(&e.path) {
@@ -62,9 +65,27 @@ pub fn show_entry_context_menu(
         });
 
         let choices = if has_file {
-            vec!["Label >", "Delete Entry", "Rename Entry", "Grade", "Move to Dir"]
+            vec![
+                "Label >",
+                "Delete Entry",
+                "Rename Entry",
+                "Batch Rename",
+                "Grade",
+                "Move to Dir",
+                "Move to location...",
+                "Copy to location...",
+            ]
         } else {
-            vec!["Label >", "Delete Entry", "Rename Entry", "Grade", "Open dir"]
+            vec![
+                "Label >",
+                "Delete Entry",
+                "Rename Entry",
+                "Batch Rename",
+                "Grade",
+                "Open dir",
+                "Move to location...",
+                "Copy to location...",
+            ]
         };
 
         let x = MenuItem::new(&choices);
@@ -80,21 +101,35 @@ pub fn show_entry_context_menu(
                         println!("Got entries: {:?}", entries);
 
                         // Label select dialog
-                        let dialog = entry_label_dialog::EntryLabelDialog::new(lens, entries);
+                        let dialog =
+                            entry_label_dialog::EntryLabelDialog::new(lens, entries, history);
 
                         wind.deactivate();
                         dialog.show();
                         wind.activate();
                         sender.send(Message::EntryTableInvalidated);
+                        sender.send(Message::HistoryChanged);
                     }
                     "Delete Entry" => {
-                        delete_entry(entries, lens);
-                        sender.send(Message::EntryTableInvalidated);
+                        sender.send(Message::Delete(DeleteTarget::Entries(entries)));
                     }
                     "Rename Entry" => {
-                        let dialog = RenameDialog::new(lens, entry);
+                        let dialog = RenameDialog::new(lens, grades, entry, history, scanner);
                         dialog.show();
                         sender.send(Message::EntryTableInvalidated);
+                        sender.send(Message::HistoryChanged);
+                    }
+                    "Batch Rename" => {
+                        let dialog = BatchRenameDialog::new(
+                            lens,
+                            grades,
+                            RenameTarget::Entries(entries),
+                            history,
+                            scanner,
+                        );
+                        dialog.show();
+                        sender.send(Message::EntryTableInvalidated);
+                        sender.send(Message::HistoryChanged);
                     }
                     "Move to Dir" => {
                         let dialog = ChoiceDialog::new(
@@ -104,6 +139,7 @@ pub fn show_entry_context_menu(
 
                         dialog.show();
                         if dialog.result() == 0 {
+                            let mut moves = Vec::new();
                             {
                                 let mut lens = lens.lock();
                                 for e in entries.iter() {
@@ -114,23 +150,48 @@ pub fn show_entry_context_menu(
                                                 println!("Error while renaming file: {:?}", err);
                                                 let err_dialog = ErrorDialog::new(err.to_string());
                                                 err_dialog.show();
+                                            } else {
+                                                let dir_path = dir_for_file(&e.path);
+                                                if let Err(err) =
+                                                    grades.lock().move_path(&e.path, &dir_path)
+                                                {
+                                                    println!("{}", err);
+                                                }
+                                                moves.push((e.path.clone(), dir_path));
                                             }
                                         }
                                     }
                                 }
                             }
+                            if !moves.is_empty() {
+                                scanner.rescan_paths(moves.iter().flat_map(|(file, dir)| {
+                                    [Path::new(file.as_str()), Path::new(dir.as_str())]
+                                }));
+                                history.lock().push(Operation::MoveToDir { moves });
+                            }
                             sender.send(Message::EntryTableInvalidated);
+                            sender.send(Message::HistoryChanged);
                         } else {
                             println!("Abort dir move");
                         }
                     }
+                    "Move to location..." => {
+                        transfer_entries(&entries, TransferMode::Move, &lens, &grades, &sender)
+                    }
+                    "Copy to location..." => {
+                        transfer_entries(&entries, TransferMode::Copy, &lens, &grades, &sender)
+                    }
                     "Open dir" => {
                         if let Err(e) = open::that(&entry.path) {
                             eprintln !("Failed to open path: {} Error {}", entry.path, e);
                         }
                     }
                     "Grade" => {
-                     // Show grade choices
+                        if let Some(grade) = show_grade_menu() {
+                            set_grade(&entries, grade, &grades, &history);
+                            sender.send(Message::EntryTableInvalidated);
+                            sender.send(Message::HistoryChanged);
+                        }
                     }
                     _ => {
                         println!("Unknown popup string: {}", val.label().unwrap())
@@ -141,27 +202,123 @@ pub fn show_entry_context_menu(
     }
 }
 
-fn delete_entry(entries: Vec<Entry>, lens: Arc<Mutex<Lens>>) {
-    if !show_delete_confirmation_dialog(entries.len()) {
-        return;
+pub fn get_selected_entries(selection: &[u32], lens: &Arc<Mutex<Lens>>) -> Vec<Entry> {
+    let lens = lens.lock();
+
+    selection
+        .iter()
+        .filter_map(|ix| lens.get_dir_entry(*ix as usize))
+        .cloned()
+        .collect()
+}
+
+const CLEAR_GRADE: &str = "Clear grade";
+
+/// Shows the grade choices, returns Some(None) if the grade should be cleared
+fn show_grade_menu() -> Option<Option<Grade>> {
+    let mut choices: Vec<&str> = Grade::ALL.iter().map(|g| g.label()).collect();
+    choices.push(CLEAR_GRADE);
+
+    let menu = MenuItem::new(&choices);
+    let label = menu.popup(app::event_x(), app::event_y())?.label()?;
+
+    if label == CLEAR_GRADE {
+        Some(None)
+    } else {
+        Grade::from_label(&label).map(Some)
     }
+}
 
-    println!("Delete the entry {:?}", entries);
+pub fn set_grade(
+    entries: &[Entry],
+    grade: Option<Grade>,
+    grades: &Arc<Mutex<GradeStore>>,
+    history: &Arc<Mutex<History>>,
+) {
+    println!("Set grade {:?} on {} entries", grade, entries.len());
 
-    let mut lens = lens.lock();
+    let grade = grade.map(|g| g as i32);
+    let result = {
+        let mut grades = grades.lock();
+        let changed: Vec<(String, Option<i32>)> = entries
+            .iter()
+            .map(|e| (e.path.clone(), grades.grade(e)))
+            .collect();
+        let graded: Vec<(String, Option<i32>)> =
+            entries.iter().map(|e| (e.path.clone(), grade)).collect();
+        grades.set_all(&graded).map(|_| changed)
+    };
 
-    for entry in entries.iter() {
-        lens.remove_entry(entry)
-            .unwrap_or_else(|_| panic!("Failed to remove entry {}", entry.name));
+    match result {
+        Ok(changed) => {
+            if !changed.is_empty() {
+                history.lock().push(Operation::SetGrade {
+                    entries: changed,
+                    grade,
+                });
+            }
+        }
+        Err(err) => {
+            println!("{}", err);
+            let err_dialog = ErrorDialog::new(err);
+            err_dialog.show();
+        }
     }
 }
 
-fn show_delete_confirmation_dialog(count: usize) -> bool {
-    let dialog = ChoiceDialog::new(
-        format!("Are you sure you want to remove {} entries?", count),
-        vec!["Ok".to_string(), "Cancel".to_string()],
-    );
-    dialog.show();
+/// Asks for the target location and how to handle name conflicts, the transfer itself
+/// runs in the background once the main loop gets the message
+fn transfer_entries(
+    entries: &[Entry],
+    mode: TransferMode,
+    lens: &Arc<Mutex<Lens>>,
+    grades: &Arc<Mutex<GradeStore>>,
+    sender: &Sender<Message>,
+) {
+    let locations: Vec<(String, String)> = lens
+        .lock()
+        .get_locations()
+        .iter()
+        .map(|l| (l.name.to_string(), l.path.to_string()))
+        .collect();
+
+    let names: Vec<&str> = locations.iter().map(|l| l.0.as_str()).collect();
+    let menu = MenuItem::new(&names);
+
+    let label = match menu.popup(app::event_x(), app::event_y()) {
+        Some(item) => item.label(),
+        None => return,
+    };
+    let dir = match locations.iter().find(|l| Some(&l.0) == label.as_ref()) {
+        Some((_, path)) => PathBuf::from(path),
+        None => return,
+    };
+
+    let sources: Vec<_> = {
+        let lens = lens.lock();
+        let grades = grades.lock();
+        entries
+            .iter()
+            .map(|e| (PathBuf::from(&e.path), entry_meta(&lens, &grades, &e.path)))
+            .collect()
+    };
+
+    match entry_transfer::plan(sources, &dir, &mut ask_conflict) {
+        Ok(items) if !items.is_empty() => {
+            sender.send(Message::StartTransfer(mode, TransferOrigin::Entries, items))
+        }
+        Ok(_) => println!("Nothing to {:?}", mode),
+        Err(err) => {
+            let err_dialog = ErrorDialog::new(err);
+            err_dialog.show();
+        }
+    }
+}
 
-    dialog.result() == 0
+/// The dir `move_file_entry_to_dir_entry` moves a file into, named after the file without extension
+fn dir_for_file(file_path: &str) -> String {
+    Path::new(file_path)
+        .with_extension("")
+        .to_string_lossy()
+        .to_string()
 }
@@ -0,0 +1,190 @@
+use fltk::app::{self, Sender};
+use fltk::enums::Key;
+use fltk::prelude::*;
+use fltk::window::Window;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use serious_organizer_lib::lens::Lens;
+
+use crate::delete_dialog::DeleteTarget;
+use crate::entry_context_menu::{get_selected_entries, set_grade};
+use crate::grade_store::GradeStore;
+use crate::history::journal::History;
+use crate::label::entry_label_dialog::EntryLabelDialog;
+use crate::model::message::Message;
+use crate::rename::batch_rename_dialog::{BatchRenameDialog, RenameTarget};
+use crate::rename_dialog::RenameDialog;
+use crate::scan::scanner::Scanner;
+use crate::table_utils::Grade;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryAction {
+    Grade(Grade),
+    Open,
+    Delete,
+    Rename,
+    Label,
+    Navigate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Char(char),
+    Key(Key),
+}
+
+/// All keyboard bindings for the entry table
+pub const ENTRY_BINDINGS: [(Binding, EntryAction); 18] = [
+    (Binding::Char('s'), EntryAction::Grade(Grade::S)),
+    (Binding::Char('a'), EntryAction::Grade(Grade::A)),
+    (Binding::Char('b'), EntryAction::Grade(Grade::B)),
+    (Binding::Char('c'), EntryAction::Grade(Grade::C)),
+    (Binding::Char('d'), EntryAction::Grade(Grade::D)),
+    (Binding::Char('e'), EntryAction::Grade(Grade::E)),
+    (Binding::Char('f'), EntryAction::Grade(Grade::F)),
+    (Binding::Char('l'), EntryAction::Label),
+    (Binding::Key(Key::Enter), EntryAction::Open),
+    (Binding::Key(Key::KPEnter), EntryAction::Open),
+    (Binding::Key(Key::Delete), EntryAction::Delete),
+    (Binding::Key(Key::F2), EntryAction::Rename),
+    (Binding::Key(Key::Up), EntryAction::Navigate),
+    (Binding::Key(Key::Down), EntryAction::Navigate),
+    (Binding::Key(Key::PageUp), EntryAction::Navigate),
+    (Binding::Key(Key::PageDown), EntryAction::Navigate),
+    (Binding::Key(Key::Home), EntryAction::Navigate),
+    (Binding::Key(Key::End), EntryAction::Navigate),
+];
+
+impl Binding {
+    fn matches(&self, key: Key) -> bool {
+        match *self {
+            Binding::Char(ch) => {
+                key == Key::from_char(ch) || key == Key::from_char(ch.to_ascii_uppercase())
+            }
+            Binding::Key(k) => key == k,
+        }
+    }
+}
+
+/// Finds the action bound to a key, modifiers are not part of any binding
+pub fn entry_action(key: Key) -> Option<EntryAction> {
+    ENTRY_BINDINGS
+        .iter()
+        .find(|(binding, _)| binding.matches(key))
+        .map(|(_, action)| *action)
+}
+
+/// Returns the action for the current key event, if any
+pub fn event_entry_action() -> Option<EntryAction> {
+    if app::is_event_ctrl() || app::is_event_alt() || app::is_event_command() {
+        return None;
+    }
+
+    entry_action(app::event_key())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run_entry_action(
+    action: EntryAction,
+    selection: Vec<u32>,
+    lens: Arc<Mutex<Lens>>,
+    grades: Arc<Mutex<GradeStore>>,
+    history: Arc<Mutex<History>>,
+    scanner: Scanner,
+    sender: Sender<Message>,
+    wind: &mut Window,
+) {
+    if selection.is_empty() {
+        return;
+    }
+
+    match action {
+        // The main window turns the selected row into EntryChanged
+        EntryAction::Navigate => (),
+        EntryAction::Grade(grade) => {
+            let entries = get_selected_entries(&selection, &lens);
+            set_grade(&entries, Some(grade), &grades, &history);
+            sender.send(Message::EntryTableInvalidated);
+            sender.send(Message::HistoryChanged);
+        }
+        EntryAction::Open => {
+            let entries = get_selected_entries(&selection, &lens);
+            for entry in entries.iter() {
+                if let Err(e) = open::that(&entry.path) {
+                    eprintln!("Failed to open path: {} Error {}", entry.path, e);
+                }
+            }
+        }
+        EntryAction::Delete => {
+            let entries = get_selected_entries(&selection, &lens);
+            sender.send(Message::Delete(DeleteTarget::Entries(entries)));
+        }
+        EntryAction::Rename => {
+            let mut entries = get_selected_entries(&selection, &lens);
+            if entries.len() > 1 {
+                let dialog = BatchRenameDialog::new(
+                    lens,
+                    grades,
+                    RenameTarget::Entries(entries),
+                    history,
+                    scanner,
+                );
+                dialog.show();
+            } else if let Some(entry) = entries.pop() {
+                let dialog = RenameDialog::new(lens, grades, entry, history, scanner);
+                dialog.show();
+            }
+            sender.send(Message::EntryTableInvalidated);
+            sender.send(Message::HistoryChanged);
+        }
+        EntryAction::Label => {
+            let entries = get_selected_entries(&selection, &lens);
+            let dialog = EntryLabelDialog::new(lens, entries, history);
+
+            wind.deactivate();
+            dialog.show();
+            wind.activate();
+            sender.send(Message::EntryTableInvalidated);
+            sender.send(Message::HistoryChanged);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grade_keys() {
+        assert_eq!(
+            entry_action(Key::from_char('s')),
+            Some(EntryAction::Grade(Grade::S))
+        );
+        assert_eq!(
+            entry_action(Key::from_char('F')),
+            Some(EntryAction::Grade(Grade::F))
+        );
+    }
+
+    #[test]
+    fn test_special_keys() {
+        assert_eq!(entry_action(Key::Enter), Some(EntryAction::Open));
+        assert_eq!(entry_action(Key::Delete), Some(EntryAction::Delete));
+        assert_eq!(entry_action(Key::F2), Some(EntryAction::Rename));
+        assert_eq!(entry_action(Key::from_char('l')), Some(EntryAction::Label));
+        assert_eq!(entry_action(Key::Down), Some(EntryAction::Navigate));
+    }
+
+    #[test]
+    fn test_unbound_key() {
+        assert_eq!(entry_action(Key::from_char('x')), None);
+    }
+
+    #[test]
+    fn test_bindings_unique() {
+        for (ix, (binding, _)) in ENTRY_BINDINGS.iter().enumerate() {
+            assert!(!ENTRY_BINDINGS[ix + 1..].iter().any(|(b, _)| b == binding));
+        }
+    }
+}
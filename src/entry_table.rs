@@ -1,4 +1,4 @@
-use parking_lot::Mutex;
+use parking_lot::{Mutex, MutexGuard};
 use std::sync::Arc;
 
 use fltk::table::*;
@@ -6,17 +6,32 @@ use fltk::{enums::*, prelude::*, *};
 
 use serious_organizer_lib::lens::{Lens, Sort, SortColumn, SortOrder};
 
-use crate::table_utils::{draw_data, draw_header, pretty_size, resize_column, ColHeader, ColSize, pretty_grade};
+use crate::grade_store::GradeStore;
+use crate::search::entry_view::EntryView;
+use crate::search::search_query::SearchQuery;
+use crate::table_utils::{
+    draw_data, draw_data_highlight, draw_header, pretty_grade, pretty_size, resize_column,
+    ColHeader, ColSize, TableStyle,
+};
 
 #[derive(Clone)]
 pub struct EntryTable {
     pub wid: TableRow,
     lens: Arc<Mutex<Lens>>,
+    grades: Arc<Mutex<GradeStore>>,
+    view: Arc<Mutex<EntryView>>,
     col_sort: Arc<Mutex<Option<Sort>>>,
+    highlight: Arc<Mutex<SearchQuery>>,
+    style: Arc<Mutex<TableStyle>>,
 }
 
 impl EntryTable {
-    pub fn new(w: i32, h: i32, lens: Arc<Mutex<Lens>>) -> EntryTable {
+    pub fn new(
+        w: i32,
+        h: i32,
+        lens: Arc<Mutex<Lens>>,
+        grades: Arc<Mutex<GradeStore>>,
+    ) -> EntryTable {
         let headers = vec![
             ColHeader::new("Name", ColSize::Ratio(0.7)),
             ColHeader::new("Path", ColSize::Greedy),
@@ -27,7 +42,11 @@ impl EntryTable {
         let mut table = EntryTable {
             wid: TableRow::default().with_size(w, h),
             lens,
+            grades,
+            view: Arc::new(Mutex::new(EntryView::default())),
             col_sort: Arc::new(Mutex::new(None)),
+            highlight: Arc::new(Mutex::new(SearchQuery::default())),
+            style: Arc::new(Mutex::new(TableStyle::default())),
         };
 
         table.wid.set_row_height_all(20);
@@ -39,31 +58,50 @@ impl EntryTable {
         table.wid.set_col_resize(true);
 
         table.wid.end();
-        table.wid.set_rows(table.lens.lock().get_dir_count() as i32);
+        table.wid.set_rows(table.view.lock().len() as i32);
 
         resize_column(&mut table, &headers);
 
         let lens_c = table.lens.clone();
+        let view_c = table.view.clone();
+        let grades_c = table.grades.clone();
+        let highlight_c = table.highlight.clone();
+        let style_c = table.style.clone();
 
         table
             .wid
             .draw_cell(move |t, ctx, row, col, x, y, w, h| match ctx {
-                TableContext::StartPage => draw::set_font(Font::Helvetica, 14),
+                TableContext::StartPage => {
+                    draw::set_font(Font::Helvetica, style_c.lock().font_size)
+                }
                 TableContext::ColHeader => draw_header(&headers[col as usize].label, x, y, w, h),
                 TableContext::Cell => {
                     let l = lens_c.lock();
-                    if let Some(dir) = l.get_dir_entry(row as usize) {
+                    if let Some(dir) = view_c.lock().entry(&l, row as usize) {
+                        let selected = t.row_selected(row);
+
+                        // Name and path show which chars matched the search
+                        if col == 0 || col == 1 {
+                            let query = highlight_c.lock();
+                            let (text, positions) = if col == 0 {
+                                (&dir.name, query.name_highlights(&dir.name))
+                            } else {
+                                (&dir.path, query.path_highlights(&dir.path))
+                            };
+                            return draw_data_highlight(text, &positions, x, y, w, h, selected);
+                        }
+
                         let (data, align) = {
                             match col {
                                 0 => (dir.name.to_string(), Align::Left),
                                 1 => (dir.path.to_string(), Align::Left),
                                 2 => (pretty_size(dir.size), Align::Right),
-                                3 => (pretty_grade(dir.grade), Align::Center),
+                                3 => (pretty_grade(grades_c.lock().grade(dir)), Align::Center),
                                 _ => ("".to_string(), Align::Center),
                             }
                         };
 
-                        draw_data(&data, x, y, w, h, t.row_selected(row), align)
+                        draw_data(&data, x, y, w, h, selected, align)
                     }
                 }
                 _ => (),
@@ -73,7 +111,28 @@ impl EntryTable {
 
     pub fn update(&mut self) {
         println!("Entry table upate");
-        let dir_count = { self.lens.lock().get_dir_count() as i32 };
+        let dir_count = {
+            // Rows follow the lens order while a column sort is set
+            let (lens_order, grade_order) = {
+                let sort = self.col_sort.lock();
+                let grade_order = sort
+                    .as_ref()
+                    .filter(|s| matches!(s.column, SortColumn::Grade))
+                    .map(|s| s.order);
+                (sort.is_some(), grade_order)
+            };
+
+            let lens = self.lens.lock();
+            let mut view = self.view.lock();
+            view.reindex(&lens, lens_order);
+
+            // The lens orders by its own grades, the ones set here can differ
+            if let Some(order) = grade_order {
+                let grades = self.grades.lock();
+                view.sort_by_key(&lens, |e| grades.grade(e), matches!(order, SortOrder::Desc));
+            }
+            view.len() as i32
+        };
         self.set_rows(dir_count);
         self.set_damage(true);
         self.set_damage_type(Damage::all());
@@ -81,38 +140,93 @@ impl EntryTable {
         self.redraw();
     }
 
+    /// Shows the entries of a finished search
+    pub fn show_rows(&mut self, rows: Vec<u32>) {
+        self.view.lock().set_rows(rows);
+        self.update();
+    }
+
+    pub fn view(&self) -> MutexGuard<'_, EntryView> {
+        self.view.lock()
+    }
+
+    /// Lens index of a table row
+    pub fn lens_ix(&self, row: usize) -> Option<usize> {
+        self.view.lock().lens_ix(row)
+    }
+
+    /// Lens indexes of table rows
+    pub fn lens_ixs(&self, rows: &[u32]) -> Vec<u32> {
+        self.view.lock().lens_ixs(rows)
+    }
+
+    /// Search whose matches are highlighted in the name and path columns
+    pub fn set_highlight(&mut self, query: SearchQuery) {
+        *self.highlight.lock() = query;
+        self.redraw();
+    }
+
+    pub fn set_style(&mut self, style: TableStyle) {
+        *self.style.lock() = style;
+        self.set_row_height_all(style.row_height);
+        self.redraw();
+    }
+
     pub fn toggle_sort_column(&mut self, col_id: i32) {
-        {
-            let mut sort = self.col_sort.lock();
-
-            let col = match col_id {
-                0 => SortColumn::Name,
-                1 => SortColumn::Path,
-                2 => SortColumn::Size,
-                3 => SortColumn::Grade,
-                _ => panic!("Trying to dir sort unknown column"),
-            };
+        let col = sort_column(col_id);
 
-            let ord = if let Some(s) = &*sort {
-                if s.column == col && s.order == SortOrder::Asc {
-                    SortOrder::Desc
-                } else {
-                    SortOrder::Asc
-                }
+        let ord = if let Some(s) = &*self.col_sort.lock() {
+            if s.column == col && s.order == SortOrder::Asc {
+                SortOrder::Desc
             } else {
                 SortOrder::Asc
+            }
+        } else {
+            SortOrder::Asc
+        };
+
+        println!("Change sort column!");
+        self.set_sort(col_id, ord);
+    }
+
+    /// Current sort as header column and order
+    pub fn sort(&self) -> Option<(i32, SortOrder)> {
+        self.col_sort.lock().as_ref().map(|s| {
+            let col_id = match s.column {
+                SortColumn::Name => 0,
+                SortColumn::Path => 1,
+                SortColumn::Size => 2,
+                _ => 3,
             };
+            (col_id, s.order)
+        })
+    }
 
-            println!("Change sort column!");
+    /// Drops the column sort so a relevance order can take over
+    pub fn clear_sort(&mut self) {
+        *self.col_sort.lock() = None;
+    }
 
+    pub fn set_sort(&mut self, col_id: i32, ord: SortOrder) {
+        {
+            let col = sort_column(col_id);
             self.lens.lock().order_by(col, ord);
-
-            *sort = Some(Sort::new(col, ord));
+            *self.col_sort.lock() = Some(Sort::new(col, ord));
         }
         self.update();
     }
 }
 
+fn sort_column(col_id: i32) -> SortColumn {
+    match col_id {
+        0 => SortColumn::Name,
+        1 => SortColumn::Path,
+        2 => SortColumn::Size,
+        3 => SortColumn::Grade,
+        _ => panic!("Trying to dir sort unknown column"),
+    }
+}
+
 use std::ops::{Deref, DerefMut};
 
 impl Deref for EntryTable {
@@ -1,6 +1,6 @@
 use fltk::frame::Frame;
 use fltk::prelude::*;
-use fltk::{button::*, window::*};
+use fltk::{button::*, enums::Align, window::*};
 
 pub struct ErrorDialog {
     error_text: String,
@@ -12,14 +12,21 @@ impl ErrorDialog {
     }
 
     pub fn show(&self) {
-        let mut dialog = Window::new(300, 325, 450, 120, "Error!");
+        // Grow with the number of lines so summaries of many errors fit
+        let lines = self.error_text.lines().count().clamp(1, 30) as i32;
+        let text_height = 25.max(lines * 14);
+
+        let mut dialog = Window::new(300, 325, 450, text_height + 95, "Error!");
         dialog.make_modal(true);
 
-        let mut output_name = Frame::new(10, 10, 390, 25, None);
+        let mut output_name = Frame::new(10, 10, 430, text_height, None);
         output_name.set_label(&self.error_text);
         output_name.set_label_size(10);
+        if lines > 1 {
+            output_name.set_align(Align::Left | Align::Top | Align::Inside | Align::Clip);
+        }
 
-        let mut btn = Button::new(10, 45, 60, 25, "Ok");
+        let mut btn = Button::new(10, text_height + 20, 60, 25, "Ok");
         let mut dialog_c = dialog.clone();
         btn.set_callback(move |_| {
             dialog_c.hide();
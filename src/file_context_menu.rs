@@ -1,35 +1,53 @@
 use parking_lot::Mutex;
 use serious_organizer_lib::models::File;
 
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use fltk::dialog::{NativeFileChooser, NativeFileChooserType};
 use fltk::{app, app::*, menu::*};
 
 use serious_organizer_lib::lens::Lens;
 
 use crate::file_table::FileTable;
+use crate::history::journal::History;
 use crate::model::message::Message;
 
-use crate::choice_dialog::ChoiceDialog;
-// use crate::rename_dialog::RenameDialog;
+use crate::delete_dialog::DeleteTarget;
+use crate::error_dialog::ErrorDialog;
+use crate::file_ops;
+use crate::grade_store::GradeStore;
+use crate::rename::batch_rename_dialog::{BatchRenameDialog, RenameTarget};
+use crate::rename_dialog::RenameDialog;
+use crate::scan::scanner::Scanner;
+use crate::transfer::entry_transfer::{EntryMeta, TransferItem, TransferMode, TransferOrigin};
 
 pub fn show_file_context_menu(
     file_table: &mut FileTable,
     selection: Vec<u32>,
     lens: Arc<Mutex<Lens>>,
+    grades: Arc<Mutex<GradeStore>>,
+    history: Arc<Mutex<History>>,
+    scanner: Scanner,
     sender: Sender<Message>,
 ) {
     if !selection.is_empty() {
         println!("Context menu!");
 
         let choices = vec![
+            "Open",
+            "Open containing folder",
+            "Copy path",
+            "Rename",
+            "Batch Rename",
+            "Move to...",
+            "Copy to...",
             "Delete File",
-            // "Rename Entry",
         ];
 
         let x = MenuItem::new(&choices);
 
-        let files = if let Some(file) = file_table.get_files().lock().clone() {
+        let files: Vec<File> = if let Some(file) = file_table.get_files().lock().clone() {
             file.iter()
                 .enumerate()
                 .filter_map(|(ix, f)| {
@@ -52,15 +70,83 @@ pub fn show_file_context_menu(
                 println!("{}", val.label().unwrap());
 
                 match val.label().unwrap().as_str() {
+                    "Open" => {
+                        for file in files.iter() {
+                            if let Err(e) = open::that(&file.path) {
+                                eprintln!("Failed to open file path: {} Error {}", file.path, e);
+                            }
+                        }
+                    }
+                    "Open containing folder" => {
+                        let mut dirs: Vec<&Path> = files
+                            .iter()
+                            .filter_map(|f| Path::new(&f.path).parent())
+                            .collect();
+                        dirs.dedup();
+
+                        for dir in dirs {
+                            if let Err(e) = open::that(dir) {
+                                eprintln!("Failed to open dir: {:?} Error {}", dir, e);
+                            }
+                        }
+                    }
+                    "Copy path" => {
+                        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+                        app::copy(&paths.join("\n"));
+                    }
+                    "Rename" => {
+                        if files.len() == 1 {
+                            let file = files.into_iter().next().unwrap();
+                            let dialog = RenameDialog::for_file(lens, file, history, scanner);
+                            dialog.show();
+                        } else {
+                            let entry = file_table
+                                .get_dir_ix()
+                                .and_then(|ix| lens.lock().get_dir_entry(ix).cloned());
+
+                            let dialog = BatchRenameDialog::new(
+                                lens,
+                                grades,
+                                RenameTarget::Files(files, entry),
+                                history,
+                                scanner,
+                            );
+                            dialog.show();
+                        }
+                        sender.send(Message::EntryTableInvalidated);
+                        sender.send(Message::FileTableInvalidated);
+                        sender.send(Message::HistoryChanged);
+                    }
+                    "Move to..." => {
+                        if let Some(dir) = choose_dir("Move files to") {
+                            transfer_files(&files, &dir, TransferMode::Move, &sender);
+                        }
+                    }
+                    "Copy to..." => {
+                        if let Some(dir) = choose_dir("Copy files to") {
+                            transfer_files(&files, &dir, TransferMode::Copy, &sender);
+                        }
+                    }
                     "Delete File" => {
-                        delete_files(files, lens);
+                        sender.send(Message::Delete(DeleteTarget::Files(files)));
+                    }
+                    "Batch Rename" => {
+                        let entry = file_table
+                            .get_dir_ix()
+                            .and_then(|ix| lens.lock().get_dir_entry(ix).cloned());
+
+                        let dialog = BatchRenameDialog::new(
+                            lens,
+                            grades,
+                            RenameTarget::Files(files, entry),
+                            history,
+                            scanner,
+                        );
+                        dialog.show();
+                        sender.send(Message::EntryTableInvalidated);
                         sender.send(Message::FileTableInvalidated);
+                        sender.send(Message::HistoryChanged);
                     }
-                    // "Rename Entry" => {
-                    //     let dialog = RenameDialog::new(lens.clone(), entry);
-                    //     dialog.show();
-                    //     sender.send(Message::EntryTableInvalidated);
-                    // }
                     _ => {
                         println!("Unknown popup string: {}", val.label().unwrap())
                     }
@@ -70,27 +156,52 @@ pub fn show_file_context_menu(
     }
 }
 
-fn delete_files(files: Vec<File>, lens: Arc<Mutex<Lens>>) {
-    if !show_delete_confirmation_dialog(files.len()) {
-        return;
+fn choose_dir(title: &str) -> Option<PathBuf> {
+    let mut chooser = NativeFileChooser::new(NativeFileChooserType::BrowseDir);
+    chooser.set_title(title);
+    chooser.show();
+
+    let dir = chooser.filename();
+    if dir.as_os_str().is_empty() {
+        None
+    } else {
+        Some(dir)
     }
+}
 
-    println!("Delete the file {:?}", files);
+/// Moves or copies the files into `dir` in the background, existing files are never
+/// overwritten
+fn transfer_files(files: &[File], dir: &Path, mode: TransferMode, sender: &Sender<Message>) {
+    println!("{:?} {} files to {:?}", mode, files.len(), dir);
 
-    let mut lens = lens.lock();
+    let mut errors = Vec::new();
+    let mut items = Vec::new();
 
     for file in files.iter() {
-        lens.remove_file(file)
-            .unwrap_or_else(|_| panic!("Failed to remove entry {}", file.name));
+        let source = PathBuf::from(&file.path);
+        match file_ops::target_path(dir, &source) {
+            Ok(target) => items.push(TransferItem {
+                source,
+                target,
+                overwrite: false,
+                meta: EntryMeta::default(),
+            }),
+            Err(err) => errors.push(format!("{}: {}", file.path, err)),
+        }
     }
-}
 
-fn show_delete_confirmation_dialog(count: usize) -> bool {
-    let dialog = ChoiceDialog::new(
-        format!("Are you sure you want to remove {} files?", count),
-        vec!["Ok".to_string(), "Cancel".to_string()],
-    );
-    dialog.show();
+    if !items.is_empty() {
+        sender.send(Message::StartTransfer(mode, TransferOrigin::Files, items));
+    }
 
-    dialog.result() == 0
+    if !errors.is_empty() {
+        let err_dialog = ErrorDialog::new(format!(
+            "Failed to {} {} of {} files:\n{}",
+            mode.verb().to_lowercase(),
+            errors.len(),
+            files.len(),
+            errors.join("\n")
+        ));
+        err_dialog.show();
+    }
 }
@@ -0,0 +1,84 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Path the file gets when put in `dir`, keeping its name
+pub fn target_path(dir: &Path, path: &Path) -> io::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+
+    Ok(dir.join(name))
+}
+
+fn already_exists(to: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{:?} already exists", to),
+    )
+}
+
+/// Whether a rename failed because the target is on another device
+pub fn is_cross_device(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::CrossesDevices
+}
+
+/// Moves a file, never overwrites. Falls back to copy and delete when the target is on
+/// another device.
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if to.exists() {
+        return Err(already_exists(to));
+    }
+
+    if let Err(err) = fs::rename(from, to) {
+        if !is_cross_device(&err) || !from.is_file() {
+            return Err(err);
+        }
+
+        println!("Rename failed ({}), copying {:?} instead", err, from);
+        fs::copy(from, to)?;
+        if let Err(err) = fs::remove_file(from) {
+            let _ = fs::remove_file(to);
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    #[test]
+    fn test_move_file() {
+        let dir = temp_dir("file_ops_move");
+        let sub = dir.join("sub");
+        fs::create_dir(&sub).unwrap();
+
+        let file = dir.join("a.mkv");
+        fs::write(&file, "data").unwrap();
+
+        let taken = target_path(&sub, &file).unwrap();
+        assert_eq!(taken, sub.join("a.mkv"));
+        fs::write(&taken, "old").unwrap();
+
+        // Never overwrites
+        assert!(move_file(&file, &taken).is_err());
+        assert_eq!(fs::read_to_string(&taken).unwrap(), "old");
+        assert!(file.exists());
+
+        let moved = dir.join("b.mkv");
+        move_file(&file, &moved).unwrap();
+        assert!(!file.exists());
+        assert_eq!(fs::read_to_string(&moved).unwrap(), "data");
+
+        // Only a rename between devices is copied
+        let missing = move_file(&file, &dir.join("c.mkv")).unwrap_err();
+        assert!(!is_cross_device(&missing));
+        assert!(!dir.join("c.mkv").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
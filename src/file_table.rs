@@ -10,7 +10,10 @@ use serious_organizer_lib::{
     models::File,
 };
 
-use crate::table_utils::{draw_data_color, draw_header, get_file_color, pretty_size, ColSize, ColHeader, resize_column};
+use crate::table_utils::{
+    draw_data_color, draw_header, get_file_color, pretty_size, resize_column, ColHeader, ColSize,
+    TableStyle, VIDEO_FORMATS,
+};
 
 #[derive(Clone)]
 pub struct FileTable {
@@ -20,6 +23,8 @@ pub struct FileTable {
     lens: Arc<Mutex<Lens>>,
     pub files: Arc<Mutex<Option<Vec<File>>>>,
     col_sort: Arc<Mutex<Sort>>,
+    style: Arc<Mutex<TableStyle>>,
+    video_extensions: Arc<Mutex<Vec<String>>>,
 }
 
 impl FileTable {
@@ -37,6 +42,10 @@ impl FileTable {
             file_id: Arc::new(AtomicIsize::new(-1)),
             files: Arc::new(Mutex::new(None)),
             col_sort: Arc::new(Mutex::new(Sort::new(SortColumn::Name, SortOrder::Asc))),
+            style: Arc::new(Mutex::new(TableStyle::default())),
+            video_extensions: Arc::new(Mutex::new(
+                VIDEO_FORMATS.iter().map(|e| e.to_string()).collect(),
+            )),
         };
 
         table.wid.set_row_height_all(20);
@@ -56,7 +65,9 @@ impl FileTable {
         table
             .wid
             .draw_cell(move |t, ctx, row, col, x, y, w, h| match ctx {
-                TableContext::StartPage => draw::set_font(Font::Helvetica, 14),
+                TableContext::StartPage => {
+                    draw::set_font(Font::Helvetica, table_c.style.lock().font_size)
+                }
                 TableContext::ColHeader => draw_header(&headers[col as usize].label, x, y, w, h),
                 TableContext::Cell => {
                     let dir_id = table_c.get_dir_ix();
@@ -67,7 +78,7 @@ impl FileTable {
                         if let Some(files) = &*table_c.files.lock() {
                             if let Some(file) = files.get(row as usize) {
                                 let name = file.name.as_str();
-                                let color = get_file_color(name);
+                                let color = get_file_color(name, &table_c.video_extensions.lock());
 
                                 match col {
                                     0 => draw_data_color(
@@ -111,6 +122,18 @@ impl FileTable {
         table
     }
 
+    pub fn set_style(&mut self, style: TableStyle) {
+        *self.style.lock() = style;
+        self.wid.set_row_height_all(style.row_height);
+        self.redraw();
+    }
+
+    /// File endings drawn in the video colour
+    pub fn set_video_extensions(&mut self, extensions: Vec<String>) {
+        *self.video_extensions.lock() = extensions;
+        self.redraw();
+    }
+
     pub fn get_dir_ix(&self) -> Option<usize> {
         let dir_id = self.dir_id.load(Ordering::Relaxed);
         if dir_id >= 0 {
@@ -230,12 +253,7 @@ impl FileTable {
         {
             let mut sort = self.col_sort.lock();
 
-            let col = match col_id {
-                0 => SortColumn::Name,
-                1 => SortColumn::Path,
-                2 => SortColumn::Size,
-                _ => panic!("Trying to dir sort unknown column"),
-            };
+            let col = sort_column(col_id);
 
             let ord = {
                 if sort.column == col && sort.order == SortOrder::Asc {
@@ -254,6 +272,22 @@ impl FileTable {
         self.sort_by_column();
     }
 
+    /// Current sort as header column and order
+    pub fn sort(&self) -> (i32, SortOrder) {
+        let sort = self.col_sort.lock();
+        let col_id = match sort.column {
+            SortColumn::Path => 1,
+            SortColumn::Size => 2,
+            _ => 0,
+        };
+        (col_id, sort.order)
+    }
+
+    pub fn set_sort(&self, col_id: i32, ord: SortOrder) {
+        *self.col_sort.lock() = Sort::new(sort_column(col_id), ord);
+        self.sort_by_column();
+    }
+
     pub fn update(&mut self) {
         println!("File table update");
 
@@ -284,6 +318,15 @@ impl FileTable {
     }
 }
 
+fn sort_column(col_id: i32) -> SortColumn {
+    match col_id {
+        0 => SortColumn::Name,
+        1 => SortColumn::Path,
+        2 => SortColumn::Size,
+        _ => panic!("Trying to dir sort unknown column"),
+    }
+}
+
 use std::ops::{Deref, DerefMut};
 
 impl Deref for FileTable {
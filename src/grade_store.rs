@@ -0,0 +1,169 @@
+use parking_lot::Mutex;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use serious_organizer_lib::models::Entry;
+
+/// Grades set in this app, kept by entry path in the library database so they stay
+/// with an entry through rescans. Entries never graded here keep the lens grade.
+pub struct GradeStore {
+    db: Arc<Mutex<Connection>>,
+    /// None for a grade that was cleared
+    grades: HashMap<String, Option<i32>>,
+}
+
+impl GradeStore {
+    pub fn load(db: Arc<Mutex<Connection>>) -> GradeStore {
+        let mut store = GradeStore {
+            db,
+            grades: HashMap::new(),
+        };
+        store.reload();
+        store
+    }
+
+    /// Reads the grades again, after the database was switched to another library
+    pub fn reload(&mut self) {
+        self.grades = read_grades(&self.db.lock()).unwrap_or_else(|err| {
+            println!("Failed to read grades: {}", err);
+            HashMap::new()
+        });
+    }
+
+    pub fn grade(&self, entry: &Entry) -> Option<i32> {
+        self.grade_at(&entry.path, entry.grade)
+    }
+
+    /// Grade of the entry at `path`, `lens_grade` when it was never graded here
+    pub fn grade_at(&self, path: &str, lens_grade: Option<i32>) -> Option<i32> {
+        self.grades.get(path).copied().unwrap_or(lens_grade)
+    }
+
+    /// Sets several grades in one transaction, nothing changes when it fails
+    pub fn set_all(&mut self, grades: &[(String, Option<i32>)]) -> Result<(), String> {
+        write_grades(&mut self.db.lock(), grades, &[])
+            .map_err(|err| format!("Failed to set grade: {}", err))?;
+
+        for (path, grade) in grades.iter() {
+            self.grades.insert(path.clone(), *grade);
+        }
+        Ok(())
+    }
+
+    /// Grades below `from`, or of `from` itself, follow a move or rename to `to`
+    pub fn move_path(&mut self, from: &str, to: &str) -> Result<(), String> {
+        let moved: Vec<(String, String)> = self
+            .grades
+            .keys()
+            .filter_map(|path| {
+                let rest = Path::new(path).strip_prefix(from).ok()?;
+                let target = if rest.as_os_str().is_empty() {
+                    Path::new(to).to_path_buf()
+                } else {
+                    Path::new(to).join(rest)
+                };
+                Some((path.clone(), target.to_string_lossy().to_string()))
+            })
+            .collect();
+
+        if moved.is_empty() {
+            return Ok(());
+        }
+
+        let grades: Vec<(String, Option<i32>)> = moved
+            .iter()
+            .map(|(old, new)| (new.clone(), self.grades[old]))
+            .collect();
+        let removed: Vec<String> = moved.iter().map(|(old, _)| old.clone()).collect();
+
+        write_grades(&mut self.db.lock(), &grades, &removed)
+            .map_err(|err| format!("Failed to move grades: {}", err))?;
+
+        for old in removed.iter() {
+            self.grades.remove(old);
+        }
+        self.grades.extend(grades);
+        Ok(())
+    }
+}
+
+fn read_grades(conn: &Connection) -> rusqlite::Result<HashMap<String, Option<i32>>> {
+    let mut stmt = conn.prepare("SELECT path, grade FROM entry_grade")?;
+    let grades = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    grades.collect()
+}
+
+fn write_grades(
+    conn: &mut Connection,
+    grades: &[(String, Option<i32>)],
+    removed: &[String],
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for path in removed.iter() {
+        tx.execute("DELETE FROM entry_grade WHERE path = ?1", params![path])?;
+    }
+    for (path, grade) in grades.iter() {
+        tx.execute(
+            "INSERT OR REPLACE INTO entry_grade (path, grade) VALUES (?1, ?2)",
+            params![path, grade],
+        )?;
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_db;
+    use crate::table_utils::Grade;
+    use crate::test_utils::temp_dir;
+
+    fn open(name: &str) -> Arc<Mutex<Connection>> {
+        let path = temp_dir(name).join("Library.sqlite3");
+        Arc::new(Mutex::new(app_db::open(&path.to_string_lossy()).unwrap()))
+    }
+
+    #[test]
+    fn test_grades_survive_reload() {
+        let db = open("grade_store");
+        let mut store = GradeStore::load(db.clone());
+        let a = Some(Grade::A as i32);
+
+        assert_eq!(store.grade_at("/m/Film", a), a);
+        store
+            .set_all(&[("/m/Film".to_string(), None), ("/m/Show".to_string(), a)])
+            .unwrap();
+        assert_eq!(store.grade_at("/m/Film", a), None);
+
+        let store = GradeStore::load(db);
+        assert_eq!(store.grade_at("/m/Film", a), None);
+        assert_eq!(store.grade_at("/m/Show", None), a);
+        assert_eq!(store.grade_at("/m/Other", None), None);
+    }
+
+    #[test]
+    fn test_move_path() {
+        let db = open("grade_store_move");
+        let mut store = GradeStore::load(db.clone());
+        let a = Some(Grade::A as i32);
+        let b = Some(Grade::B as i32);
+
+        store
+            .set_all(&[
+                ("/m/Film".to_string(), a),
+                ("/m/Films/Other".to_string(), b),
+                ("/m/Film/Extra".to_string(), b),
+            ])
+            .unwrap();
+
+        store.move_path("/m/Film", "/n/Film").unwrap();
+
+        let store = GradeStore::load(db);
+        assert_eq!(store.grade_at("/n/Film", None), a);
+        assert_eq!(store.grade_at("/n/Film/Extra", None), b);
+        assert_eq!(store.grade_at("/m/Film", None), None);
+        assert_eq!(store.grade_at("/m/Films/Other", None), b);
+    }
+}
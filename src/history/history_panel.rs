@@ -0,0 +1,71 @@
+use fltk::app::Sender;
+use fltk::browser::HoldBrowser;
+use fltk::button::Button;
+use fltk::prelude::*;
+use fltk::window::*;
+
+use crate::history::journal::History;
+use crate::model::message::Message;
+
+/// Small window listing the operation journal, undone operations are shown in italics
+pub struct HistoryPanel {
+    dialog: Window,
+    browser: HoldBrowser,
+}
+
+impl HistoryPanel {
+    pub fn new(sender: Sender<Message>) -> Self {
+        let mut dialog = Window::new(850, 100, 300, 330, "History");
+
+        let mut browser = HoldBrowser::new(10, 10, 280, 275, None);
+        browser.set_text_size(12);
+
+        let mut but_undo = Button::new(10, 295, 60, 25, "Undo");
+        but_undo.set_tooltip("Ctrl+Z");
+        let sender_c = sender.clone();
+        but_undo.set_callback(move |_| sender_c.send(Message::Undo));
+
+        let mut but_redo = Button::new(80, 295, 60, 25, "Redo");
+        but_redo.set_tooltip("Ctrl+Shift+Z");
+        but_redo.set_callback(move |_| sender.send(Message::Redo));
+
+        let mut but_close = Button::new(230, 295, 60, 25, "Close");
+        let mut dialog_c = dialog.clone();
+        but_close.set_callback(move |_| dialog_c.hide());
+
+        dialog.end();
+
+        HistoryPanel { dialog, browser }
+    }
+
+    pub fn show(&mut self) {
+        self.dialog.show();
+    }
+
+    pub fn update(&mut self, history: &History) {
+        self.browser.clear();
+
+        for (ix, operation) in history.operations().iter().enumerate() {
+            // "@." stops format codes in the description from being read
+            let mut line = if ix < history.position() {
+                "@.".to_string()
+            } else {
+                "@i@.".to_string()
+            };
+            line.push_str(&operation.description());
+
+            if !operation.can_undo() {
+                line.push_str(" (can not be undone)");
+            }
+
+            self.browser.add(&line);
+        }
+
+        if history.position() > 0 {
+            self.browser.select(history.position() as i32);
+            self.browser.set_bottomline(history.position() as i32);
+        }
+
+        self.browser.redraw();
+    }
+}
@@ -0,0 +1,400 @@
+use std::path::Path;
+
+use crate::table_utils::pretty_grade;
+use crate::trash::TrashedItem;
+
+/// Number of operations kept in the journal
+const MAX_HISTORY: usize = 200;
+
+/// A change that was made, with what is needed to reverse it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// Entry paths with the grade before, and the grade that was set
+    SetGrade {
+        entries: Vec<(String, Option<i32>)>,
+        grade: Option<i32>,
+    },
+    /// Entry paths with the labels before, and the labels that were set on all of them.
+    /// Paths, as entry ids change whenever the lens is updated.
+    SetLabels {
+        entries: Vec<(String, Vec<u32>)>,
+        labels: Vec<u32>,
+    },
+    RenameEntry {
+        old_path: String,
+        new_path: String,
+    },
+    /// Old and new path of each renamed entry
+    RenameEntries {
+        renames: Vec<(String, String)>,
+    },
+    /// Old and new path of each file renamed on disk
+    RenameFiles {
+        renames: Vec<(String, String)>,
+    },
+    /// Old and new path of each file moved to another dir
+    MoveFiles {
+        moves: Vec<(String, String)>,
+    },
+    /// Source and path of each copy that was made
+    CopyFiles {
+        copies: Vec<(String, String)>,
+    },
+    /// Old and new path of each entry moved to another location
+    MoveEntries {
+        moves: Vec<(String, String)>,
+    },
+    /// Source and path of each entry copy that was made
+    CopyEntries {
+        copies: Vec<(String, String)>,
+    },
+    /// File path and new dir path of each file entry moved into a dir with its name
+    MoveToDir {
+        moves: Vec<(String, String)>,
+    },
+    TrashEntries {
+        items: Vec<TrashedItem>,
+    },
+    TrashFiles {
+        items: Vec<TrashedItem>,
+    },
+    DeleteEntries {
+        paths: Vec<String>,
+    },
+    DeleteFiles {
+        paths: Vec<String>,
+    },
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+fn count_text(count: usize, singular: &str, plural: &str) -> String {
+    if count == 1 {
+        format!("1 {}", singular)
+    } else {
+        format!("{} {}", count, plural)
+    }
+}
+
+impl Operation {
+    pub fn description(&self) -> String {
+        match self {
+            Operation::SetGrade { entries, grade } => match grade {
+                Some(grade) => format!(
+                    "Set grade {} on {}",
+                    pretty_grade(Some(*grade)),
+                    count_text(entries.len(), "entry", "entries")
+                ),
+                None => format!(
+                    "Clear grade on {}",
+                    count_text(entries.len(), "entry", "entries")
+                ),
+            },
+            Operation::SetLabels { entries, .. } => {
+                format!(
+                    "Change labels on {}",
+                    count_text(entries.len(), "entry", "entries")
+                )
+            }
+            Operation::RenameEntry { old_path, new_path } => {
+                format!("Rename {} to {}", file_name(old_path), file_name(new_path))
+            }
+            Operation::RenameEntries { renames } => {
+                format!("Rename {}", count_text(renames.len(), "entry", "entries"))
+            }
+            Operation::RenameFiles { renames } => {
+                format!("Rename {}", count_text(renames.len(), "file", "files"))
+            }
+            Operation::MoveFiles { moves } => {
+                format!("Move {}", count_text(moves.len(), "file", "files"))
+            }
+            Operation::CopyFiles { copies } => {
+                format!("Copy {}", count_text(copies.len(), "file", "files"))
+            }
+            Operation::MoveEntries { moves } => {
+                format!("Move {}", count_text(moves.len(), "entry", "entries"))
+            }
+            Operation::CopyEntries { copies } => {
+                format!("Copy {}", count_text(copies.len(), "entry", "entries"))
+            }
+            Operation::MoveToDir { moves } => match moves.as_slice() {
+                [(file_path, _)] => format!("Move {} to dir", file_name(file_path)),
+                _ => format!(
+                    "Move {} to dirs",
+                    count_text(moves.len(), "entry", "entries")
+                ),
+            },
+            Operation::TrashEntries { items } => format!(
+                "Move {} to trash",
+                count_text(items.len(), "entry", "entries")
+            ),
+            Operation::TrashFiles { items } => {
+                format!("Move {} to trash", count_text(items.len(), "file", "files"))
+            }
+            Operation::DeleteEntries { paths } => {
+                format!("Delete {}", count_text(paths.len(), "entry", "entries"))
+            }
+            Operation::DeleteFiles { paths } => {
+                format!("Delete {}", count_text(paths.len(), "file", "files"))
+            }
+        }
+    }
+
+    /// Permanently deleted entries and files are gone from disk and can not be brought back
+    pub fn can_undo(&self) -> bool {
+        !matches!(
+            self,
+            Operation::DeleteEntries { .. } | Operation::DeleteFiles { .. }
+        )
+    }
+}
+
+/// Journal of operations. Operations before `position` are done, the rest can be redone.
+#[derive(Debug, Default)]
+pub struct History {
+    operations: Vec<Operation>,
+    position: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    /// Adds a done operation, anything that could be redone is dropped
+    pub fn push(&mut self, operation: Operation) {
+        self.operations.truncate(self.position);
+        self.operations.push(operation);
+
+        if self.operations.len() > MAX_HISTORY {
+            self.operations.remove(0);
+        }
+
+        self.position = self.operations.len();
+    }
+
+    /// The last done operation that can be undone, with its index. Operations that can not
+    /// be undone are stepped over. The position only moves with `mark_undone`.
+    pub fn next_undo(&self) -> Option<(usize, Operation)> {
+        self.operations[..self.position]
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, operation)| operation.can_undo())
+            .map(|(index, operation)| (index, operation.clone()))
+    }
+
+    /// The next operation that can be redone, with its index
+    pub fn next_redo(&self) -> Option<(usize, Operation)> {
+        self.operations
+            .iter()
+            .enumerate()
+            .skip(self.position)
+            .find(|(_, operation)| operation.can_undo())
+            .map(|(index, operation)| (index, operation.clone()))
+    }
+
+    /// Steps back to before the operation from `next_undo` once it has been undone.
+    /// Nothing happens if the journal changed in between.
+    pub fn mark_undone(&mut self, index: usize, operation: &Operation) {
+        if index < self.position && self.operations.get(index) == Some(operation) {
+            self.position = index;
+        }
+    }
+
+    /// Steps forward past the operation from `next_redo` once it has been redone, and
+    /// past the operations after it that can not be undone. `done` replaces it, redoing
+    /// can give a new result such as a new trash path.
+    pub fn mark_redone(&mut self, index: usize, operation: &Operation, done: Operation) {
+        if index < self.position || self.operations.get(index) != Some(operation) {
+            return;
+        }
+
+        self.operations[index] = done;
+        self.position = index + 1;
+        while self
+            .operations
+            .get(self.position)
+            .map(|operation| !operation.can_undo())
+            .unwrap_or(false)
+        {
+            self.position += 1;
+        }
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Number of operations that are done
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grade(id: u32) -> Operation {
+        Operation::SetGrade {
+            entries: vec![(format!("/e{}", id), None)],
+            grade: Some(1),
+        }
+    }
+
+    fn delete() -> Operation {
+        Operation::DeleteEntries {
+            paths: vec!["/a".to_string()],
+        }
+    }
+
+    fn undo(history: &mut History) -> Option<Operation> {
+        let (index, operation) = history.next_undo()?;
+        history.mark_undone(index, &operation);
+        Some(operation)
+    }
+
+    fn redo(history: &mut History) -> Option<Operation> {
+        let (index, operation) = history.next_redo()?;
+        history.mark_redone(index, &operation, operation.clone());
+        Some(operation)
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut history = History::new();
+        history.push(grade(1));
+        history.push(grade(2));
+
+        assert_eq!(undo(&mut history), Some(grade(2)));
+        assert_eq!(undo(&mut history), Some(grade(1)));
+        assert_eq!(undo(&mut history), None);
+        assert_eq!(history.position(), 0);
+
+        assert_eq!(redo(&mut history), Some(grade(1)));
+        assert_eq!(redo(&mut history), Some(grade(2)));
+        assert_eq!(redo(&mut history), None);
+    }
+
+    #[test]
+    fn test_position_moves_only_when_marked() {
+        let mut history = History::new();
+        history.push(grade(1));
+        history.push(grade(2));
+
+        // A failed undo leaves the operation done
+        assert_eq!(history.next_undo(), Some((1, grade(2))));
+        assert_eq!(history.next_undo(), Some((1, grade(2))));
+        assert_eq!(history.position(), 2);
+
+        // The journal changed before the undo finished
+        history.push(grade(3));
+        history.mark_undone(1, &grade(3));
+        assert_eq!(history.position(), 3);
+    }
+
+    #[test]
+    fn test_push_drops_redo() {
+        let mut history = History::new();
+        history.push(grade(1));
+        history.push(grade(2));
+        undo(&mut history);
+
+        history.push(grade(3));
+
+        assert_eq!(history.operations(), &[grade(1), grade(3)]);
+        assert_eq!(redo(&mut history), None);
+    }
+
+    #[test]
+    fn test_steps_over_operations_that_can_not_be_undone() {
+        let mut history = History::new();
+        history.push(grade(1));
+        history.push(delete());
+
+        assert_eq!(undo(&mut history), Some(grade(1)));
+        assert_eq!(history.position(), 0);
+        assert_eq!(redo(&mut history), Some(grade(1)));
+        assert_eq!(history.position(), 2);
+        assert_eq!(redo(&mut history), None);
+    }
+
+    #[test]
+    fn test_redo_replaces_operation() {
+        let mut history = History::new();
+        history.push(grade(1));
+        history.push(grade(2));
+        undo(&mut history);
+        undo(&mut history);
+
+        let (index, operation) = history.next_redo().unwrap();
+        history.mark_redone(index, &operation, grade(3));
+
+        assert_eq!(history.operations(), &[grade(3), grade(2)]);
+        assert_eq!(history.position(), 1);
+        assert!(Operation::TrashFiles { items: vec![] }.can_undo());
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let mut history = History::new();
+        for id in 0..(MAX_HISTORY as u32 + 5) {
+            history.push(grade(id));
+        }
+
+        assert_eq!(history.operations().len(), MAX_HISTORY);
+        assert_eq!(history.operations()[0], grade(5));
+        assert_eq!(history.position(), MAX_HISTORY);
+    }
+
+    #[test]
+    fn test_description() {
+        assert_eq!(
+            Operation::RenameEntry {
+                old_path: "/loc/old".to_string(),
+                new_path: "/loc/new".to_string()
+            }
+            .description(),
+            "Rename old to new"
+        );
+        assert_eq!(delete().description(), "Delete 1 entry");
+        assert_eq!(
+            Operation::RenameFiles {
+                renames: vec![("/a".to_string(), "/b".to_string()); 3],
+            }
+            .description(),
+            "Rename 3 files"
+        );
+        assert_eq!(
+            Operation::SetLabels {
+                entries: vec![
+                    ("/loc/a".to_string(), vec![]),
+                    ("/loc/b".to_string(), vec![3])
+                ],
+                labels: vec![4],
+            }
+            .description(),
+            "Change labels on 2 entries"
+        );
+        assert_eq!(
+            Operation::MoveToDir {
+                moves: vec![("/loc/a.mkv".to_string(), "/loc/a".to_string())],
+            }
+            .description(),
+            "Move a.mkv to dir"
+        );
+        assert_eq!(
+            Operation::MoveToDir {
+                moves: vec![("/loc/a.mkv".to_string(), "/loc/a".to_string()); 2],
+            }
+            .description(),
+            "Move 2 entries to dirs"
+        );
+    }
+}
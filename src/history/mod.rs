@@ -0,0 +1,3 @@
+pub mod history_panel;
+pub mod journal;
+pub mod undo;
@@ -0,0 +1,552 @@
+use fltk::app::Sender;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serious_organizer_lib::lens::Lens;
+
+use crate::error_dialog::ErrorDialog;
+use crate::file_ops;
+use crate::grade_store::GradeStore;
+use crate::history::journal::{History, Operation};
+use crate::model::message::Message;
+use crate::scan::scanner::{entry_by_path, Scanner};
+use crate::transfer::entry_transfer::{
+    entry_meta, EntryMeta, TransferItem, TransferMode, TransferOrigin,
+};
+use crate::trash::{self, TrashedItem};
+
+/// Sets the labels of each entry to exactly the given labels
+fn set_labels(lens: &mut Lens, labels: &[(String, Vec<u32>)]) -> Result<(), String> {
+    // Ids are looked up first, so nothing is changed when an entry is gone
+    let labels: Vec<(u32, &Vec<u32>)> = labels
+        .iter()
+        .map(|(path, wanted)| {
+            entry_by_path(lens, path)
+                .map(|e| (e.id as u32, wanted))
+                .ok_or_else(|| format!("Entry not found: {}", path))
+        })
+        .collect::<Result<_, String>>()?;
+
+    for (entry_id, wanted) in labels.into_iter() {
+        let current: HashSet<u32> = lens
+            .entry_labels(entry_id)
+            .into_iter()
+            .map(|id| id as u32)
+            .collect();
+
+        let to_remove: Vec<u32> = current
+            .iter()
+            .filter(|id| !wanted.contains(id))
+            .copied()
+            .collect();
+
+        lens.remove_entry_labels(vec![entry_id], to_remove);
+        lens.add_entry_labels(vec![entry_id], wanted.clone());
+    }
+    Ok(())
+}
+
+fn rename(
+    lens: &mut Lens,
+    grades: &Arc<Mutex<GradeStore>>,
+    scanner: &Scanner,
+    from: &str,
+    to: &str,
+) -> Result<(), String> {
+    let entry = entry_by_path(lens, from)
+        .cloned()
+        .ok_or_else(|| format!("Entry not found: {}", from))?;
+
+    let name = Path::new(to)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Invalid path: {}", to))?;
+
+    lens.rename_entry(entry, &name)
+        .map_err(|err| format!("Failed to rename {}: {}", from, err))?;
+    scanner.rescan_paths([Path::new(from), Path::new(to)].into_iter());
+    grades.lock().move_path(from, to)
+}
+
+/// The error of a step that failed, with what went wrong putting back the steps before it
+fn rolled_back(err: String, revert_errors: Vec<String>) -> String {
+    if revert_errors.is_empty() {
+        format!("{}\nThe changes before it were reverted", err)
+    } else {
+        format!("{}\nFailed to revert:\n{}", err, revert_errors.join("\n"))
+    }
+}
+
+/// Renames each entry from the first path to the second. Stops at the first error and
+/// renames the ones that were done back, so the entries are left as they were.
+fn rename_entries<'a>(
+    lens: &mut Lens,
+    grades: &Arc<Mutex<GradeStore>>,
+    scanner: &Scanner,
+    renames: impl Iterator<Item = (&'a String, &'a String)>,
+) -> Result<(), String> {
+    let mut done: Vec<(&String, &String)> = Vec::new();
+    for (from, to) in renames {
+        if let Err(err) = rename(lens, grades, scanner, from, to) {
+            let revert_errors = done
+                .iter()
+                .rev()
+                .filter_map(|(from, to)| rename(lens, grades, scanner, to, from).err())
+                .collect();
+            return Err(rolled_back(err, revert_errors));
+        }
+        done.push((from, to));
+    }
+
+    Ok(())
+}
+
+/// Moves each file from the first path to the second and rescans the locations on both
+/// sides. Stops at the first error and moves the files that were done back.
+fn move_files<'a>(
+    scanner: &Scanner,
+    moves: impl Iterator<Item = (&'a String, &'a String)>,
+) -> Result<(), String> {
+    let mut done: Vec<(&String, &String)> = Vec::new();
+    let mut result = Ok(());
+    for (from, to) in moves {
+        done.push((from, to));
+        if let Err(err) = file_ops::move_file(Path::new(from), Path::new(to)) {
+            let revert_errors = done
+                .iter()
+                .rev()
+                .skip(1)
+                .filter_map(|(from, to)| {
+                    file_ops::move_file(Path::new(to), Path::new(from))
+                        .err()
+                        .map(|err| format!("Failed to move back {}: {}", to, err))
+                })
+                .collect();
+            result = Err(rolled_back(
+                format!("Failed to move {}: {}", from, err),
+                revert_errors,
+            ));
+            break;
+        }
+    }
+
+    scanner.rescan_paths(
+        done.iter()
+            .flat_map(|(from, to)| [Path::new(from.as_str()), Path::new(to.as_str())]),
+    );
+
+    result
+}
+
+fn remove_copies(scanner: &Scanner, copies: &[(String, String)]) -> Result<(), String> {
+    let errors: Vec<String> = copies
+        .iter()
+        .filter_map(|(_, to)| {
+            fs::remove_file(to)
+                .err()
+                .map(|err| format!("Failed to remove copy {}: {}", to, err))
+        })
+        .collect();
+
+    scanner.rescan_paths(copies.iter().map(|(_, to)| Path::new(to.as_str())));
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Entry copies can be whole dirs, they go to the trash rather than being removed
+fn trash_copies(scanner: &Scanner, copies: &[(String, String)]) -> Result<(), String> {
+    let errors: Vec<String> = copies
+        .iter()
+        .filter_map(|(_, to)| {
+            trash::trash(Path::new(to))
+                .err()
+                .map(|err| format!("Failed to trash copy {}: {}", to, err))
+        })
+        .collect();
+
+    scanner.rescan_paths(copies.iter().map(|(_, to)| Path::new(to.as_str())));
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+fn restore_from_trash(scanner: &Scanner, items: &[TrashedItem]) -> Result<(), String> {
+    let errors: Vec<String> = items
+        .iter()
+        .filter_map(|item| {
+            trash::restore(item)
+                .err()
+                .map(|err| format!("{:?}: {}", item.original_path, err))
+        })
+        .collect();
+
+    scanner.rescan_paths(items.iter().map(|i| i.original_path.as_path()));
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Failed to restore:\n{}", errors.join("\n")))
+    }
+}
+
+/// Trashes the items again, returns the new trash locations
+fn trash_again(scanner: &Scanner, items: &[TrashedItem]) -> Result<Vec<TrashedItem>, String> {
+    let mut trashed = Vec::new();
+    let mut errors = Vec::new();
+
+    for item in items.iter() {
+        match trash::trash(&item.original_path) {
+            Ok(item) => trashed.push(item),
+            Err(err) => errors.push(format!("{:?}: {}", item.original_path, err)),
+        }
+    }
+
+    scanner.rescan_paths(items.iter().map(|i| i.original_path.as_path()));
+
+    if errors.is_empty() {
+        Ok(trashed)
+    } else {
+        Err(format!("Failed to move to trash:\n{}", errors.join("\n")))
+    }
+}
+
+/// Moves the file back out of its dir and rescans the location it is in
+fn move_out_of_dir(
+    grades: &Arc<Mutex<GradeStore>>,
+    scanner: &Scanner,
+    file_path: &str,
+    dir_path: &str,
+) -> Result<(), String> {
+    let file_name = Path::new(file_path)
+        .file_name()
+        .ok_or_else(|| format!("Invalid path: {}", file_path))?;
+    let moved_path = Path::new(dir_path).join(file_name);
+
+    fs::rename(&moved_path, file_path)
+        .map_err(|err| format!("Failed to move {:?} back: {}", moved_path, err))?;
+
+    if let Err(err) = fs::remove_dir(dir_path) {
+        println!("Left dir {} in place: {}", dir_path, err);
+    }
+
+    if let Err(err) = grades.lock().move_path(dir_path, file_path) {
+        println!("{}", err);
+    }
+
+    scanner.rescan_paths([Path::new(file_path), Path::new(dir_path)].into_iter());
+
+    Ok(())
+}
+
+fn move_into_dir(
+    lens: &mut Lens,
+    grades: &Arc<Mutex<GradeStore>>,
+    scanner: &Scanner,
+    file_path: &str,
+    dir_path: &str,
+) -> Result<(), String> {
+    let entry = entry_by_path(lens, file_path)
+        .cloned()
+        .ok_or_else(|| format!("Entry not found: {}", file_path))?;
+
+    lens.move_file_entry_to_dir_entry(&entry)
+        .map_err(|err| format!("Failed to move {} to dir: {}", file_path, err))?;
+    scanner.rescan_paths([Path::new(file_path), Path::new(dir_path)].into_iter());
+    grades.lock().move_path(file_path, dir_path)
+}
+
+/// Moves each file back out of its dir, keeps going past errors
+fn move_out_of_dirs(
+    grades: &Arc<Mutex<GradeStore>>,
+    scanner: &Scanner,
+    moves: &[(String, String)],
+) -> Result<(), String> {
+    let errors: Vec<String> = moves
+        .iter()
+        .rev()
+        .filter_map(|(file_path, dir_path)| {
+            move_out_of_dir(grades, scanner, file_path, dir_path).err()
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Moves each file into its dir again, keeps going past errors
+fn move_into_dirs(
+    lens: &mut Lens,
+    grades: &Arc<Mutex<GradeStore>>,
+    scanner: &Scanner,
+    moves: &[(String, String)],
+) -> Result<(), String> {
+    let errors: Vec<String> = moves
+        .iter()
+        .filter_map(|(file_path, dir_path)| {
+            move_into_dir(lens, grades, scanner, file_path, dir_path).err()
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+fn file_items<'a>(pairs: impl Iterator<Item = (&'a String, &'a String)>) -> Vec<TransferItem> {
+    pairs
+        .map(|(from, to)| TransferItem {
+            source: PathBuf::from(from),
+            target: PathBuf::from(to),
+            overwrite: false,
+            meta: EntryMeta::default(),
+        })
+        .collect()
+}
+
+/// Entries take their grade and labels along, like in the transfer that is undone
+fn entry_items<'a>(
+    lens: &Arc<Mutex<Lens>>,
+    grades: &Arc<Mutex<GradeStore>>,
+    pairs: impl Iterator<Item = (&'a String, &'a String)>,
+) -> Vec<TransferItem> {
+    let lens = lens.lock();
+    let grades = grades.lock();
+    pairs
+        .map(|(from, to)| TransferItem {
+            source: PathBuf::from(from),
+            target: PathBuf::from(to),
+            overwrite: false,
+            meta: entry_meta(&lens, &grades, from),
+        })
+        .collect()
+}
+
+/// Moves and copies can take long, undoing them runs as a background transfer
+fn undo_transfer(
+    operation: &Operation,
+    lens: &Arc<Mutex<Lens>>,
+    grades: &Arc<Mutex<GradeStore>>,
+) -> Option<(TransferMode, Vec<TransferItem>)> {
+    match operation {
+        Operation::MoveFiles { moves } => Some((
+            TransferMode::Move,
+            file_items(moves.iter().rev().map(|(old, new)| (new, old))),
+        )),
+        Operation::MoveEntries { moves } => Some((
+            TransferMode::Move,
+            entry_items(
+                lens,
+                grades,
+                moves.iter().rev().map(|(old, new)| (new, old)),
+            ),
+        )),
+        _ => None,
+    }
+}
+
+fn redo_transfer(
+    operation: &Operation,
+    lens: &Arc<Mutex<Lens>>,
+    grades: &Arc<Mutex<GradeStore>>,
+) -> Option<(TransferMode, Vec<TransferItem>)> {
+    match operation {
+        Operation::MoveFiles { moves } => Some((
+            TransferMode::Move,
+            file_items(moves.iter().map(|(old, new)| (old, new))),
+        )),
+        Operation::CopyFiles { copies } => Some((
+            TransferMode::Copy,
+            file_items(copies.iter().map(|(old, new)| (old, new))),
+        )),
+        Operation::MoveEntries { moves } => Some((
+            TransferMode::Move,
+            entry_items(lens, grades, moves.iter().map(|(old, new)| (old, new))),
+        )),
+        Operation::CopyEntries { copies } => Some((
+            TransferMode::Copy,
+            entry_items(lens, grades, copies.iter().map(|(old, new)| (old, new))),
+        )),
+        _ => None,
+    }
+}
+
+/// Undoes an operation that is not a transfer
+pub fn undo_operation(
+    operation: &Operation,
+    lens: &Arc<Mutex<Lens>>,
+    grades: &Arc<Mutex<GradeStore>>,
+    scanner: &Scanner,
+) -> Result<(), String> {
+    println!("Undo: {}", operation.description());
+
+    match operation {
+        Operation::SetGrade { entries, .. } => grades.lock().set_all(entries),
+        Operation::SetLabels { entries, .. } => set_labels(&mut lens.lock(), entries),
+        Operation::RenameEntry { old_path, new_path } => {
+            rename(&mut lens.lock(), grades, scanner, new_path, old_path)
+        }
+        Operation::RenameEntries { renames } => rename_entries(
+            &mut lens.lock(),
+            grades,
+            scanner,
+            renames.iter().rev().map(|(old, new)| (new, old)),
+        ),
+        Operation::RenameFiles { renames: moves } => {
+            move_files(scanner, moves.iter().rev().map(|(old, new)| (new, old)))
+        }
+        Operation::CopyFiles { copies } => remove_copies(scanner, copies),
+        Operation::CopyEntries { copies } => trash_copies(scanner, copies),
+        Operation::MoveToDir { moves } => move_out_of_dirs(grades, scanner, moves),
+        Operation::TrashEntries { items } | Operation::TrashFiles { items } => {
+            restore_from_trash(scanner, items)
+        }
+        Operation::MoveFiles { .. } | Operation::MoveEntries { .. } => {
+            Err(format!("Undo as a transfer: {}", operation.description()))
+        }
+        Operation::DeleteEntries { .. } | Operation::DeleteFiles { .. } => {
+            Err(format!("Can not undo: {}", operation.description()))
+        }
+    }
+}
+
+/// Does an operation that is not a transfer again, returns it as it was done this time
+pub fn redo_operation(
+    operation: &Operation,
+    lens: &Arc<Mutex<Lens>>,
+    grades: &Arc<Mutex<GradeStore>>,
+    scanner: &Scanner,
+) -> Result<Operation, String> {
+    println!("Redo: {}", operation.description());
+
+    match operation {
+        Operation::SetGrade { entries, grade } => {
+            let graded: Vec<(String, Option<i32>)> = entries
+                .iter()
+                .map(|(path, _)| (path.clone(), *grade))
+                .collect();
+            grades.lock().set_all(&graded)?;
+        }
+        Operation::SetLabels { entries, labels } => {
+            let wanted: Vec<(String, Vec<u32>)> = entries
+                .iter()
+                .map(|(path, _)| (path.clone(), labels.clone()))
+                .collect();
+            set_labels(&mut lens.lock(), &wanted)?;
+        }
+        Operation::RenameEntry { old_path, new_path } => {
+            rename(&mut lens.lock(), grades, scanner, old_path, new_path)?
+        }
+        Operation::RenameEntries { renames } => rename_entries(
+            &mut lens.lock(),
+            grades,
+            scanner,
+            renames.iter().map(|(old, new)| (old, new)),
+        )?,
+        Operation::RenameFiles { renames: moves } => {
+            move_files(scanner, moves.iter().map(|(old, new)| (old, new)))?
+        }
+        Operation::MoveToDir { moves } => move_into_dirs(&mut lens.lock(), grades, scanner, moves)?,
+        Operation::TrashEntries { items } => {
+            let items = trash_again(scanner, items)?;
+            return Ok(Operation::TrashEntries { items });
+        }
+        Operation::TrashFiles { items } => {
+            let items = trash_again(scanner, items)?;
+            return Ok(Operation::TrashFiles { items });
+        }
+        Operation::MoveFiles { .. }
+        | Operation::CopyFiles { .. }
+        | Operation::MoveEntries { .. }
+        | Operation::CopyEntries { .. } => {
+            return Err(format!("Redo as a transfer: {}", operation.description()));
+        }
+        Operation::DeleteEntries { .. } | Operation::DeleteFiles { .. } => {
+            return Err(format!("Can not redo: {}", operation.description()));
+        }
+    }
+
+    Ok(operation.clone())
+}
+
+fn finish(result: Result<(), String>, sender: &Sender<Message>) {
+    if let Err(err) = result {
+        println!("{}", err);
+        let err_dialog = ErrorDialog::new(err);
+        err_dialog.show();
+    }
+
+    sender.send(Message::EntryTableInvalidated);
+    sender.send(Message::FileTableInvalidated);
+    sender.send(Message::LabelTableInvalidated);
+    sender.send(Message::HistoryChanged);
+}
+
+/// Undoes the last operation, errors are shown in a dialog. The operation stays done
+/// when undoing it fails. Transfers are handed to the main loop and marked once finished.
+pub fn undo(
+    history: &Arc<Mutex<History>>,
+    lens: &Arc<Mutex<Lens>>,
+    grades: &Arc<Mutex<GradeStore>>,
+    scanner: &Scanner,
+    sender: &Sender<Message>,
+) {
+    let next = history.lock().next_undo();
+    if let Some((index, operation)) = next {
+        if let Some((mode, items)) = undo_transfer(&operation, lens, grades) {
+            sender.send(Message::StartTransfer(
+                mode,
+                TransferOrigin::Undo(index, operation),
+                items,
+            ));
+            return;
+        }
+
+        let result = undo_operation(&operation, lens, grades, scanner);
+        if result.is_ok() {
+            history.lock().mark_undone(index, &operation);
+        }
+        finish(result, sender);
+    }
+}
+
+/// Redoes the last undone operation, errors are shown in a dialog. The operation stays
+/// undone when redoing it fails. Transfers are handed to the main loop like in `undo`.
+pub fn redo(
+    history: &Arc<Mutex<History>>,
+    lens: &Arc<Mutex<Lens>>,
+    grades: &Arc<Mutex<GradeStore>>,
+    scanner: &Scanner,
+    sender: &Sender<Message>,
+) {
+    let next = history.lock().next_redo();
+    if let Some((index, operation)) = next {
+        if let Some((mode, items)) = redo_transfer(&operation, lens, grades) {
+            sender.send(Message::StartTransfer(
+                mode,
+                TransferOrigin::Redo(index, operation),
+                items,
+            ));
+            return;
+        }
+
+        let result = redo_operation(&operation, lens, grades, scanner).map(|done| {
+            history.lock().mark_redone(index, &operation, done);
+        });
+        finish(result, sender);
+    }
+}
@@ -10,6 +10,7 @@ use serious_organizer_lib::models::Entry;
 use parking_lot::Mutex;
 use std::{collections::HashSet, sync::Arc};
 
+use crate::history::journal::{History, Operation};
 use crate::label::entry_label_list::EntryLabelList;
 
 // use super::entry_label_list::EntryLabelList;
@@ -22,13 +23,16 @@ pub enum LabelMessage {
 
 pub struct EntryLabelDialog {
     lens: Arc<Mutex<Lens>>,
+    history: Arc<Mutex<History>>,
     entry_ids: Arc<Vec<u32>>,
+    entry_paths: Arc<Vec<String>>,
     select_labels: Arc<Mutex<HashSet<u32>>>,
 }
 
 impl EntryLabelDialog {
-    pub fn new(lens: Arc<Mutex<Lens>>, entries: Vec<Entry>) -> Self {
+    pub fn new(lens: Arc<Mutex<Lens>>, entries: Vec<Entry>, history: Arc<Mutex<History>>) -> Self {
         let entry_ids: Vec<u32> = entries.iter().map(|e| e.id as u32).collect();
+        let entry_paths: Vec<String> = entries.iter().map(|e| e.path.clone()).collect();
 
         // Gather all labels that have entries that are selected
         let mut org_labels = HashSet::new();
@@ -46,7 +50,9 @@ impl EntryLabelDialog {
 
         EntryLabelDialog {
             lens,
+            history,
             entry_ids: Arc::new(entry_ids),
+            entry_paths: Arc::new(entry_paths),
             select_labels: Arc::new(Mutex::new(select_labels)),
         }
     }
@@ -74,8 +80,10 @@ impl EntryLabelDialog {
 
         // Button save callback
         let entry_ids_c = self.entry_ids.clone();
+        let entry_paths_c = self.entry_paths.clone();
         let lbl_table_c = lbl_table.clone();
         let lens_c = self.lens.clone();
+        let history_c = self.history.clone();
         let sender_c = sender.clone();
         but_save.set_callback(move |_| {
             let currently_selected_labels = lbl_table_c.selected_label_ids.lock();
@@ -85,6 +93,17 @@ impl EntryLabelDialog {
 
             // Get all labels
             let mut lens = lens_c.lock();
+
+            // Remember the labels of each entry so the change can be undone
+            let before: Vec<(String, Vec<u32>)> = entry_ids_c
+                .iter()
+                .zip(entry_paths_c.iter())
+                .map(|(id, path)| {
+                    let labels = lens.entry_labels(*id).into_iter().map(|l| l as u32);
+                    (path.clone(), labels.collect())
+                })
+                .collect();
+
             let mut labels = HashSet::new();
             for label in lens.get_labels().iter() {
                 labels.insert(label.id as u32);
@@ -111,6 +130,13 @@ impl EntryLabelDialog {
             let entries = entry_ids_c.iter().copied().collect();
             lens.remove_entry_labels(entries, to_remove);
 
+            let mut labels_after: Vec<u32> = currently_selected_labels.iter().copied().collect();
+            labels_after.sort_unstable();
+            history_c.lock().push(Operation::SetLabels {
+                entries: before,
+                labels: labels_after,
+            });
+
             sender_c.send(LabelMessage::ExitDialog);
         });
 
@@ -0,0 +1,482 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use serious_organizer_lib::lens::LabelState;
+
+/// Label filter such as `(Drama OR Comedy) AND NOT Watched`.
+///
+/// Keywords are case insensitive, `unlabeled` matches entries without labels and names
+/// with spaces or that clash with a keyword can be quoted: `"Sci Fi"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelExpr {
+    Label { id: u32, name: String },
+    Unlabeled,
+    Not(Box<LabelExpr>),
+    And(Vec<LabelExpr>),
+    Or(Vec<LabelExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    /// Column of the problem, starting at 1
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (column {})", self.message, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Unlabeled,
+    Name(String),
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Open => "'('".to_string(),
+        Token::Close => "')'".to_string(),
+        Token::And => "AND".to_string(),
+        Token::Or => "OR".to_string(),
+        Token::Not => "NOT".to_string(),
+        Token::Unlabeled => "unlabeled".to_string(),
+        Token::Name(name) => format!("'{}'", name),
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut ix = 0;
+
+    while ix < chars.len() {
+        let start = ix;
+        match chars[ix] {
+            c if c.is_whitespace() => ix += 1,
+            '(' => {
+                tokens.push((Token::Open, start));
+                ix += 1;
+            }
+            ')' => {
+                tokens.push((Token::Close, start));
+                ix += 1;
+            }
+            '"' => {
+                let end = chars[ix + 1..]
+                    .iter()
+                    .position(|c| *c == '"')
+                    .map(|e| ix + 1 + e)
+                    .ok_or(ParseError {
+                        message: "Missing closing quote".to_string(),
+                        column: start + 1,
+                    })?;
+                let name: String = chars[ix + 1..end].iter().collect();
+                tokens.push((Token::Name(name), start));
+                ix = end + 1;
+            }
+            _ => {
+                while ix < chars.len() && !chars[ix].is_whitespace() && !"()\"".contains(chars[ix])
+                {
+                    ix += 1;
+                }
+                let word: String = chars[start..ix].iter().collect();
+                let token = match word.to_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "unlabeled" => Token::Unlabeled,
+                    _ => Token::Name(word),
+                };
+                tokens.push((token, start));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    end: usize,
+    labels: &'a [(u32, String)],
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.0)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.pos).map(|t| t.1).unwrap_or(self.end) + 1
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        ParseError {
+            message,
+            column: self.column(),
+        }
+    }
+
+    fn or(&mut self) -> Result<LabelExpr, ParseError> {
+        let mut terms = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            terms.push(self.and()?);
+        }
+
+        Ok(if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            LabelExpr::Or(terms)
+        })
+    }
+
+    fn and(&mut self) -> Result<LabelExpr, ParseError> {
+        let mut terms = vec![self.unary()?];
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            terms.push(self.unary()?);
+        }
+
+        Ok(if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            LabelExpr::And(terms)
+        })
+    }
+
+    fn unary(&mut self) -> Result<LabelExpr, ParseError> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.error("Expected a label".to_string())),
+        };
+
+        match token {
+            Token::Not => {
+                self.pos += 1;
+                Ok(LabelExpr::Not(Box::new(self.unary()?)))
+            }
+            Token::Open => {
+                self.pos += 1;
+                let expr = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(self.error("Expected ')'".to_string()));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Token::Unlabeled => {
+                self.pos += 1;
+                Ok(LabelExpr::Unlabeled)
+            }
+            Token::Name(name) => {
+                let label = self
+                    .labels
+                    .iter()
+                    .find(|(_, n)| n.to_lowercase() == name.to_lowercase())
+                    .ok_or_else(|| self.error(format!("Unknown label '{}'", name)))?;
+                self.pos += 1;
+                Ok(LabelExpr::Label {
+                    id: label.0,
+                    name: label.1.clone(),
+                })
+            }
+            other => Err(self.error(format!("Expected a label, found {}", describe(&other)))),
+        }
+    }
+}
+
+/// Parses an expression using the given `(id, name)` labels, an empty text is no filter
+pub fn parse(text: &str, labels: &[(u32, String)]) -> Result<Option<LabelExpr>, ParseError> {
+    let tokens = tokenize(text)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end: text.chars().count(),
+        labels,
+    };
+
+    let expr = parser.or()?;
+    if let Some(token) = parser.peek() {
+        let message = match token {
+            Token::Close => "Unexpected ')'".to_string(),
+            other => format!("Expected AND or OR before {}", describe(other)),
+        };
+        return Err(parser.error(message));
+    }
+
+    Ok(Some(expr))
+}
+
+fn needs_quotes(name: &str) -> bool {
+    name.is_empty()
+        || name
+            .chars()
+            .any(|c| c.is_whitespace() || "()\"".contains(c))
+        || ["and", "or", "not", "unlabeled"].contains(&name.to_lowercase().as_str())
+}
+
+impl fmt::Display for LabelExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter, terms: &[LabelExpr], sep: &str| {
+            for (ix, term) in terms.iter().enumerate() {
+                if ix > 0 {
+                    write!(f, " {} ", sep)?;
+                }
+                // AND binds tighter than OR, only OR inside AND needs parentheses
+                match (sep, term) {
+                    ("AND", LabelExpr::Or(_)) => write!(f, "({})", term)?,
+                    _ => write!(f, "{}", term)?,
+                }
+            }
+            Ok(())
+        };
+
+        match self {
+            LabelExpr::Label { name, .. } if needs_quotes(name) => write!(f, "\"{}\"", name),
+            LabelExpr::Label { name, .. } => write!(f, "{}", name),
+            LabelExpr::Unlabeled => write!(f, "unlabeled"),
+            LabelExpr::Not(inner) => match **inner {
+                LabelExpr::And(_) | LabelExpr::Or(_) => write!(f, "NOT ({})", inner),
+                _ => write!(f, "NOT {}", inner),
+            },
+            LabelExpr::And(terms) => join(f, terms, "AND"),
+            LabelExpr::Or(terms) => join(f, terms, "OR"),
+        }
+    }
+}
+
+impl LabelExpr {
+    /// Checks an entry with the given label ids
+    pub fn matches(&self, labels: &[u32]) -> bool {
+        match self {
+            LabelExpr::Label { id, .. } => labels.contains(id),
+            LabelExpr::Unlabeled => labels.is_empty(),
+            LabelExpr::Not(inner) => !inner.matches(labels),
+            LabelExpr::And(terms) => terms.iter().all(|t| t.matches(labels)),
+            LabelExpr::Or(terms) => terms.iter().any(|t| t.matches(labels)),
+        }
+    }
+
+    /// Distinct labels the expression asks for and labels it rules out, NOT flips between them
+    pub fn label_counts(&self) -> (usize, usize) {
+        fn collect(expr: &LabelExpr, negated: bool, ids: &mut (HashSet<u32>, HashSet<u32>)) {
+            match expr {
+                LabelExpr::Label { id, .. } if negated => {
+                    ids.1.insert(*id);
+                }
+                LabelExpr::Label { id, .. } => {
+                    ids.0.insert(*id);
+                }
+                LabelExpr::Unlabeled => (),
+                LabelExpr::Not(inner) => collect(inner, !negated, ids),
+                LabelExpr::And(terms) | LabelExpr::Or(terms) => {
+                    terms.iter().for_each(|t| collect(t, negated, ids))
+                }
+            }
+        }
+
+        let mut ids = (HashSet::new(), HashSet::new());
+        collect(self, false, &mut ids);
+        (ids.0.len(), ids.1.len())
+    }
+
+    fn terms(&self) -> Vec<LabelExpr> {
+        match self {
+            LabelExpr::And(terms) => terms.clone(),
+            other => vec![other.clone()],
+        }
+    }
+}
+
+fn from_terms(mut terms: Vec<LabelExpr>) -> Option<LabelExpr> {
+    match terms.len() {
+        0 => None,
+        1 => terms.pop(),
+        _ => Some(LabelExpr::And(terms)),
+    }
+}
+
+fn is_label(expr: &LabelExpr, label_id: u32) -> bool {
+    matches!(expr, LabelExpr::Label { id, .. } if *id == label_id)
+}
+
+fn is_not_label(expr: &LabelExpr, label_id: u32) -> bool {
+    matches!(expr, LabelExpr::Not(inner) if is_label(inner, label_id))
+}
+
+/// How the sidebar shows a label, included or excluded when it is one of the AND terms
+pub fn label_state(expr: Option<&LabelExpr>, label_id: u32) -> LabelState {
+    let terms = expr.map(|e| e.terms()).unwrap_or_default();
+
+    if terms.iter().any(|t| is_label(t, label_id)) {
+        LabelState::Include
+    } else if terms.iter().any(|t| is_not_label(t, label_id)) {
+        LabelState::Exclude
+    } else {
+        LabelState::Unset
+    }
+}
+
+/// Sidebar click on a label. Include adds the label as an AND term or removes an exclude,
+/// exclude adds `NOT label` or removes an include.
+pub fn toggle_label(
+    expr: Option<&LabelExpr>,
+    label_id: u32,
+    name: &str,
+    include: bool,
+) -> Option<LabelExpr> {
+    let mut terms = expr.map(|e| e.terms()).unwrap_or_default();
+    let label = LabelExpr::Label {
+        id: label_id,
+        name: name.to_string(),
+    };
+
+    match (label_state(expr, label_id), include) {
+        (LabelState::Unset, true) => terms.push(label),
+        (LabelState::Unset, false) => terms.push(LabelExpr::Not(Box::new(label))),
+        (LabelState::Exclude, true) => terms.retain(|t| !is_not_label(t, label_id)),
+        (LabelState::Include, false) => terms.retain(|t| !is_label(t, label_id)),
+        _ => (),
+    }
+
+    from_terms(terms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels() -> Vec<(u32, String)> {
+        vec![
+            (1, "Drama".to_string()),
+            (2, "Comedy".to_string()),
+            (3, "Watched".to_string()),
+            (4, "Sci Fi".to_string()),
+        ]
+    }
+
+    fn parse_ok(text: &str) -> LabelExpr {
+        parse(text, &labels()).unwrap().unwrap()
+    }
+
+    fn error(text: &str) -> ParseError {
+        parse(text, &labels()).unwrap_err()
+    }
+
+    #[test]
+    fn test_parse_and_match() {
+        let expr = parse_ok("(drama OR Comedy) and not Watched");
+
+        assert!(expr.matches(&[1]));
+        assert!(expr.matches(&[2, 4]));
+        assert!(!expr.matches(&[1, 3]));
+        assert!(!expr.matches(&[]));
+        assert_eq!(expr.to_string(), "(Drama OR Comedy) AND NOT Watched");
+    }
+
+    #[test]
+    fn test_precedence() {
+        // AND binds tighter than OR
+        let expr = parse_ok("Drama OR Comedy AND Watched");
+        assert!(expr.matches(&[1]));
+        assert!(!expr.matches(&[2]));
+        assert!(expr.matches(&[2, 3]));
+    }
+
+    #[test]
+    fn test_unlabeled_and_quotes() {
+        let expr = parse_ok("unlabeled OR \"sci fi\"");
+        assert!(expr.matches(&[]));
+        assert!(expr.matches(&[4]));
+        assert!(!expr.matches(&[1]));
+        assert_eq!(expr.to_string(), "unlabeled OR \"Sci Fi\"");
+
+        assert!(parse_ok("NOT unlabeled").matches(&[2]));
+        assert_eq!(parse("  ", &labels()), Ok(None));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            error("Drama AND Horror"),
+            ParseError {
+                message: "Unknown label 'Horror'".to_string(),
+                column: 11
+            }
+        );
+        assert_eq!(error("(Drama OR Comedy").message, "Expected ')'");
+        assert_eq!(error("(Drama OR Comedy").column, 17);
+        assert_eq!(error("Drama)").message, "Unexpected ')'");
+        assert_eq!(
+            error("Drama Comedy").message,
+            "Expected AND or OR before 'Comedy'"
+        );
+        assert_eq!(error("NOT").message, "Expected a label");
+        assert_eq!(
+            error("Drama AND OR Comedy").message,
+            "Expected a label, found OR"
+        );
+        assert_eq!(error("\"Sci Fi").message, "Missing closing quote");
+    }
+
+    #[test]
+    fn test_label_counts() {
+        assert_eq!(
+            parse_ok("(Drama OR Comedy) AND NOT Watched").label_counts(),
+            (2, 1)
+        );
+        assert_eq!(
+            parse_ok("NOT (Drama AND NOT Comedy)").label_counts(),
+            (1, 1)
+        );
+        assert_eq!(parse_ok("unlabeled").label_counts(), (0, 0));
+        assert_eq!(parse_ok("Drama OR Drama").label_counts(), (1, 0));
+        assert_eq!(
+            parse_ok("(Drama AND NOT Watched) OR (Comedy AND NOT Watched)").label_counts(),
+            (2, 1)
+        );
+    }
+
+    #[test]
+    fn test_sidebar_toggle() {
+        let expr = toggle_label(None, 1, "Drama", true);
+        assert_eq!(label_state(expr.as_ref(), 1), LabelState::Include);
+
+        let expr = toggle_label(expr.as_ref(), 4, "Sci Fi", false);
+        assert_eq!(
+            expr.as_ref().unwrap().to_string(),
+            "Drama AND NOT \"Sci Fi\""
+        );
+        assert_eq!(label_state(expr.as_ref(), 4), LabelState::Exclude);
+
+        // Including an excluded label removes the exclude, like the old Include/Exclude cycle
+        let expr = toggle_label(expr.as_ref(), 4, "Sci Fi", true);
+        assert_eq!(expr.as_ref().unwrap().to_string(), "Drama");
+
+        let expr = toggle_label(expr.as_ref(), 1, "Drama", false);
+        assert_eq!(expr, None);
+
+        // Typed expressions are kept as one term
+        let typed = parse_ok("Drama OR Comedy");
+        assert_eq!(label_state(Some(&typed), 1), LabelState::Unset);
+        let expr = toggle_label(Some(&typed), 3, "Watched", false);
+        assert_eq!(
+            expr.unwrap().to_string(),
+            "(Drama OR Comedy) AND NOT Watched"
+        );
+    }
+}
@@ -8,6 +8,7 @@ use fltk::{enums::*, prelude::*, *};
 use serious_organizer_lib::lens::LabelState;
 use serious_organizer_lib::lens::Lens;
 
+use crate::label::label_expr::{self, LabelExpr};
 use crate::model::message::Message;
 use crate::table_utils::{draw_data, draw_header, ColHeader, ColSize, resize_column};
 
@@ -15,13 +16,20 @@ use crate::table_utils::{draw_data, draw_header, ColHeader, ColSize, resize_colu
 pub struct LabelList {
     pub wid: TableRow,
     lens: Arc<Mutex<Lens>>,
+    label_expr: Arc<Mutex<Option<LabelExpr>>>,
     sender: Sender<Message>,
 }
 
 // use std::rc::Rc;
 
 impl LabelList {
-    pub fn new(w: i32, h: i32, lens: Arc<Mutex<Lens>>, sender: Sender<Message>) -> LabelList {
+    pub fn new(
+        w: i32,
+        h: i32,
+        lens: Arc<Mutex<Lens>>,
+        label_expr: Arc<Mutex<Option<LabelExpr>>>,
+        sender: Sender<Message>,
+    ) -> LabelList {
         let headers = vec![
             ColHeader::new("Name", ColSize::Greedy),
             ColHeader::new("State", ColSize::Greedy),
@@ -31,6 +39,7 @@ impl LabelList {
         let mut table = LabelList {
             wid: TableRow::default().with_size(w, h),
             lens,
+            label_expr,
             sender,
         };
 
@@ -55,6 +64,7 @@ impl LabelList {
         resize_column(&mut table, &headers);
         
         let lens_c = table.lens.clone();
+        let label_expr_c = table.label_expr.clone();
 
         table
             .wid
@@ -67,7 +77,9 @@ impl LabelList {
                     let l = lens_c.lock();
                     let label_lst = l.get_labels();
                     if let Some(lbl) = label_lst.get(row as usize) {
-                        let lbl_text = match lbl.state {
+                        let state =
+                            label_expr::label_state(label_expr_c.lock().as_ref(), lbl.id as u32);
+                        let lbl_text = match state {
                             LabelState::Unset => "U",
                             LabelState::Include => "I",
                             LabelState::Exclude => "E",
@@ -132,60 +144,38 @@ impl LabelList {
             && self.callback_context() == TableContext::Cell
         {
             let lbl_ix = self.callback_row() as usize;
-            let state_change = {
-                let mut lens = lens.lock();
+            let query = {
+                let lens = lens.lock();
 
                 let labels_list = lens.get_labels();
                 if let Some(lbl) = labels_list.get(lbl_ix) {
                     let label_id = lbl.id as u32;
+                    let expr = self.label_expr.lock();
 
                     let btn = app::event_button();
 
-                    // Left click
-                    if btn == 1 {
+                    // Left click includes, right click excludes
+                    if btn == 1 || btn == 3 {
                         println!(
-                            "Mouse left clicked {:?} {:?} {:?}",
-                            lbl.state,
+                            "Mouse clicked {} {:?} {:?}",
+                            btn,
                             lbl.name,
                             self.callback_context()
                         );
 
-                        match lbl.state {
-                            LabelState::Unset => lens.add_inlude_label(label_id),
-                            LabelState::Include => (), // Do nothing
-                            LabelState::Exclude => lens.remove_label_filter(label_id),
-                        };
-
-                        self.redraw();
-                        true
-
-                    // Right click
-                    } else if btn == 3 {
-                        println!(
-                            "Mouse right clicked {:?} {:?} {:?}",
-                            lbl.state,
-                            lbl.name,
-                            self.callback_context()
-                        );
-
-                        match lbl.state {
-                            LabelState::Unset => lens.add_exclude_label(label_id),
-                            LabelState::Include => lens.remove_label_filter(label_id),
-                            LabelState::Exclude => (), // Do nothing
-                        };
-
-                        self.redraw();
-                        true
+                        let expr =
+                            label_expr::toggle_label(expr.as_ref(), label_id, &lbl.name, btn == 1);
+                        Some(expr.map(|e| e.to_string()).unwrap_or_default())
                     } else {
-                        false
+                        None
                     }
                 } else {
-                    false
+                    None
                 }
             };
 
-            if state_change {
-                self.sender.send(Message::EntryTableInvalidated);
+            if let Some(query) = query {
+                self.sender.send(Message::LabelQueryChanged(query));
                 return true;
             }
         }
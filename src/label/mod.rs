@@ -2,3 +2,4 @@ pub mod add_label_dialog;
 pub mod entry_label_dialog;
 pub mod label_list;
 pub mod entry_label_list;
+pub mod label_expr;
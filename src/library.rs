@@ -0,0 +1,222 @@
+use fltk::app::Sender;
+use fltk::dialog::{NativeFileChooser, NativeFileChooserOptions, NativeFileChooserType};
+use fltk::enums::Shortcut;
+use fltk::menu::{MenuBar, MenuFlag};
+use fltk::prelude::*;
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::model::message::Message;
+
+/// Libraries kept in the File menu
+pub const MAX_RECENT: usize = 8;
+
+const LIBRARY_EXTENSION: &str = "sqlite3";
+
+/// Moves the library to the front of the recent list
+pub fn add_recent(recent: &mut Vec<String>, path: &str) {
+    recent.retain(|p| p != path);
+    recent.insert(0, path.to_string());
+    recent.truncate(MAX_RECENT);
+}
+
+/// Library name shown in the window title, the database file name without extension
+pub fn library_name(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+pub fn window_title(path: &str) -> String {
+    format!("Serious Organizer - {}", library_name(path))
+}
+
+/// Menu item text for a path, slashes would otherwise open submenus
+fn menu_label(path: &str) -> String {
+    path.replace('\\', "\\\\")
+        .replace('/', "\\/")
+        .replace('&', "&&")
+}
+
+/// New databases get the usual extension when none was typed
+fn with_library_extension(path: PathBuf) -> PathBuf {
+    if path.extension().is_some() {
+        path
+    } else {
+        path.with_extension(LIBRARY_EXTENSION)
+    }
+}
+
+/// Fills the File menu, the recent list changes whenever another library is opened
+pub fn build_file_menu(menu_bar: &mut MenuBar, sender: &Sender<Message>, recent: &[String]) {
+    menu_bar.clear();
+
+    menu_bar.add_emit(
+        "&File/&Open Library...",
+        Shortcut::Ctrl | 'o',
+        MenuFlag::Normal,
+        sender.clone(),
+        Message::ShowOpenLibrary,
+    );
+    menu_bar.add_emit(
+        "&File/&New Library...",
+        Shortcut::None,
+        MenuFlag::MenuDivider,
+        sender.clone(),
+        Message::ShowNewLibrary,
+    );
+
+    if recent.is_empty() {
+        menu_bar.add_emit(
+            "&File/&Recent Libraries/(none)",
+            Shortcut::None,
+            MenuFlag::Inactive,
+            sender.clone(),
+            Message::ShowOpenLibrary,
+        );
+    }
+
+    for path in recent.iter() {
+        menu_bar.add_emit(
+            &format!("&File/&Recent Libraries/{}", menu_label(path)),
+            Shortcut::None,
+            MenuFlag::Normal,
+            sender.clone(),
+            Message::OpenLibrary(path.clone()),
+        );
+    }
+
+    menu_bar.add_emit(
+        "&File/&Settings...",
+        Shortcut::None,
+        MenuFlag::Normal,
+        sender.clone(),
+        Message::ShowSettings,
+    );
+}
+
+fn chooser(title: &str, kind: NativeFileChooserType, current: &str) -> NativeFileChooser {
+    let mut chooser = NativeFileChooser::new(kind);
+    chooser.set_title(title);
+    chooser.set_filter("Libraries\t*.sqlite3\nAll Files\t*");
+
+    if let Some(dir) = Path::new(current).parent() {
+        let _ = chooser.set_directory(&dir);
+    }
+    chooser
+}
+
+fn chosen_path(chooser: &NativeFileChooser) -> Option<PathBuf> {
+    Some(chooser.filename()).filter(|p| !p.as_os_str().is_empty())
+}
+
+/// Start of every SQLite database file
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// The lens and the library tables need a SQLite database. An empty or missing file is
+/// fine, it becomes one when opened.
+pub fn check_library(path: &Path) -> Result<(), String> {
+    let mut header = Vec::new();
+    let read = File::open(path).and_then(|file| file.take(16).read_to_end(&mut header));
+
+    match read {
+        Ok(_) if header.is_empty() || header == SQLITE_HEADER => Ok(()),
+        Ok(_) => Err(format!("{:?} is not a library database", path)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(format!("Failed to read library {:?}: {}", path, err)),
+    }
+}
+
+/// Asks for an existing database to open
+pub fn choose_library(current: &str) -> Option<String> {
+    let mut chooser = chooser("Open library", NativeFileChooserType::BrowseFile, current);
+    chooser.show();
+
+    chosen_path(&chooser).map(|p| p.to_string_lossy().to_string())
+}
+
+/// Asks where to put a new database and creates the empty file for it
+pub fn create_library(current: &str) -> Option<Result<String, String>> {
+    let mut chooser = chooser(
+        "New library",
+        NativeFileChooserType::BrowseSaveFile,
+        current,
+    );
+    chooser.set_option(NativeFileChooserOptions::SaveAsConfirm);
+    chooser.set_preset_file("Library.sqlite3");
+    chooser.show();
+
+    let path = with_library_extension(chosen_path(&chooser)?);
+    if path.exists() {
+        println!("Library exists, opening {:?}", path);
+        return Some(Ok(path.to_string_lossy().to_string()));
+    }
+
+    Some(
+        File::create(&path)
+            .map(|_| path.to_string_lossy().to_string())
+            .map_err(|err| format!("Failed to create library {:?}: {}", path, err)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_add_recent() {
+        let mut recent = Vec::new();
+        for ix in 0..10 {
+            add_recent(&mut recent, &format!("/data/{}.sqlite3", ix));
+        }
+        assert_eq!(recent.len(), MAX_RECENT);
+        assert_eq!(recent[0], "/data/9.sqlite3");
+
+        add_recent(&mut recent, "/data/5.sqlite3");
+        assert_eq!(recent.len(), MAX_RECENT);
+        assert_eq!(
+            &recent[..3],
+            ["/data/5.sqlite3", "/data/9.sqlite3", "/data/8.sqlite3"]
+        );
+    }
+
+    #[test]
+    fn test_names_and_labels() {
+        assert_eq!(library_name("/data/Movies.sqlite3"), "Movies");
+        assert_eq!(window_title("Music.db"), "Serious Organizer - Music");
+        assert_eq!(menu_label("/data/A&B.sqlite3"), "\\/data\\/A&&B.sqlite3");
+        assert_eq!(menu_label("C:\\Lib.sqlite3"), "C:\\\\Lib.sqlite3");
+
+        assert_eq!(
+            with_library_extension(PathBuf::from("/data/Lib")),
+            PathBuf::from("/data/Lib.sqlite3")
+        );
+        assert_eq!(
+            with_library_extension(PathBuf::from("/data/Lib.db")),
+            PathBuf::from("/data/Lib.db")
+        );
+    }
+
+    #[test]
+    fn test_check_library() {
+        let dir = temp_dir("library_check");
+        let empty = dir.join("empty.sqlite3");
+        let library = dir.join("library.sqlite3");
+        let text = dir.join("notes.txt");
+        fs::write(&empty, "").unwrap();
+        fs::write(&library, b"SQLite format 3\0 and the rest").unwrap();
+        fs::write(&text, "SQLite format 2").unwrap();
+
+        assert!(check_library(&empty).is_ok());
+        assert!(check_library(&library).is_ok());
+        assert!(check_library(&dir.join("missing.sqlite3")).is_ok());
+        assert!(check_library(&text).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,31 +1,110 @@
+use fltk::button::Button;
 use fltk::frame::Frame;
+use fltk::misc::Progress;
 use fltk::prelude::*;
-use fltk::window::*;
+use fltk::{enums::*, window::*};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::scan::scanner::{ScanProgress, ScanSummary};
+use crate::table_utils::pretty_size;
 
 pub struct LoadingDialog {
     dialog: Window,
+    status: Frame,
+    counts: Frame,
+    progress: Progress,
+    but_cancel: Button,
+    finished: Arc<AtomicBool>,
 }
 
 impl LoadingDialog {
-    pub fn new() -> Self {
-        let mut dialog = Window::new(300, 325, 450, 120, "Loading!");
-        dialog.make_modal(true);
+    pub fn new(cancel: Arc<AtomicBool>) -> Self {
+        let mut dialog = Window::new(300, 325, 450, 130, "Loading!");
+
+        let mut status = Frame::new(10, 10, 430, 25, None);
+        status.set_label("Loading...");
+        status.set_label_size(14);
+        status.set_align(Align::Left | Align::Inside | Align::Clip);
+
+        let mut counts = Frame::new(10, 35, 430, 20, None);
+        counts.set_label_size(10);
+        counts.set_align(Align::Left | Align::Inside);
 
-        let mut output_name = Frame::new(10, 10, 390, 25, None);
-        output_name.set_label("Loading...");
-        output_name.set_label_size(20);
+        let mut progress = Progress::new(10, 60, 430, 20, None);
+        progress.set_minimum(0.);
+        progress.set_maximum(1.);
+        progress.set_selection_color(Color::Blue);
+
+        // Cancels a running scan, closes the dialog once the scan is finished
+        let finished = Arc::new(AtomicBool::new(false));
+        let mut but_cancel = Button::new(10, 95, 60, 25, "Cancel");
+        let mut status_c = status.clone();
+        let mut dialog_c = dialog.clone();
+        let finished_c = finished.clone();
+        but_cancel.set_callback(move |_| {
+            if finished_c.load(Ordering::Relaxed) {
+                dialog_c.hide();
+            } else {
+                cancel.store(true, Ordering::Relaxed);
+                status_c.set_label("Cancelling...");
+            }
+        });
 
         dialog.end();
 
-        LoadingDialog { dialog }
+        LoadingDialog {
+            dialog,
+            status,
+            counts,
+            progress,
+            but_cancel,
+            finished,
+        }
     }
 
     pub fn show(&mut self) {
+        self.finished.store(false, Ordering::Relaxed);
+        self.but_cancel.set_label("Cancel");
+        self.status.set_label("Loading...");
+        self.counts.set_label("");
+        self.progress.set_value(0.);
         self.dialog.show();
+    }
+
+    pub fn update(&mut self, progress: &ScanProgress) {
+        self.status.set_label(&format!(
+            "Scanning {} ({}/{})",
+            progress.location,
+            progress.location_ix,
+            progress.location_count
+        ));
+        self.counts.set_label(&format!(
+            "Dirs: {}  Files: {}  Size: {}",
+            progress.dirs,
+            progress.files,
+            pretty_size(progress.bytes)
+        ));
+
+        self.progress.set_maximum(progress.location_count.max(1) as f64);
+        self.progress.set_value(progress.location_ix as f64);
+        self.dialog.redraw();
+    }
+
+    pub fn finish(&mut self, summary: &ScanSummary) {
+        self.finished.store(true, Ordering::Relaxed);
+        self.but_cancel.set_label("Close");
 
-        // while dialog.shown() {
-        //     let _ = fltk::app::wait();
-        // }
+        self.status.set_label(&format!(
+            "Done. Added: {}  Removed: {}  Modified: {}",
+            summary.added, summary.removed, summary.modified
+        ));
+        self.counts.set_label(&format!(
+            "{} unchanged locations skipped, {} offline",
+            summary.skipped_locations, summary.offline_locations
+        ));
+        self.progress.set_value(self.progress.maximum());
+        self.dialog.redraw();
     }
 
     pub fn hide(&mut self) {
@@ -1,12 +1,23 @@
 use fltk::table::TableRow;
-use fltk::{button::*, input::*, window::*};
+use fltk::{button::*, dialog::*, frame::*, input::*, window::*};
 use fltk::{enums::*, prelude::*};
 use serious_organizer_lib::lens::Lens;
 // use serious_organizer_lib::lens
 use parking_lot::Mutex;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 
+use crate::grade_store::GradeStore;
+use crate::location::location_options::LocationOptionsStore;
+use crate::location::location_path::{is_online, validate_location_path};
+use crate::location::location_store;
+use crate::location::scan_rules_editor::ScanRulesEditor;
 use crate::location_table;
+use crate::scan::scan_index::rebase_path;
+use crate::scan::scanner::Scanner;
+use crate::table_utils::{pretty_size, pretty_time};
 
 pub struct Location {
     name: Option<String>,
@@ -46,16 +57,124 @@ impl Location {
     }
 }
 
+/// Checks the inputs, shows any problem with the path and activates the buttons that can be used
+fn validate_inputs(
+    lens: &Arc<Mutex<Lens>>,
+    location: &Location,
+    selected: Option<usize>,
+    frame_error: &mut Frame,
+    but_save: &mut Button,
+    but_update: &mut Button,
+) {
+    let (_name, path) = match location.values() {
+        Some(values) => values,
+        None => {
+            frame_error.set_label("");
+            but_save.deactivate();
+            but_update.deactivate();
+            return;
+        }
+    };
+
+    let locations: Vec<(i32, String, String)> = lens
+        .lock()
+        .get_locations()
+        .iter()
+        .map(|loc| (loc.id, loc.name.to_string(), loc.path.to_string()))
+        .collect();
+    let selected_id = selected.and_then(|ix| locations.get(ix)).map(|loc| loc.0);
+
+    let add_result = validate_location_path(&path, &locations, None);
+    let update_result = selected_id.map(|id| validate_location_path(&path, &locations, Some(id)));
+
+    // With a row selected the inputs belong to that location
+    if add_result.is_ok() && selected.is_none() {
+        but_save.activate();
+    } else {
+        but_save.deactivate();
+    }
+
+    match update_result {
+        Some(Ok(())) => but_update.activate(),
+        _ => but_update.deactivate(),
+    }
+
+    let error = match update_result {
+        Some(result) => result.err(),
+        None => add_result.err(),
+    };
+    frame_error.set_label(&error.unwrap_or_default());
+    frame_error.redraw();
+}
+
+/// Entry count and size of each location in the lens, an entry belongs to the location
+/// its path starts with
+fn location_totals(lens: &Lens) -> HashMap<i32, (usize, i64)> {
+    let locations = lens.get_locations();
+    let mut totals = HashMap::new();
+
+    for entry in (0..lens.get_dir_count()).filter_map(|ix| lens.get_dir_entry(ix)) {
+        let location = locations
+            .iter()
+            .find(|l| Path::new(&entry.path).starts_with(&l.path));
+        if let Some(location) = location {
+            let total = totals.entry(location.id).or_insert((0, 0));
+            total.0 += 1;
+            total.1 += entry.size;
+        }
+    }
+
+    totals
+}
+
+/// Labels of the entries below `old_root`, by the path the entry has below `new_root`
+fn moved_labels(lens: &Lens, old_root: &str, new_root: &str) -> Vec<(String, Vec<u32>)> {
+    (0..lens.get_dir_count())
+        .filter_map(|ix| lens.get_dir_entry(ix))
+        .filter_map(|entry| {
+            let path = rebase_path(&entry.path, old_root, new_root)?;
+            let labels: Vec<u32> = lens
+                .entry_labels(entry.id as u32)
+                .into_iter()
+                .map(|id| id as u32)
+                .collect();
+            if labels.is_empty() {
+                None
+            } else {
+                Some((path, labels))
+            }
+        })
+        .collect()
+}
+
 pub struct LocationDialog {
     lens: Arc<Mutex<Lens>>,
+    grades: Arc<Mutex<GradeStore>>,
     location: Arc<Mutex<Location>>,
     selected_location: Arc<Mutex<Option<usize>>>,
+    db: Arc<Mutex<Connection>>,
+    db_path: String,
+    scanner: Scanner,
+    options: Arc<Mutex<LocationOptionsStore>>,
 }
 
 impl LocationDialog {
-    pub fn new(lens: Arc<Mutex<Lens>>) -> Self {
+    pub fn new(
+        lens: Arc<Mutex<Lens>>,
+        grades: Arc<Mutex<GradeStore>>,
+        db: Arc<Mutex<Connection>>,
+        db_path: &str,
+        scanner: Scanner,
+    ) -> Self {
+        let options = LocationOptionsStore::load(&db.lock());
+
         LocationDialog {
             lens,
+            grades,
+            db,
+            db_path: db_path.to_string(),
+            scanner,
+            options: Arc::new(Mutex::new(options)),
             location: Arc::new(Mutex::new(Location {
                 name: None,
                 path: None,
@@ -65,41 +184,111 @@ impl LocationDialog {
     }
 
     pub fn show(&self) {
-        let mut dialog = Window::new(300, 100, 500, 515, "Location");
+        let mut dialog = Window::new(300, 100, 600, 540, "Location");
         dialog.make_modal(true);
 
         let lens_c = self.lens.clone();
 
-        let mut input_name = Input::new(60, 10, 80, 25, "Name");
-        let mut input_path = Input::new(190, 10, 140, 25, "Path");
-        let mut but_save = Button::new(350, 10, 60, 25, "Save");
-        let mut but_delete = Button::new(420, 10, 60, 25, "Remove");
+        let mut input_name = Input::new(55, 10, 75, 25, "Name");
+        let mut input_path = Input::new(170, 10, 200, 25, "Path");
+        let mut but_browse = Button::new(375, 10, 70, 25, "Browse...");
+        let mut but_save = Button::new(450, 10, 60, 25, "Save");
+        let mut but_delete = Button::new(520, 10, 70, 25, "Remove");
+        let mut check_watch = CheckButton::new(10, 45, 150, 25, "Watch for changes");
+        check_watch.deactivate();
+        let mut but_update = Button::new(450, 45, 60, 25, "Update");
+        but_update.set_tooltip("Save name and path to the selected location");
+        but_update.deactivate();
+
+        let mut frame_error = Frame::new(10, 72, 580, 20, "");
+        frame_error.set_label_color(Color::Red);
+        frame_error.set_label_size(12);
+        frame_error.set_align(Align::Left | Align::Inside | Align::Clip);
+
+        let mut rules_editor = ScanRulesEditor::new(10, 100);
 
+        // Checked once, a path of an offline location can take long to time out
+        let online: HashMap<i32, bool> = {
+            let lens = self.lens.lock();
+            lens.get_locations()
+                .iter()
+                .map(|l| (l.id, is_online(&l.path)))
+                .collect()
+        };
+        let totals = location_totals(&self.lens.lock());
+
+        let options_c = self.options.clone();
+        let scanner_c = self.scanner.clone();
         let mut location_table = location_table::LocationTable::new(
             5,
-            45,
-            480,
-            390,
-            vec!["Name".to_string(), "Path".to_string()],
+            175,
+            590,
+            285,
+            vec![
+                "Name".to_string(),
+                "Path".to_string(),
+                "Watch".to_string(),
+                "Entries".to_string(),
+                "Size".to_string(),
+                "Last scan".to_string(),
+                "Status".to_string(),
+            ],
             self.lens.lock().get_locations().len() as u32,
             Box::new(move |row, col| {
                 let l = lens_c.lock();
                 let loc_list = l.get_locations();
-                if loc_list.len() >= row as usize {
-                    let loc = &loc_list[row as usize];
+                if let Some(loc) = loc_list.get(row as usize) {
+                    // Locations added while the dialog is open had their path checked
+                    let online = online.get(&loc.id).copied().unwrap_or(true);
+                    let color = if online { Color::Gray0 } else { Color::Inactive };
+
+                    let total = totals.get(&loc.id);
+                    let last_scan = scanner_c
+                        .with_index(|index| index.location(loc.id).and_then(|i| i.last_scan));
 
-                    match col {
+                    let (text, align) = match col {
                         0 => (loc.name.to_string(), Align::Left),
                         1 => (loc.path.to_string(), Align::Left),
+                        2 => {
+                            let watch = options_c.lock().get(loc.id).watch;
+                            (if watch { "Yes" } else { "" }.to_string(), Align::Center)
+                        }
+                        3 => (
+                            total
+                                .map(|(count, _)| count.to_string())
+                                .unwrap_or_default(),
+                            Align::Right,
+                        ),
+                        4 => (
+                            total
+                                .map(|(_, size)| pretty_size(*size))
+                                .unwrap_or_default(),
+                            Align::Right,
+                        ),
+                        5 => (
+                            last_scan
+                                .map(pretty_time)
+                                .unwrap_or_else(|| "Never".to_string()),
+                            Align::Left,
+                        ),
+                        6 => (
+                            if online { "Online" } else { "Offline" }.to_string(),
+                            Align::Center,
+                        ),
                         _ => ("".to_string(), Align::Center),
-                    }
+                    };
+                    (text, align, color)
                 } else {
                     println!("Invalid location row: {}", row);
-                    ("".to_string(), Align::Center)
+                    ("".to_string(), Align::Center, Color::Gray0)
                 }
             }),
         );
 
+        for (col, width) in [70, 150, 45, 55, 65, 115, 60].iter().enumerate() {
+            location_table.set_col_width(col as i32, *width);
+        }
+
         // Button save callback
         let location_c = self.location.clone();
         let lens_c = self.lens.clone();
@@ -108,6 +297,17 @@ impl LocationDialog {
             let loc = location_c.lock();
             if let Some((name, path)) = loc.values() {
                 let mut lens = lens_c.lock();
+
+                let locations: Vec<(i32, String, String)> = lens
+                    .get_locations()
+                    .iter()
+                    .map(|l| (l.id, l.name.to_string(), l.path.to_string()))
+                    .collect();
+                if let Err(err) = validate_location_path(&path, &locations, None) {
+                    println!("Invalid location path: {}", err);
+                    return;
+                }
+
                 lens.add_location(&name, &path);
 
                 let len = lens.get_locations().len();
@@ -117,9 +317,78 @@ impl LocationDialog {
         });
         but_save.deactivate();
 
+        // Button update callback
+        let location_c = self.location.clone();
+        let select_c = self.selected_location.clone();
+        let lens_c = self.lens.clone();
+        let grades_c = self.grades.clone();
+        let db_c = self.db.clone();
+        let db_path_c = self.db_path.clone();
+        let scanner_c = self.scanner.clone();
+        let mut table_c = location_table.clone();
+        but_update.set_callback(move |_| {
+            let select = *select_c.lock();
+            let values = location_c.lock().values();
+            if let (Some(loc_ix), Some((name, path))) = (select, values) {
+                let locations: Vec<(i32, String, String)> = lens_c
+                    .lock()
+                    .get_locations()
+                    .iter()
+                    .map(|l| (l.id, l.name.to_string(), l.path.to_string()))
+                    .collect();
+                let (id, old_name, old_path) = match locations.get(loc_ix) {
+                    Some(loc) => loc.clone(),
+                    None => return,
+                };
+                if let Err(err) = validate_location_path(&path, &locations, Some(id)) {
+                    println!("Invalid location path: {}", err);
+                    return;
+                }
+                if name == old_name && path == old_path {
+                    return;
+                }
+
+                // The lens drops the labels of entries that are not at their path any more,
+                // they are put back once the entries are at the new path
+                let path_changed = old_path != path;
+                let labels = if path_changed {
+                    moved_labels(&lens_c.lock(), &old_path, &path)
+                } else {
+                    Vec::new()
+                };
+
+                let updated = location_store::update_location(
+                    &db_c.lock(),
+                    id,
+                    &old_name,
+                    &old_path,
+                    &name,
+                    &path,
+                );
+                if let Err(err) = updated {
+                    println!("Failed to update location {}: {}", id, err);
+                    return;
+                }
+                *lens_c.lock() = Lens::new(&db_path_c);
+                println!("Update location {}: {} -> {}", id, old_path, path);
+
+                if path_changed {
+                    if let Err(err) = grades_c.lock().move_path(&old_path, &path) {
+                        println!("Failed to move grades: {}", err);
+                    }
+                    scanner_c.move_location(id, &old_path, &path, labels);
+                }
+
+                table_c.redraw();
+            }
+        });
+
         // Button delete callback
         let lens_c = self.lens.clone();
         let select_c = self.selected_location.clone();
+        let options_c = self.options.clone();
+        let db_c = self.db.clone();
+        let scanner_c = self.scanner.clone();
         let mut table_c = location_table.clone();
         but_delete.set_callback(move |_| {
             let select = *select_c.lock();
@@ -129,9 +398,16 @@ impl LocationDialog {
                 let locations = lens.get_locations();
                 let loc = &locations[loc_ix as usize];
                 let loc_id = loc.id as u32;
+                let options_id = loc.id;
 
                 lens.remove_location(loc_id);
 
+                let mut options = options_c.lock();
+                if let Err(err) = options.remove(&db_c.lock(), options_id) {
+                    println!("Failed to save location options: {}", err);
+                }
+                scanner_c.remove_location(options_id);
+
                 let len = lens.get_locations().len();
                 table_c.set_rows(len as i32);
                 table_c.redraw();
@@ -143,37 +419,144 @@ impl LocationDialog {
         input_name.set_trigger(CallbackTrigger::Changed);
         let location_c = self.location.clone();
         let mut but_c = but_save.clone();
+        let mut but_update_c = but_update.clone();
+        let select_c = self.selected_location.clone();
+        let lens_c = self.lens.clone();
+        let mut error_c = frame_error.clone();
         input_name.set_callback(move |input_c: &mut Input| {
             let name = input_c.value();
             let mut loc = location_c.lock();
             loc.set_name(&name);
 
-            if loc.valid() {
-                but_c.activate();
-            } else {
-                but_c.deactivate();
-            }
+            validate_inputs(
+                &lens_c,
+                &loc,
+                *select_c.lock(),
+                &mut error_c,
+                &mut but_c,
+                &mut but_update_c,
+            );
         });
 
         // Path changed
         input_path.set_trigger(CallbackTrigger::Changed);
         let location_c = self.location.clone();
         let mut but_c = but_save.clone();
+        let mut but_update_c = but_update.clone();
+        let select_c = self.selected_location.clone();
+        let lens_c = self.lens.clone();
+        let mut error_c = frame_error.clone();
         input_path.set_callback(move |input_c: &mut Input| {
             let path = input_c.value();
             let mut loc = location_c.lock();
             loc.set_path(&path);
 
-            if loc.valid() {
-                but_c.activate();
-            } else {
-                but_c.deactivate();
+            validate_inputs(
+                &lens_c,
+                &loc,
+                *select_c.lock(),
+                &mut error_c,
+                &mut but_c,
+                &mut but_update_c,
+            );
+        });
+
+        // Browse for path
+        let location_c = self.location.clone();
+        let select_c = self.selected_location.clone();
+        let lens_c = self.lens.clone();
+        let mut input_path_c = input_path.clone();
+        let mut error_c = frame_error.clone();
+        let mut but_save_c = but_save.clone();
+        let mut but_update_c = but_update.clone();
+        but_browse.set_callback(move |_| {
+            let mut chooser = NativeFileChooser::new(NativeFileChooserType::BrowseDir);
+            chooser.set_title("Select location");
+
+            let current = input_path_c.value();
+            if !current.trim().is_empty() {
+                let _ = chooser.set_directory(&current);
+            }
+
+            chooser.show();
+            let path = chooser.filename();
+            if path.as_os_str().is_empty() {
+                return;
+            }
+
+            let path = path.to_string_lossy().to_string();
+            input_path_c.set_value(&path);
+
+            let mut loc = location_c.lock();
+            loc.set_path(&path);
+            validate_inputs(
+                &lens_c,
+                &loc,
+                *select_c.lock(),
+                &mut error_c,
+                &mut but_save_c,
+                &mut but_update_c,
+            );
+        });
+
+        // Watch toggled
+        let lens_c = self.lens.clone();
+        let select_c = self.selected_location.clone();
+        let options_c = self.options.clone();
+        let db_c = self.db.clone();
+        let mut table_c = location_table.clone();
+        check_watch.set_callback(move |check_c| {
+            if let Some(loc_ix) = *select_c.lock() {
+                let lens = lens_c.lock();
+                if let Some(loc) = lens.get_locations().get(loc_ix) {
+                    let mut options = options_c.lock();
+                    let mut loc_options = options.get(loc.id);
+                    loc_options.watch = check_c.is_checked();
+                    if let Err(err) = options.set(&db_c.lock(), loc.id, loc_options) {
+                        println!("Failed to save location options: {}", err);
+                    }
+                }
+                table_c.redraw();
+            }
+        });
+
+        // Save scan rules
+        let lens_c = self.lens.clone();
+        let select_c = self.selected_location.clone();
+        let options_c = self.options.clone();
+        let db_c = self.db.clone();
+        let scanner_c = self.scanner.clone();
+        let editor_c = rules_editor.clone();
+        rules_editor.but_save.set_callback(move |_| {
+            if let Some(loc_ix) = *select_c.lock() {
+                let lens = lens_c.lock();
+                if let Some(loc) = lens.get_locations().get(loc_ix) {
+                    let mut options = options_c.lock();
+                    let mut loc_options = options.get(loc.id);
+                    loc_options.rules = editor_c.rules();
+                    println!("Save scan rules for {}: {:?}", loc.name, loc_options.rules);
+                    if let Err(err) = options.set(&db_c.lock(), loc.id, loc_options) {
+                        println!("Failed to save location options: {}", err);
+                    }
+                    // What was stored was scanned with the old rules
+                    scanner_c.invalidate(loc.id);
+                }
             }
         });
 
         // Location selected
+        let lens_c = self.lens.clone();
         let select_c = self.selected_location.clone();
+        let options_c = self.options.clone();
         let mut but_c = but_delete.clone();
+        let mut check_c = check_watch.clone();
+        let mut editor_c = rules_editor.clone();
+        let mut but_update_c = but_update.clone();
+        let mut but_save_c = but_save.clone();
+        let mut input_name_c = input_name.clone();
+        let mut input_path_c = input_path.clone();
+        let location_c = self.location.clone();
+        let mut error_c = frame_error.clone();
         location_table.wid.set_trigger(CallbackTrigger::Changed);
         location_table
             .wid
@@ -183,10 +566,35 @@ impl LocationDialog {
                 if rt >= 0 {
                     *select_c.lock() = Some(rt as usize);
                     but_c.activate();
+
+                    if let Some(loc) = lens_c.lock().get_locations().get(rt as usize) {
+                        let loc_options = options_c.lock().get(loc.id);
+                        check_c.set_checked(loc_options.watch);
+                        check_c.activate();
+                        editor_c.set_rules(&loc_options.rules);
+                        editor_c.set_active(true);
+
+                        input_name_c.set_value(&loc.name);
+                        input_path_c.set_value(&loc.path);
+                        let mut location = location_c.lock();
+                        location.set_name(&loc.name);
+                        location.set_path(&loc.path);
+                    }
                 } else {
                     *select_c.lock() = None;
                     but_c.deactivate();
+                    check_c.deactivate();
+                    editor_c.set_active(false);
                 }
+
+                validate_inputs(
+                    &lens_c,
+                    &location_c.lock(),
+                    *select_c.lock(),
+                    &mut error_c,
+                    &mut but_save_c,
+                    &mut but_update_c,
+                );
             });
 
         dialog.end();
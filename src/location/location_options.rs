@@ -0,0 +1,146 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::scan::scan_rules::ScanRules;
+
+/// Settings for a location that the lens does not keep, stored as json in the library
+/// database by location id
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocationOptions {
+    /// Keep the location in sync with the file system
+    #[serde(default)]
+    pub watch: bool,
+    #[serde(default)]
+    pub rules: ScanRules,
+}
+
+impl LocationOptions {
+    pub fn to_data(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Unreadable data gives the default options rather than failing
+    pub fn from_data(data: &str) -> LocationOptions {
+        serde_json::from_str(data).unwrap_or_else(|err| {
+            println!("Failed to read location options '{}': {}", data, err);
+            LocationOptions::default()
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LocationOptionsStore {
+    pub locations: HashMap<i32, LocationOptions>,
+}
+
+impl LocationOptionsStore {
+    pub fn load(conn: &Connection) -> LocationOptionsStore {
+        let loaded = conn
+            .prepare("SELECT location_id, data FROM location_options")
+            .and_then(|mut stmt| {
+                let rows = stmt.query_map([], |row| {
+                    let data: String = row.get(1)?;
+                    Ok((row.get(0)?, LocationOptions::from_data(&data)))
+                })?;
+                rows.collect::<rusqlite::Result<HashMap<_, _>>>()
+            });
+
+        match loaded {
+            Ok(locations) => LocationOptionsStore { locations },
+            Err(err) => {
+                println!("Failed to load location options: {}", err);
+                LocationOptionsStore::default()
+            }
+        }
+    }
+
+    pub fn get(&self, location_id: i32) -> LocationOptions {
+        self.locations
+            .get(&location_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set(
+        &mut self,
+        conn: &Connection,
+        location_id: i32,
+        options: LocationOptions,
+    ) -> rusqlite::Result<()> {
+        conn.execute(
+            "INSERT OR REPLACE INTO location_options (location_id, data) VALUES (?1, ?2)",
+            params![location_id, options.to_data()],
+        )?;
+        self.locations.insert(location_id, options);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, conn: &Connection, location_id: i32) -> rusqlite::Result<()> {
+        conn.execute(
+            "DELETE FROM location_options WHERE location_id = ?1",
+            params![location_id],
+        )?;
+        self.locations.remove(&location_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_db;
+    use crate::test_utils::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_defaults_for_unknown_location() {
+        let store = LocationOptionsStore::default();
+        assert_eq!(store.get(3), LocationOptions::default());
+        assert!(!store.get(3).watch);
+        assert_eq!(
+            LocationOptions::from_data("not json"),
+            LocationOptions::default()
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = temp_dir("location_options");
+        let conn = app_db::open(&dir.join("Library.sqlite3").to_string_lossy()).unwrap();
+
+        let mut store = LocationOptionsStore::load(&conn);
+        store
+            .set(
+                &conn,
+                1,
+                LocationOptions {
+                    watch: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        store
+            .set(
+                &conn,
+                2,
+                LocationOptions {
+                    watch: false,
+                    rules: ScanRules {
+                        max_depth: Some(3),
+                        ..Default::default()
+                    },
+                },
+            )
+            .unwrap();
+        store.remove(&conn, 1).unwrap();
+
+        let loaded = LocationOptionsStore::load(&conn);
+        assert!(!loaded.get(1).watch);
+        assert_eq!(loaded.locations.len(), 1);
+        assert_eq!(loaded.get(2).rules.max_depth, Some(3));
+
+        drop(conn);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,114 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn normalize(path: &Path) -> PathBuf {
+    // Offline locations can not be canonicalized, compare them as written
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// A location is online when its path is a reachable directory, removable drives are often not
+pub fn is_online(path: &str) -> bool {
+    Path::new(path).is_dir()
+}
+
+/// Checks that a path can be used as a location.
+///
+/// `locations` are the existing locations as (id, name, path), the location with `ignore_id`
+/// is skipped so a location can be updated with its own path.
+pub fn validate_location_path(
+    path: &str,
+    locations: &[(i32, String, String)],
+    ignore_id: Option<i32>,
+) -> Result<(), String> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err("Path is empty".to_string());
+    }
+
+    let metadata = fs::metadata(path).map_err(|_| format!("Path does not exist: {}", path))?;
+    if !metadata.is_dir() {
+        return Err(format!("Path is not a directory: {}", path));
+    }
+
+    if fs::read_dir(path).is_err() {
+        return Err(format!("Path is not readable: {}", path));
+    }
+
+    let path = normalize(Path::new(path));
+    for (id, name, loc_path) in locations.iter() {
+        if Some(*id) == ignore_id {
+            continue;
+        }
+
+        let loc_path = normalize(Path::new(loc_path));
+        if path == loc_path {
+            return Err(format!("Path is already used by location {}", name));
+        } else if path.starts_with(&loc_path) {
+            return Err(format!("Path is inside location {}", name));
+        } else if loc_path.starts_with(&path) {
+            return Err(format!("Path contains location {}", name));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    fn temp_tree(name: &str) -> PathBuf {
+        let root = temp_dir(&format!("loc_path_{}", name));
+        fs::create_dir_all(root.join("a").join("b")).unwrap();
+        fs::create_dir_all(root.join("c")).unwrap();
+        fs::write(root.join("file"), "x").unwrap();
+        root
+    }
+
+    fn loc(id: i32, path: &Path) -> (i32, String, String) {
+        (id, format!("loc{}", id), path.to_string_lossy().to_string())
+    }
+
+    #[test]
+    fn test_path_must_be_readable_dir() {
+        let root = temp_tree("dir");
+
+        assert!(validate_location_path("  ", &[], None).is_err());
+        assert!(
+            validate_location_path(&root.join("missing").to_string_lossy(), &[], None).is_err()
+        );
+        assert!(validate_location_path(&root.join("file").to_string_lossy(), &[], None).is_err());
+        assert_eq!(
+            validate_location_path(&root.to_string_lossy(), &[], None),
+            Ok(())
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_path_overlapping_locations() {
+        let root = temp_tree("overlap");
+        let locations = vec![loc(1, &root.join("a"))];
+
+        let nested = root.join("a").join("b").to_string_lossy().to_string();
+        let parent = root.to_string_lossy().to_string();
+        let same = root.join("a").to_string_lossy().to_string();
+        let sibling = root.join("c").to_string_lossy().to_string();
+
+        assert_eq!(
+            validate_location_path(&nested, &locations, None),
+            Err("Path is inside location loc1".to_string())
+        );
+        assert_eq!(
+            validate_location_path(&parent, &locations, None),
+            Err("Path contains location loc1".to_string())
+        );
+        assert!(validate_location_path(&same, &locations, None).is_err());
+        assert_eq!(validate_location_path(&same, &locations, Some(1)), Ok(()));
+        assert_eq!(validate_location_path(&sibling, &locations, None), Ok(()));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Columns a table needs to hold the locations of the lens
+const LOCATION_COLUMNS: [&str; 3] = ["id", "name", "path"];
+
+/// Changes the name and path of a location in place, so it keeps its id and everything
+/// stored for it. The lens keeps its locations in the library database but can not
+/// change one, so its table is found by its columns and the row by the location as the
+/// lens has it. Nothing is written unless exactly one table has that row. The lens has
+/// to be loaded again to see the change.
+pub fn update_location(
+    conn: &Connection,
+    id: i32,
+    old_name: &str,
+    old_path: &str,
+    name: &str,
+    path: &str,
+) -> Result<(), String> {
+    let tables = location_tables(conn).map_err(|err| err.to_string())?;
+
+    let mut found = Vec::new();
+    for table in tables.into_iter() {
+        let row: Option<i32> = conn
+            .query_row(
+                &format!(
+                    "SELECT id FROM \"{}\" WHERE id = ?1 AND name = ?2 AND path = ?3",
+                    table
+                ),
+                params![id, old_name, old_path],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|err| err.to_string())?;
+        if row.is_some() {
+            found.push(table);
+        }
+    }
+
+    let table = match found.as_slice() {
+        [table] => table,
+        [] => return Err(format!("Location {} was not found in the library", id)),
+        _ => return Err(format!("Location {} is in more than one table", id)),
+    };
+
+    conn.execute(
+        &format!(
+            "UPDATE \"{}\" SET name = ?1, path = ?2 WHERE id = ?3",
+            table
+        ),
+        params![name, path, id],
+    )
+    .map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+/// Tables with every column of a location
+fn location_tables(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let tables: Vec<String> = {
+        let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table'")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+
+    let mut found = Vec::new();
+    for table in tables.into_iter() {
+        let columns: Vec<String> = {
+            let mut stmt = conn.prepare(&format!("PRAGMA table_info(\"{}\")", table))?;
+            let rows = stmt.query_map([], |row| row.get(1))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        let has_columns = LOCATION_COLUMNS
+            .iter()
+            .all(|c| columns.iter().any(|column| column.eq_ignore_ascii_case(c)));
+        if has_columns {
+            found.push(table);
+        }
+    }
+
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(conn: &Connection, table: &str, id: i32) -> (String, String) {
+        conn.query_row(
+            &format!("SELECT name, path FROM {} WHERE id = ?1", table),
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_update_location() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE Location (id INTEGER PRIMARY KEY, name TEXT, path TEXT, size INTEGER);
+             CREATE TABLE Entry (id INTEGER PRIMARY KEY, name TEXT, path TEXT);
+             INSERT INTO Location VALUES (1, 'Movies', '/mnt/movies', 0);
+             INSERT INTO Location VALUES (2, 'Music', '/mnt/music', 0);
+             INSERT INTO Entry VALUES (1, 'Other', '/mnt/other');",
+        )
+        .unwrap();
+
+        update_location(&conn, 1, "Movies", "/mnt/movies", "Films", "/media/films").unwrap();
+
+        assert_eq!(
+            location(&conn, "Location", 1),
+            ("Films".to_string(), "/media/films".to_string())
+        );
+        assert_eq!(
+            location(&conn, "Location", 2),
+            ("Music".to_string(), "/mnt/music".to_string())
+        );
+        assert_eq!(
+            location(&conn, "Entry", 1),
+            ("Other".to_string(), "/mnt/other".to_string())
+        );
+
+        // What the lens has must match the row
+        assert!(update_location(&conn, 2, "Music", "/mnt/other", "Music", "/x").is_err());
+        assert!(update_location(&conn, 3, "Music", "/mnt/music", "Music", "/x").is_err());
+        assert_eq!(
+            location(&conn, "Location", 2),
+            ("Music".to_string(), "/mnt/music".to_string())
+        );
+    }
+}
@@ -2,7 +2,7 @@ use fltk::table::*;
 use fltk::*;
 use fltk::{prelude::*, enums::*};
 
-use crate::table_utils::{draw_data, draw_header};
+use crate::table_utils::{draw_data_color, draw_header};
 
 pub struct LocationTable {
     pub wid: TableRow,
@@ -16,7 +16,7 @@ impl LocationTable {
         h: i32,
         headers: Vec<String>,
         row_count: u32,
-        cell_data: Box<dyn Fn(i32, i32) -> (String, Align)>,
+        cell_data: Box<dyn Fn(i32, i32) -> (String, Align, Color)>,
     ) -> LocationTable {
         let mut table = LocationTable {
             wid: TableRow::new(x, y, w, h, ""),
@@ -39,8 +39,8 @@ impl LocationTable {
                 table::TableContext::ColHeader => draw_header(&headers[col as usize], x, y, w, h),
                 // table::TableContext::RowHeader => draw_header(&format!("{}", row + 1), x, y, w, h),
                 table::TableContext::Cell => {
-                    let (data, align) = cell_data(row, col);
-                    draw_data(&data, x, y, w, h, table_c.row_selected(row), align)
+                    let (data, align, color) = cell_data(row, col);
+                    draw_data_color(&data, x, y, w, h, color, table_c.row_selected(row), align)
                 }
                 _ => (),
             },
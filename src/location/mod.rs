@@ -1,2 +1,6 @@
 pub mod location_dialog;
-pub mod location_table;
\ No newline at end of file
+pub mod location_options;
+pub mod location_path;
+pub mod location_store;
+pub mod location_table;
+pub mod scan_rules_editor;
@@ -0,0 +1,82 @@
+use fltk::prelude::*;
+use fltk::{button::*, input::*};
+
+use crate::scan::scan_rules::{format_patterns, parse_patterns, ScanRules};
+
+/// Inputs for editing the scan rules of a location
+#[derive(Clone)]
+pub struct ScanRulesEditor {
+    input_exclude: Input,
+    input_include: Input,
+    input_depth: IntInput,
+    check_hidden: CheckButton,
+    check_symlinks: CheckButton,
+    pub but_save: Button,
+}
+
+impl ScanRulesEditor {
+    pub fn new(x: i32, y: i32) -> Self {
+        let mut input_exclude = Input::new(x + 60, y, 170, 25, "Exclude");
+        input_exclude.set_tooltip("Comma separated patterns, e.g. *.part, .git/");
+        let mut input_include = Input::new(x + 300, y, 170, 25, "Include");
+        input_include.set_tooltip("Only files matching these patterns are added");
+
+        let mut input_depth = IntInput::new(x + 70, y + 35, 40, 25, "Max depth");
+        input_depth.set_tooltip("Empty for no limit");
+        let check_hidden = CheckButton::new(x + 120, y + 35, 110, 25, "Skip hidden");
+        let check_symlinks = CheckButton::new(x + 235, y + 35, 130, 25, "Follow symlinks");
+        let but_save = Button::new(x + 390, y + 35, 80, 25, "Save rules");
+
+        let mut editor = ScanRulesEditor {
+            input_exclude,
+            input_include,
+            input_depth,
+            check_hidden,
+            check_symlinks,
+            but_save,
+        };
+
+        editor.set_rules(&ScanRules::default());
+        editor.set_active(false);
+        editor
+    }
+
+    pub fn set_rules(&mut self, rules: &ScanRules) {
+        self.input_exclude
+            .set_value(&format_patterns(&rules.exclude));
+        self.input_include
+            .set_value(&format_patterns(&rules.include));
+        self.input_depth
+            .set_value(&rules.max_depth.map(|d| d.to_string()).unwrap_or_default());
+        self.check_hidden.set_checked(rules.skip_hidden);
+        self.check_symlinks.set_checked(rules.follow_symlinks);
+    }
+
+    pub fn rules(&self) -> ScanRules {
+        ScanRules {
+            exclude: parse_patterns(&self.input_exclude.value()),
+            include: parse_patterns(&self.input_include.value()),
+            max_depth: self.input_depth.value().trim().parse().ok(),
+            follow_symlinks: self.check_symlinks.is_checked(),
+            skip_hidden: self.check_hidden.is_checked(),
+        }
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        if active {
+            self.input_exclude.activate();
+            self.input_include.activate();
+            self.input_depth.activate();
+            self.check_hidden.activate();
+            self.check_symlinks.activate();
+            self.but_save.activate();
+        } else {
+            self.input_exclude.deactivate();
+            self.input_include.deactivate();
+            self.input_depth.deactivate();
+            self.check_hidden.deactivate();
+            self.check_symlinks.deactivate();
+            self.but_save.deactivate();
+        }
+    }
+}
@@ -1,44 +1,89 @@
 use fltk::table::TableRow;
-use log::LevelFilter;
 use parking_lot::Mutex;
-use simplelog::{CombinedLogger, Config, SimpleLogger};
+use rusqlite::Connection;
+use simplelog::{CombinedLogger, Config, SharedLogger, SimpleLogger, WriteLogger};
 
 use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
-use std::thread;
 
-use fltk::{app, app::*, button::*, frame, group, input::*, table::TableContext, window};
+use fltk::{
+    app, app::*, button::*, dialog, frame, group, input::*, menu, table::TableContext, window,
+};
 use fltk::{enums::*, image, prelude::*};
 
-use serious_organizer_lib::dir_search;
-use serious_organizer_lib::lens::Lens;
+use serious_organizer_lib::lens::{Lens, SortOrder};
 
 #[macro_use]
 extern crate log;
 
+mod app_db;
 mod choice_dialog;
+mod config;
+mod delete_dialog;
+mod duplicates;
 mod entry_context_menu;
+mod entry_shortcuts;
 mod entry_table;
 mod error_dialog;
 mod file_context_menu;
+mod file_ops;
 mod file_table;
+mod grade_store;
+mod history;
 mod label;
 mod label_filter;
+mod library;
 mod loading_dialog;
 mod location;
 mod model;
+mod rename;
 mod rename_dialog;
+mod scan;
+mod search;
+mod status_bar;
 mod table_utils;
-
+#[cfg(test)]
+mod test_utils;
+mod transfer;
+mod trash;
+mod view;
+mod window_settings;
+
+use config::app_config::{self, AppConfig};
+use config::config_dialog::ConfigDialog;
+use duplicates::duplicate_dialog::DuplicateDialog;
+use duplicates::similar_dialog::SimilarDialog;
 use entry_table::EntryTable;
+use error_dialog::ErrorDialog;
 
 use entry_context_menu::show_entry_context_menu;
+use entry_shortcuts::{event_entry_action, run_entry_action, EntryAction};
 use file_context_menu::show_file_context_menu;
 use file_table::FileTable;
+use grade_store::GradeStore;
+use history::history_panel::HistoryPanel;
+use history::journal::History;
+use history::undo;
 use model::message::Message;
-
+use scan::scanner::{ScanMode, Scanner};
+use scan::watcher::LocationWatcher;
+use search::entry_filter::EntryFilter;
+use search::search_query::{self, SearchQuery};
+use search::search_worker::{SearchRequest, SearchWorker};
+use status_bar::StatusBar;
+use table_utils::{column_widths, set_column_widths};
+use transfer::entry_transfer::{self, TransferOrigin};
+use transfer::transfer_dialog::TransferDialog;
+use view::grade_filter::GradeFilter;
+use view::saved_view::{save_view, SavedView, ViewSort, ViewState};
+use view::saved_view_list::SavedViewList;
+use window_settings::WindowSettings;
+
+use label::label_expr::{self, LabelExpr};
 use label::label_list;
 use location::location_dialog;
+use location::location_options::LocationOptionsStore;
 use location::location_table;
 
 use crate::label::add_label_dialog;
@@ -54,6 +99,18 @@ pub fn get_selected_index(table: &mut TableRow) -> Vec<u32> {
     selected
 }
 
+/// Locations that have the watch option turned on
+fn watched_locations(lens: &Arc<Mutex<Lens>>, db: &Arc<Mutex<Connection>>) -> Vec<(i32, PathBuf)> {
+    let options = LocationOptionsStore::load(&db.lock());
+
+    lens.lock()
+        .get_locations()
+        .iter()
+        .filter(|l| options.get(l.id).watch)
+        .map(|l| (l.id, PathBuf::from(&l.path)))
+        .collect()
+}
+
 #[cfg(debug_assertions)]
 fn get_dir_path() -> String {
     ::std::env::current_exe()
@@ -98,30 +155,109 @@ fn get_dir_path() -> String {
     }
 }
 
+fn init_logger(config: &AppConfig) {
+    let level = config.log_level();
+    let mut loggers: Vec<Box<dyn SharedLogger>> = vec![SimpleLogger::new(level, Config::default())];
+
+    if let Some(log_file) = &config.log_file {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_file);
+        match file {
+            Ok(file) => loggers.push(WriteLogger::new(level, Config::default(), file)),
+            Err(err) => println!("Failed to open log file {}: {}", log_file, err),
+        }
+    }
+
+    CombinedLogger::init(loggers).unwrap();
+}
+
+/// Applies the config settings that take effect without a restart
+fn apply_config(config: &AppConfig, dir_tbl: &mut EntryTable, file_tbl: &mut FileTable) {
+    dir_tbl.set_style(config.table_style());
+    file_tbl.set_style(config.table_style());
+    file_tbl.set_video_extensions(config.video_extensions.clone());
+}
+
+fn sort_order(descending: bool) -> SortOrder {
+    if descending {
+        SortOrder::Desc
+    } else {
+        SortOrder::Asc
+    }
+}
+
+/// Places the tables and the sidebar inside the tiles, once the flex layout has sized them
+fn layout_panes(
+    settings: &WindowSettings,
+    table_row: &mut group::Tile,
+    table_col: &mut group::Tile,
+    dir_tbl: &mut TableRow,
+    file_tbl: &mut TableRow,
+    filter_col: &mut group::Flex,
+) {
+    let (x, y, w, h) = (table_row.x(), table_row.y(), table_row.w(), table_row.h());
+    let (tables_w, entry_h) = settings.pane_sizes(w, h);
+
+    table_col.resize(x, y, tables_w, h);
+    dir_tbl.resize(x, y, tables_w, entry_h);
+    file_tbl.resize(x, y + entry_h, tables_w, h - entry_h);
+    filter_col.resize(x + tables_w, y, w - tables_w, h);
+
+    // Tiles drag and resize from the sizes they had when last initialized
+    table_col.init_sizes();
+    table_row.init_sizes();
+    table_row.redraw();
+}
+
 fn main() {
+    // Before any thread is started
+    table_utils::init_local_offset();
+
+    // The config, window settings and icon live in the default data dir
+    let default_db_path = get_dir_path();
+    let app_config_path = app_config::config_path_from_args(std::env::args())
+        .unwrap_or_else(|| Path::new(&default_db_path).with_file_name("config.toml"));
+    let mut config = AppConfig::load(&app_config_path);
+
+    init_logger(&config);
     info!("Starting");
-    CombinedLogger::init(vec![
-        SimpleLogger::new(LevelFilter::Info, Config::default()),
-        // WriteLogger::new(LevelFilter::Info, Config::default(), std::fs::File::create("serious_server.log").expect("Failed to init logger")),
-    ])
-    .unwrap();
 
-    let db_path = get_dir_path();
+    let mut db_path = config
+        .db_path
+        .clone()
+        .unwrap_or_else(|| default_db_path.clone());
+    if let Err(err) = library::check_library(Path::new(&db_path)) {
+        println!("{}, opening the default library", err);
+        db_path = default_db_path.clone();
+    }
     println!("dbpath: {}", db_path);
+    library::add_recent(&mut config.recent_libraries, &db_path);
     let lens = Arc::new(Mutex::new(Lens::new(&db_path)));
-
-    let w_size: i32 = 715;
-    let h_size: i32 = 800;
+    let app_db = app_db::open(&db_path)
+        .unwrap_or_else(|err| panic!("Failed to open library {}: {}", db_path, err));
+    let app_db = Arc::new(Mutex::new(app_db));
+    let grades = Arc::new(Mutex::new(GradeStore::load(app_db.clone())));
 
     let mut app = App::default();
     app.set_scheme(app::AppScheme::Base);
 
+    let settings_path = window_settings::settings_path(&default_db_path);
+    let mut settings = WindowSettings::load(&settings_path);
+    let (screen_w, screen_h) = app::screen_size();
+    settings.fit_screen(screen_w as i32, screen_h as i32);
+
+    let w_size: i32 = settings.width;
+    let h_size: i32 = settings.height;
+
     let (sender, reciever) = app::channel::<Message>();
 
-    let mut wind = window::Window::new(100, 100, w_size, h_size, "Serious Organizer");
+    let mut wind = window::Window::new(settings.x, settings.y, w_size, h_size, None);
+    wind.set_label(&library::window_title(&db_path));
     wind.make_resizable(true);
 
-    let config_path = Path::new(&db_path);
+    let config_path = Path::new(&default_db_path);
     let mut config_path: PathBuf = config_path.parent().unwrap().to_path_buf();
     config_path.push("SerousIcon.png");
     if let Ok(image) = image::PngImage::load(config_path) {
@@ -133,45 +269,96 @@ fn main() {
     let mut col = group::Flex::default_fill().column();
     col.set_margin(10);
 
+    let mut menu_bar = menu::MenuBar::default().with_size(w_size - 10, 25);
+    library::build_file_menu(&mut menu_bar, &sender, &config.recent_libraries);
+    col.set_size(&menu_bar, 25);
+
     let mut top_pack = group::Pack::default().with_size(w_size - 10, 25);
 
     let _spacer = frame::Frame::default().with_size(45, 25);
 
-    let mut input = Input::default().with_size(200, 25).with_label("Search");
+    let mut input = Input::default().with_size(185, 25).with_label("Search");
+    let mut check_fuzzy = CheckButton::default().with_size(55, 25).with_label("Fuzzy");
     let mut but_reload = Button::default().with_size(60, 25).with_label("Reload");
+    let check_full_scan = CheckButton::default().with_size(50, 25).with_label("Full");
     let mut but = Button::default().with_size(80, 25).with_label("Locations");
     let mut label_filter = Button::default()
         .with_size(100, 25)
         .with_label("Label Filters");
+    let mut but_history = Button::default().with_size(60, 25).with_label("History");
 
     top_pack.end();
     top_pack.set_spacing(10);
     top_pack.set_type(group::PackType::Horizontal);
     col.set_size(&top_pack, 25);
 
+    // Search parse errors, shown right below the search input
+    let mut search_error = frame::Frame::default().with_size(w_size - 10, 16);
+    search_error.set_label_size(11);
+    search_error.set_label_color(Color::Red);
+    search_error.set_align(Align::Left | Align::Inside);
+    col.set_size(&search_error, 16);
+
     // Setup dir table
-    let label_width = 195;
+    let label_width = settings.sidebar_width;
 
-    let mut table_row = group::Flex::default_fill().row();
+    // Tiles so the sidebar edge and the split between the tables can be dragged
+    let mut table_row = group::Tile::default_fill();
 
-    let table_col = group::Flex::default_fill().column();
+    let mut table_col = group::Tile::default_fill();
 
     let lens_c = lens.clone();
 
-    let mut dir_tbl = EntryTable::new(w_size - label_width - 10, 390, lens_c);
+    let mut dir_tbl = EntryTable::new(w_size - label_width - 10, 390, lens_c, grades.clone());
 
     let mut file_tbl = FileTable::new(w_size - label_width - 10, 260, lens.clone());
 
-    table_col.resizable(&dir_tbl.wid);
-    table_col.resizable(&file_tbl.wid);
+    apply_config(&config, &mut dir_tbl, &mut file_tbl);
+    set_column_widths(&mut dir_tbl.wid, &settings.entry_columns);
+    set_column_widths(&mut file_tbl.wid, &settings.file_columns);
+
+    if let Some(sort) = settings.entry_sort {
+        dir_tbl.set_sort(sort.column, sort_order(sort.descending));
+    }
+    if let Some(sort) = settings.file_sort {
+        file_tbl.set_sort(sort.column, sort_order(sort.descending));
+    }
 
     table_col.end();
 
     // Filter Column
     let mut filter_col = group::Flex::default_fill().column();
 
+    let mut view_list = SavedViewList::new(label_width, 120, app_db.clone(), sender.clone());
+    let mut but_save_view = Button::default()
+        .with_size(label_width, 25)
+        .with_label("Save View");
+
+    let mut label_query = Input::default().with_size(label_width, 25);
+    label_query.set_tooltip("Label filter, e.g. (Drama OR Comedy) AND NOT Watched");
+
+    let mut label_error = frame::Frame::default().with_size(label_width, 30);
+    label_error.set_label_size(11);
+    label_error.set_label_color(Color::Red);
+    label_error.set_align(Align::Left | Align::Inside | Align::Wrap);
+
+    let mut grade_choice = menu::Choice::default().with_size(label_width, 25);
+    grade_choice.set_tooltip("Grade filter");
+    for filter in GradeFilter::choices() {
+        grade_choice.add_choice(&filter.label());
+    }
+    grade_choice.set_value(0);
+
+    let label_expr: Arc<Mutex<Option<LabelExpr>>> = Arc::new(Mutex::new(None));
+
     let sender_c = sender.clone();
-    let mut label_list = label_list::LabelList::new(label_width, h_size, lens.clone(), sender_c);
+    let mut label_list = label_list::LabelList::new(
+        label_width,
+        h_size,
+        lens.clone(),
+        label_expr.clone(),
+        sender_c,
+    );
 
     let mut filter_button_pack = group::Pack::default().with_size(w_size - 10, 25);
 
@@ -183,112 +370,70 @@ fn main() {
     filter_button_pack.set_spacing(10);
     filter_button_pack.set_type(group::PackType::Horizontal);
 
+    let mut duplicate_button_pack = group::Pack::default().with_size(label_width, 25);
+
+    let mut but_duplicates = Button::default().with_size(90, 25).with_label("Duplicates");
+    let mut but_similar = Button::default()
+        .with_size(95, 25)
+        .with_label("Similar Names");
+
+    duplicate_button_pack.end();
+    duplicate_button_pack.set_spacing(10);
+    duplicate_button_pack.set_type(group::PackType::Horizontal);
+
+    filter_col.set_size(&view_list.wid, 120);
+    filter_col.set_size(&but_save_view, 25);
+    filter_col.set_size(&label_query, 25);
+    filter_col.set_size(&label_error, 30);
+    filter_col.set_size(&grade_choice, 25);
     filter_col.set_size(&filter_button_pack, 25);
+    filter_col.set_size(&duplicate_button_pack, 25);
     filter_col.resizable(&label_list.wid);
 
     filter_col.end();
 
-    table_row.set_size(&filter_col, label_width);
-
+    // The sidebar keeps its width when the window is resized
     table_row.resizable(&table_col);
     table_row.end();
 
+    let mut status_bar = StatusBar::new(w_size - 10, 20);
+    col.set_size(&status_bar.frame, 20);
+
     col.end();
 
     wind.end();
     wind.show();
 
+    col.recalc();
+    layout_panes(
+        &settings,
+        &mut table_row,
+        &mut table_col,
+        &mut dir_tbl.wid,
+        &mut file_tbl.wid,
+        &mut filter_col,
+    );
+
     // *** End of widget contruction ***
 
     // * Reload button *
-    let lens_c = lens.clone();
-    let sender_c = sender.clone();
+    let scan_cancel = Arc::new(AtomicBool::new(false));
 
-    but_reload.set_callback(move |_| {
-        let lens_c = lens_c.clone();
-        let sender_c = sender_c.clone();
-
-        sender_c.send(Message::ShowLoading);
-
-        thread::spawn(move || {
-            println!("Start update data");
-            let mut lens = lens_c.lock();
-
-            let paths = lens
-                .get_locations()
-                .iter()
-                .map(|e| (e.id, e.path.clone()))
-                .collect();
-
-            let mut dir_s = dir_search::get_all_data(&paths);
-            // let mut dir_new = dir_search::get_all_data2(&paths);
-
-            // for i in 0..dir_new.len() {
-            //     let new = &dir_new[i].1;
-            //     let old = &dir_s[i].1;
-
-            //     if new.name != old.name {
-            //         println!("Got dir mismatch! name");
-            //         println!("new {:?}", new.name);
-            //         println!("old {:?}", old.name);
-            //         println!("------");
-            //     }
-
-            //     if new.size != old.size {
-            //         println!("Got dir mismatch! size ");
-            //     }
-
-            //     if new.path != old.path {
-            //         println!("Got dir mismatch! path");
-            //     }
-
-            //     if new.location_id != old.location_id {
-            //         println!("Got dir mismatch! location_id ");
-            //     }
-
-            //     if new.files.len() != old.files.len() {
-            //         println!("Got dir mismatch! files.len PAth: {:?}", new.path);
-            //         println!("new {:?}", new.files.len());
-            //         println!("old {:?}", old.files.len());
-
-            //         println!("Extra in new: ");
-            //         for f in new.files.iter() {
-            //             if !old.files.contains(&f) {
-            //                 println!("{:?}", f.name);
-            //             }
-            //         }
-
-            //         println!("------");
-            //     } else {
-            //         for f in new.files.iter() {
-            //             if !old.files.contains(&f) {
-            //                 println!("File missmatch: {:?}", f.name);
-            //             }
-            //         }
-
-            //         for f in old.files.iter() {
-            //             if !new.files.contains(&f) {
-            //                 println!("File missmatch: {:?}", f.name);
-            //             }
-            //         }
-            //     }
-            // }
-
-            lens.update_data(&mut dir_s);
-
-            sender_c.send(Message::HideLoading);
-            println!("Done update data");
-        });
-    });
+    // The index belongs to the library, the scanner is replaced on a switch
+    let mut scanner = Scanner::new(
+        lens.clone(),
+        app_db.clone(),
+        sender.clone(),
+        scan_cancel.clone(),
+    );
+
+    but_reload.emit(sender.clone(), Message::Reload);
 
     // * Locations *
-    let lens_c = lens.clone();
+    but.emit(sender.clone(), Message::ShowLocations);
 
-    but.set_callback(move |_| {
-        // println!("Hello World!");
-        let dialog = location_dialog::LocationDialog::new(lens_c.clone());
-        dialog.show();
-    });
+    let mut location_watcher = LocationWatcher::new(scanner.clone());
+    location_watcher.set_locations(watched_locations(&lens, &app_db));
 
     // * Label filter *
     let lens_c = lens.clone();
@@ -297,6 +442,21 @@ fn main() {
         let dialog = label_filter::label_filter_dialog::LabelFilterDialog::new(lens_c.clone());
         dialog.show();
     });
+
+    // * History *
+    let history = Arc::new(Mutex::new(History::new()));
+    let mut history_panel = HistoryPanel::new(sender.clone());
+
+    let sender_c = sender.clone();
+    but_history.set_callback(move |_| sender_c.send(Message::ShowHistory));
+
+    // * Duplicates *
+    let sender_c = sender.clone();
+    but_duplicates.set_callback(move |_| sender_c.send(Message::ShowDuplicates));
+
+    let sender_c = sender.clone();
+    but_similar.set_callback(move |_| sender_c.send(Message::ShowSimilarNames));
+
     // * Setup file table *
 
     let sender_c = sender.clone();
@@ -387,58 +547,65 @@ fn main() {
             sender_c.send(Message::EntryShowContextMenu(selection));
             return true;
         }
+
+        // Keyboard, navigation keys are left to the table and picked up on release
+        if evt == Event::KeyDown {
+            if let Some(action) = event_entry_action() {
+                if action != EntryAction::Navigate {
+                    let selection = get_selected_index(dir_wid);
+                    sender_c.send(Message::EntryShortcut(action, selection));
+                    return true;
+                }
+            }
+        }
+
+        if evt == Event::KeyUp && event_entry_action() == Some(EntryAction::Navigate) {
+            let selection = get_selected_index(dir_wid);
+            sender_c.send(Message::EntryShortcut(EntryAction::Navigate, selection));
+        }
+
         false
     });
 
     // Setup search input
     input.set_trigger(CallbackTrigger::Changed);
-    let lens_c = lens.clone();
+    input.set_tooltip("Search names, e.g. \"big buck\" size:>2GB grade:>=B loc:Movies -ext:nfo");
     let sender_c = sender.clone();
     input.set_callback(move |input_c: &mut Input| {
-        // let dir_count;
-        {
-            let mut lens = lens_c.lock();
-            lens.update_search_text(&input_c.value());
-            // dir_count = lens.get_dir_count();
-        }
-        sender_c.send(Message::EntryTableInvalidated);
-        sender_c.send(Message::FileTableInvalidated);
-
-        // println!("Banan editing {} found: {}", input_c.value(), dir_count);
+        sender_c.send(Message::SearchChanged(input_c.value()));
     });
 
-    let lens_c = lens.clone();
+    check_fuzzy.set_tooltip("Match search words as scattered letters, best matches first");
     let sender_c = sender.clone();
+    check_fuzzy.set_callback(move |check_c| {
+        sender_c.send(Message::FuzzySearchChanged(check_c.is_checked()));
+    });
 
-    but_filter_none.set_callback(move |_| {
-        let mut lens = lens_c.lock();
-        let labels = { lens.get_labels().clone() };
-        for lbl in labels.iter() {
-            lens.add_exclude_label(lbl.id as u32);
-        }
+    // Setup label filter input
+    label_query.set_trigger(CallbackTrigger::Changed);
+    let sender_c = sender.clone();
+    label_query.set_callback(move |input_c: &mut Input| {
+        sender_c.send(Message::LabelQueryChanged(input_c.value()));
+    });
 
-        lens.update_ix_list();
+    // Setup saved views and grade filter
+    but_save_view.emit(sender.clone(), Message::SaveView(None));
 
-        sender_c.send(Message::EntryTableInvalidated);
-        sender_c.send(Message::FileTableInvalidated);
-        sender_c.send(Message::LabelTableInvalidated);
+    let sender_c = sender.clone();
+    grade_choice.set_callback(move |choice_c| {
+        if let Some(filter) = GradeFilter::choices().get(choice_c.value() as usize) {
+            sender_c.send(Message::GradeFilterChanged(*filter));
+        }
     });
 
-    let lens_c = lens.clone();
     let sender_c = sender.clone();
+    but_filter_none.set_callback(move |_| {
+        sender_c.send(Message::LabelQueryChanged("unlabeled".to_string()));
+    });
 
+    let sender_c = sender.clone();
     but_filter_reset.set_callback(move |_| {
-        let mut lens = lens_c.lock();
-        let labels = { lens.get_labels().clone() };
-        for lbl in labels.iter() {
-            lens.remove_label_filter(lbl.id as u32);
-        }
-
-        lens.update_ix_list();
-
-        sender_c.send(Message::EntryTableInvalidated);
-        sender_c.send(Message::FileTableInvalidated);
-        sender_c.send(Message::LabelTableInvalidated);
+        sender_c.send(Message::LabelQueryChanged(String::new()));
     });
 
     let lens_c = lens.clone();
@@ -449,6 +616,7 @@ fn main() {
         dialog.show();
     });
 
+    let sender_c = sender.clone();
     wind.handle(move |h_wnd, evt: Event| {
         if evt == Event::Activate {
             println!("Wind activate!");
@@ -468,22 +636,211 @@ fn main() {
             return true; // Skip this?
         }
 
+        // Ctrl+Z undo, Ctrl+Shift+Z redo
+        if evt.contains(Event::Shortcut)
+            && app::is_event_ctrl()
+            && app::event_key() == Key::from_char('z')
+        {
+            if app::is_event_shift() {
+                sender_c.send(Message::Redo);
+            } else {
+                sender_c.send(Message::Undo);
+            }
+            return true;
+        }
+
         false
     });
 
-    let mut loading_dialog = loading_dialog::LoadingDialog::new();
+    let mut loading_dialog = loading_dialog::LoadingDialog::new(scan_cancel.clone());
+    let mut scan_running = false;
+
+    let transfer_cancel = Arc::new(AtomicBool::new(false));
+    let mut transfer_dialog = TransferDialog::new(transfer_cancel.clone());
+    let mut transfer_running = false;
+
+    let mut entry_filter = EntryFilter::default();
+    let search_worker = SearchWorker::new(lens.clone(), grades.clone(), sender.clone());
+    sender.send(Message::EntryFilterChanged);
 
     while app.wait() {
         if let Some(msg) = reciever.recv() {
             match msg {
                 // Label Table
                 Message::LabelTableInvalidated => label_list.update(),
+                Message::LabelQueryChanged(query) => {
+                    if label_query.value() != query {
+                        label_query.set_value(&query);
+                    }
+
+                    let labels: Vec<(u32, String)> = lens
+                        .lock()
+                        .get_labels()
+                        .iter()
+                        .map(|l| (l.id as u32, l.name.clone()))
+                        .collect();
+
+                    // Keep the last valid filter while the expression is being typed
+                    match label_expr::parse(&query, &labels) {
+                        Ok(expr) => {
+                            label_error.set_label("");
+                            *label_expr.lock() = expr;
+
+                            sender.send(Message::EntryFilterChanged);
+                            sender.send(Message::LabelTableInvalidated);
+                        }
+                        Err(err) => label_error.set_label(&err.to_string()),
+                    }
+                }
+
+                // Search
+                Message::SearchChanged(text) => {
+                    if input.value() != text {
+                        input.set_value(&text);
+                    }
+
+                    // Keep the last valid search while the query is being typed
+                    match search_query::parse(&text) {
+                        Ok(query) => {
+                            search_error.set_label("");
+                            entry_filter.query = SearchQuery {
+                                fuzzy: entry_filter.query.fuzzy,
+                                ..query
+                            };
+                            sender.send(Message::EntryFilterChanged);
+                        }
+                        Err(err) => search_error.set_label(&format!("Search: {}", err)),
+                    }
+                }
+                Message::FuzzySearchChanged(fuzzy) => {
+                    check_fuzzy.set_checked(fuzzy);
+                    entry_filter.query.fuzzy = fuzzy;
+
+                    // Relevance order needs the column sort out of the way
+                    if fuzzy {
+                        dir_tbl.clear_sort();
+                    }
+                    sender.send(Message::EntryFilterChanged);
+                }
+                Message::GradeFilterChanged(filter) => {
+                    entry_filter.grade = filter;
+                    sender.send(Message::EntryFilterChanged);
+                }
+                Message::EntryFilterChanged => {
+                    entry_filter.rank = dir_tbl.sort().is_none();
+                    search_worker.schedule(SearchRequest {
+                        filter: entry_filter.clone(),
+                        labels: label_expr.lock().clone(),
+                    });
+                }
+                Message::SearchStarted => status_bar.set_searching(true),
+                Message::SearchFinished(generation, result) => {
+                    if search_worker.is_current(generation) {
+                        status_bar.set_searching(false);
+                        dir_tbl.set_highlight(entry_filter.query.clone());
+                        dir_tbl.show_rows(result.rows);
+
+                        let ix = get_selected_index(&mut dir_tbl);
+                        sender.send(Message::EntryChanged(ix.first().map(|ix| *ix as usize)));
+                        sender.send(Message::FileTableInvalidated);
+                    }
+                }
+
+                // Views
+                Message::ApplyView(view) => {
+                    if let Some(ix) = GradeFilter::choices().iter().position(|g| *g == view.grade) {
+                        grade_choice.set_value(ix as i32);
+                    }
+                    entry_filter.grade = view.grade;
+
+                    match view.sort {
+                        Some(sort) => dir_tbl.set_sort(sort.column, sort_order(sort.descending)),
+                        None => dir_tbl.clear_sort(),
+                    }
+
+                    check_fuzzy.set_checked(view.fuzzy);
+                    entry_filter.query.fuzzy = view.fuzzy;
+                    sender.send(Message::SearchChanged(view.search));
+
+                    // Refreshes the tables once the label filter is applied
+                    sender.send(Message::LabelQueryChanged(view.label_query));
+                }
+                Message::SaveView(saved) => {
+                    let view = ViewState {
+                        search: input.value(),
+                        label_query: label_query.value(),
+                        sort: dir_tbl.sort().map(|(column, ord)| ViewSort {
+                            column,
+                            descending: ord == SortOrder::Desc,
+                        }),
+                        grade: GradeFilter::choices()
+                            .get(grade_choice.value() as usize)
+                            .copied()
+                            .unwrap_or_default(),
+                        fuzzy: entry_filter.query.fuzzy,
+                    };
+
+                    let saved = match saved {
+                        Some(saved) => Some(SavedView {
+                            data: view.to_data(),
+                            ..saved
+                        }),
+                        None => dialog::input_default("View name", "")
+                            .map(|name| name.trim().to_string())
+                            .filter(|name| !name.is_empty())
+                            .map(|name| SavedView {
+                                id: -1,
+                                name,
+                                data: view.to_data(),
+                            }),
+                    };
+
+                    if let Some(saved) = saved {
+                        if let Err(err) = save_view(&app_db.lock(), &saved) {
+                            let err_dialog =
+                                ErrorDialog::new(format!("Failed to save view: {}", err));
+                            err_dialog.show();
+                        }
+                    }
+
+                    view_list.update();
+                }
+
+                // Locations
+                Message::ShowLocations => {
+                    let dialog = location_dialog::LocationDialog::new(
+                        lens.clone(),
+                        grades.clone(),
+                        app_db.clone(),
+                        &db_path,
+                        scanner.clone(),
+                    );
+                    dialog.show();
+                    sender.send(Message::LocationsChanged);
+                    // An edited location has the lens loaded again
+                    sender.send(Message::EntryTableInvalidated);
+                    sender.send(Message::FileTableInvalidated);
+                }
+                Message::LocationsChanged => {
+                    location_watcher.set_locations(watched_locations(&lens, &app_db))
+                }
 
                 // Entry Table
-                Message::EntryChanged(ix) => file_tbl.set_dir_ix(ix),
+                Message::EntryChanged(row) => {
+                    file_tbl.set_dir_ix(row.and_then(|row| dir_tbl.lens_ix(row)));
+                    status_bar.refresh(
+                        &lens,
+                        &mut dir_tbl,
+                        label_expr.lock().as_ref(),
+                        entry_filter.ranked(),
+                    );
+                }
 
                 Message::EntryTableInvalidated => {
+                    // Drops the rows of removed entries now, the search adds new ones
                     dir_tbl.update();
+                    sender.send(Message::EntryFilterChanged);
+
                     let ix = get_selected_index(&mut dir_tbl);
                     if !ix.is_empty() {
                         sender.send(Message::EntryChanged(Some(ix[0] as usize)));
@@ -491,17 +848,50 @@ fn main() {
                         sender.send(Message::EntryChanged(None));
                     }
                 }
-                Message::EntryTableSortCol(col) => dir_tbl.toggle_sort_column(col),
-                Message::EntryShowContextMenu(selection) => {
-                    show_entry_context_menu(selection, lens.clone(), sender.clone(), &mut wind)
+                Message::EntryTableSortCol(col) => {
+                    dir_tbl.toggle_sort_column(col);
+                    status_bar.refresh(&lens, &mut dir_tbl, label_expr.lock().as_ref(), false);
+
+                    // A column sort replaces the relevance order
+                    if entry_filter.rank {
+                        sender.send(Message::EntryFilterChanged);
+                    }
                 }
+                Message::EntryShowContextMenu(selection) => show_entry_context_menu(
+                    dir_tbl.lens_ixs(&selection),
+                    lens.clone(),
+                    grades.clone(),
+                    history.clone(),
+                    scanner.clone(),
+                    sender.clone(),
+                    &mut wind,
+                ),
+                Message::EntryShortcut(EntryAction::Navigate, selection) => sender.send(
+                    Message::EntryChanged(selection.first().map(|row| *row as usize)),
+                ),
+                Message::EntryShortcut(action, selection) => run_entry_action(
+                    action,
+                    dir_tbl.lens_ixs(&selection),
+                    lens.clone(),
+                    grades.clone(),
+                    history.clone(),
+                    scanner.clone(),
+                    sender.clone(),
+                    &mut wind,
+                ),
 
                 // File Table
                 Message::FileTableInvalidated => file_tbl.update(),
                 Message::FileTableSortCol(col) => file_tbl.toggle_sort_column(col),
-                Message::FileShowContextMenu(selection) => {
-                    show_file_context_menu(&mut file_tbl, selection, lens.clone(), sender.clone())
-                }
+                Message::FileShowContextMenu(selection) => show_file_context_menu(
+                    &mut file_tbl,
+                    selection,
+                    lens.clone(),
+                    grades.clone(),
+                    history.clone(),
+                    scanner.clone(),
+                    sender.clone(),
+                ),
                 Message::FileTableChanged(ix) => file_tbl.set_file_ix(ix as usize),
                 Message::FileTableOpen => {
                     let path = file_tbl.get_selected_file_path();
@@ -515,9 +905,293 @@ fn main() {
                 }
 
                 // Loading Dialog
-                Message::ShowLoading => loading_dialog.show(),
-                Message::HideLoading => loading_dialog.hide(),
+                Message::Reload => {
+                    let mode = if check_full_scan.is_checked() {
+                        ScanMode::Full
+                    } else {
+                        ScanMode::Incremental
+                    };
+
+                    scanner.reload(mode);
+                }
+                Message::ShowLoading => {
+                    scan_running = true;
+                    but_reload.deactivate();
+                    loading_dialog.show();
+                }
+                Message::HideLoading => {
+                    scan_running = false;
+                    but_reload.activate();
+                    loading_dialog.hide();
+                }
+                Message::ScanProgress(progress) => loading_dialog.update(&progress),
+                Message::ScanFinished(summary) => {
+                    println!("Scan finished {:?}", summary);
+                    scan_running = false;
+                    but_reload.activate();
+                    loading_dialog.finish(&summary);
+                }
+
+                // Delete
+                Message::Delete(target) => {
+                    delete_dialog::delete(
+                        target,
+                        lens.clone(),
+                        &history,
+                        &scanner,
+                        &sender,
+                        config.delete_mode(),
+                    );
+                }
+                Message::DeleteFinished(summary) => {
+                    sender.send(Message::EntryTableInvalidated);
+                    sender.send(Message::FileTableInvalidated);
+                    sender.send(Message::HistoryChanged);
+
+                    if let Some(text) = summary.failure_text() {
+                        let err_dialog = ErrorDialog::new(text);
+                        err_dialog.show();
+                    }
+                }
+
+                // Transfer Dialog
+                Message::StartTransfer(mode, origin, items) => {
+                    if transfer_running {
+                        let err_dialog = ErrorDialog::new(
+                            "Wait for the running move or copy to finish".to_string(),
+                        );
+                        err_dialog.show();
+                    } else {
+                        transfer_running = true;
+                        entry_transfer::start_transfer(
+                            grades.clone(),
+                            sender.clone(),
+                            transfer_cancel.clone(),
+                            scanner.clone(),
+                            items,
+                            mode,
+                            origin,
+                        );
+                    }
+                }
+                Message::TransferStarted(mode, origin) => transfer_dialog.show(mode, origin.what()),
+                Message::TransferProgress(progress) => transfer_dialog.update(&progress),
+                Message::TransferFinished(mode, origin, summary) => {
+                    println!("Transfer finished {:?}", summary);
+                    transfer_running = false;
+                    transfer_dialog.finish(mode, origin.what(), &summary);
+
+                    let complete = summary.errors.is_empty() && !summary.cancelled;
+                    match &origin {
+                        TransferOrigin::Undo(index, operation) if complete => {
+                            history.lock().mark_undone(*index, operation);
+                        }
+                        TransferOrigin::Redo(index, operation) if complete => {
+                            let done = operation.clone();
+                            history.lock().mark_redone(*index, operation, done);
+                        }
+                        _ if !summary.done.is_empty() => {
+                            if let Some(operation) = origin.operation(mode, summary.done.clone()) {
+                                history.lock().push(operation);
+                            }
+                        }
+                        _ => (),
+                    }
+                    sender.send(Message::HistoryChanged);
+
+                    if !summary.errors.is_empty() {
+                        let err_dialog = ErrorDialog::new(format!(
+                            "Failed to {} {} {}:\n{}",
+                            mode.verb().to_lowercase(),
+                            summary.errors.len(),
+                            origin.what(),
+                            summary.errors.join("\n")
+                        ));
+                        err_dialog.show();
+                    }
+                }
+
+                // History
+                Message::Undo => undo::undo(&history, &lens, &grades, &scanner, &sender),
+                Message::Redo => undo::redo(&history, &lens, &grades, &scanner, &sender),
+                Message::HistoryChanged => history_panel.update(&history.lock()),
+                Message::ShowHistory => {
+                    history_panel.update(&history.lock());
+                    history_panel.show();
+                }
+
+                // Duplicates
+                Message::ShowDuplicates => {
+                    let dialog = DuplicateDialog::new(
+                        lens.clone(),
+                        app_db.clone(),
+                        history.clone(),
+                        scanner.clone(),
+                    );
+                    dialog.show();
+                    sender.send(Message::EntryTableInvalidated);
+                    sender.send(Message::FileTableInvalidated);
+                    sender.send(Message::HistoryChanged);
+                }
+                Message::ShowSettings => {
+                    let dialog = ConfigDialog::new(config.clone(), app_config_path.clone());
+                    if let Some(new_config) = dialog.show() {
+                        apply_config(&new_config, &mut dir_tbl, &mut file_tbl);
+
+                        let new_db_path = new_config
+                            .db_path
+                            .clone()
+                            .unwrap_or_else(|| default_db_path.clone());
+                        if new_db_path != db_path {
+                            sender.send(Message::OpenLibrary(new_db_path));
+                        }
+
+                        config = new_config;
+                    }
+                }
+                Message::ShowSimilarNames => {
+                    let dialog = SimilarDialog::new(
+                        lens.clone(),
+                        grades.clone(),
+                        history.clone(),
+                        scanner.clone(),
+                        sender.clone(),
+                        config.delete_mode(),
+                    );
+                    dialog.show();
+                    sender.send(Message::EntryTableInvalidated);
+                    sender.send(Message::HistoryChanged);
+                    sender.send(Message::EntryChanged(None));
+                }
+
+                // Libraries
+                Message::ShowOpenLibrary => {
+                    if let Some(path) = library::choose_library(&db_path) {
+                        sender.send(Message::OpenLibrary(path));
+                    }
+                }
+                Message::ShowNewLibrary => match library::create_library(&db_path) {
+                    Some(Ok(path)) => sender.send(Message::OpenLibrary(path)),
+                    Some(Err(err)) => ErrorDialog::new(err).show(),
+                    None => (),
+                },
+                Message::OpenLibrary(path) => {
+                    if path == db_path {
+                        continue;
+                    }
+
+                    // Running jobs hold on to the lens and would write into the new library
+                    if scan_running || transfer_running {
+                        let err_dialog = ErrorDialog::new(
+                            "Wait for the running scan, move or copy to finish".to_string(),
+                        );
+                        err_dialog.show();
+                        continue;
+                    }
+
+                    if !Path::new(&path).is_file() {
+                        let err_dialog = ErrorDialog::new(format!("Library not found: {}", path));
+                        err_dialog.show();
+
+                        config.recent_libraries.retain(|p| *p != path);
+                        library::build_file_menu(&mut menu_bar, &sender, &config.recent_libraries);
+                        continue;
+                    }
+
+                    if let Err(err) = library::check_library(Path::new(&path)) {
+                        let err_dialog = ErrorDialog::new(err);
+                        err_dialog.show();
+                        continue;
+                    }
+
+                    println!("Open library {}", path);
+
+                    let library_db = match app_db::open(&path) {
+                        Ok(conn) => conn,
+                        Err(err) => {
+                            let err_dialog = ErrorDialog::new(format!(
+                                "Failed to open library {}: {}",
+                                path, err
+                            ));
+                            err_dialog.show();
+                            continue;
+                        }
+                    };
+
+                    // The old threads work on the lens, they are done before it is replaced.
+                    // Every widget shares this lens, so only its contents are replaced.
+                    location_watcher.stop();
+                    scanner.stop();
+                    *lens.lock() = Lens::new(&path);
+                    *app_db.lock() = library_db;
+                    grades.lock().reload();
+                    db_path = path;
+
+                    scanner = Scanner::new(
+                        lens.clone(),
+                        app_db.clone(),
+                        sender.clone(),
+                        scan_cancel.clone(),
+                    );
+                    location_watcher = LocationWatcher::new(scanner.clone());
+                    location_watcher.set_locations(watched_locations(&lens, &app_db));
+
+                    // Undo would act on entries of the old library
+                    *history.lock() = History::new();
+                    sender.send(Message::HistoryChanged);
+
+                    // Labels and views are per library, the search and sort are kept
+                    *label_expr.lock() = None;
+                    label_query.set_value("");
+                    label_error.set_label("");
+                    grade_choice.set_value(0);
+                    entry_filter.grade = GradeFilter::default();
+                    dir_tbl.show_rows(Vec::new());
+                    if let Some((col, ord)) = dir_tbl.sort() {
+                        dir_tbl.set_sort(col, ord);
+                    }
+
+                    file_tbl.set_dir_ix(None);
+                    label_list.update();
+                    view_list.update();
+                    sender.send(Message::EntryFilterChanged);
+
+                    wind.set_label(&library::window_title(&db_path));
+
+                    library::add_recent(&mut config.recent_libraries, &db_path);
+                    config.db_path = Some(db_path.clone());
+                    if let Err(err) = config.save(&app_config_path) {
+                        println!("Failed to save config {:?}: {}", app_config_path, err);
+                    }
+                    library::build_file_menu(&mut menu_bar, &sender, &config.recent_libraries);
+                }
             }
         }
     }
+
+    let (file_column, file_order) = file_tbl.sort();
+    let settings = WindowSettings {
+        x: wind.x(),
+        y: wind.y(),
+        width: wind.w(),
+        height: wind.h(),
+        entry_columns: column_widths(&dir_tbl.wid),
+        file_columns: column_widths(&file_tbl.wid),
+        entry_sort: dir_tbl.sort().map(|(column, ord)| ViewSort {
+            column,
+            descending: ord == SortOrder::Desc,
+        }),
+        file_sort: Some(ViewSort {
+            column: file_column,
+            descending: file_order == SortOrder::Desc,
+        }),
+        sidebar_width: filter_col.w(),
+        split_ratio: dir_tbl.h() as f32 / table_col.h().max(1) as f32,
+    };
+    if let Err(err) = settings.save(&settings_path) {
+        println!(
+            "Failed to save window settings {:?}: {}",
+            settings_path, err
+        );
+    }
 }
@@ -1,13 +1,41 @@
+use crate::delete_dialog::{DeleteSummary, DeleteTarget};
+use crate::entry_shortcuts::EntryAction;
+use crate::scan::scanner::{ScanProgress, ScanSummary};
+use crate::search::search_worker::SearchResult;
+use crate::transfer::entry_transfer::{
+    TransferItem, TransferMode, TransferOrigin, TransferProgress, TransferSummary,
+};
+use crate::view::grade_filter::GradeFilter;
+use crate::view::saved_view::{SavedView, ViewState};
+
 #[derive(Debug,  Clone)]
 pub enum Message {
     // Label Table Events
     LabelTableInvalidated,
+    LabelQueryChanged(String),
+
+    // View Events
+    ApplyView(ViewState),
+    SaveView(Option<SavedView>),
+    GradeFilterChanged(GradeFilter),
     
+    // Location Events
+    ShowLocations,
+    LocationsChanged,
+
+    // Search Events
+    SearchChanged(String),
+    FuzzySearchChanged(bool),
+    EntryFilterChanged,
+    SearchStarted,
+    SearchFinished(u64, SearchResult),
+
     // Entry Table Events
     EntryTableInvalidated,
     EntryTableSortCol(i32),
     EntryChanged(Option<usize>),
     EntryShowContextMenu(Vec<u32>),
+    EntryShortcut(EntryAction, Vec<u32>),
 
     // File table Events
     FileTableInvalidated,
@@ -17,6 +45,37 @@ pub enum Message {
     FileTableOpen,
 
     // Loading Events
+    Reload,
     ShowLoading,
     HideLoading,
+    ScanProgress(ScanProgress),
+    ScanFinished(ScanSummary),
+
+    // Delete Events
+    Delete(DeleteTarget),
+    DeleteFinished(DeleteSummary),
+
+    // Transfer Events
+    StartTransfer(TransferMode, TransferOrigin, Vec<TransferItem>),
+    TransferStarted(TransferMode, TransferOrigin),
+    TransferProgress(TransferProgress),
+    TransferFinished(TransferMode, TransferOrigin, TransferSummary),
+
+    // History Events
+    Undo,
+    Redo,
+    HistoryChanged,
+    ShowHistory,
+
+    // Settings Events
+    ShowSettings,
+
+    // Library Events
+    ShowOpenLibrary,
+    ShowNewLibrary,
+    OpenLibrary(String),
+
+    // Duplicate Events
+    ShowDuplicates,
+    ShowSimilarNames,
 }
@@ -0,0 +1,412 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::table_utils::pretty_grade;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseChange {
+    Keep,
+    Lower,
+    Upper,
+    Title,
+}
+
+impl CaseChange {
+    pub const ALL: [CaseChange; 4] = [
+        CaseChange::Keep,
+        CaseChange::Lower,
+        CaseChange::Upper,
+        CaseChange::Title,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CaseChange::Keep => "Keep case",
+            CaseChange::Lower => "lower case",
+            CaseChange::Upper => "UPPER CASE",
+            CaseChange::Title => "Title Case",
+        }
+    }
+
+    fn apply(&self, text: &str) -> String {
+        match self {
+            CaseChange::Keep => text.to_string(),
+            CaseChange::Lower => text.to_lowercase(),
+            CaseChange::Upper => text.to_uppercase(),
+            CaseChange::Title => {
+                let mut result = String::new();
+                let mut word_start = true;
+                for ch in text.chars() {
+                    if word_start {
+                        result.extend(ch.to_uppercase());
+                    } else {
+                        result.extend(ch.to_lowercase());
+                    }
+                    word_start = ch.is_whitespace() || "_-([".contains(ch);
+                }
+                result
+            }
+        }
+    }
+}
+
+/// Something to rename, with the values the tokens can insert
+#[derive(Debug, Clone)]
+pub struct RenameItem {
+    pub path: String,
+    pub grade: Option<i32>,
+    pub labels: Vec<String>,
+}
+
+/// How to build the new names.
+///
+/// `find` is replaced with `replace` in the old name, as a regex `replace` can use `$1` for
+/// capture groups. `pattern` then builds the name, `{name}` is the replaced name. Both `replace`
+/// and `pattern` can use `{n}`, `{n:03}` (number padded to 3 digits), `{grade}` and `{labels}`.
+#[derive(Debug, Clone)]
+pub struct RenameRule {
+    pub find: String,
+    pub replace: String,
+    pub use_regex: bool,
+    pub pattern: String,
+    pub case: CaseChange,
+    /// First number for `{n}`
+    pub start: usize,
+}
+
+impl Default for RenameRule {
+    fn default() -> Self {
+        RenameRule {
+            find: String::new(),
+            replace: String::new(),
+            use_regex: false,
+            pattern: "{name}".to_string(),
+            case: CaseChange::Keep,
+            start: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameStatus {
+    Ok,
+    Unchanged,
+    /// Two items get the same new name
+    Collision,
+    /// Another file already has the new name
+    Exists,
+    Invalid,
+}
+
+impl RenameStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RenameStatus::Ok => "",
+            RenameStatus::Unchanged => "Unchanged",
+            RenameStatus::Collision => "Collision",
+            RenameStatus::Exists => "Exists",
+            RenameStatus::Invalid => "Invalid name",
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            RenameStatus::Collision | RenameStatus::Exists | RenameStatus::Invalid
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenamePreview {
+    pub old_path: String,
+    pub old_name: String,
+    pub new_name: String,
+    pub status: RenameStatus,
+}
+
+impl RenamePreview {
+    pub fn new_path(&self) -> String {
+        Path::new(&self.old_path)
+            .with_file_name(&self.new_name)
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Replaces the tokens in `text`, `{name}` is only allowed when `name` is given.
+/// With `escape_dollar` the inserted values are escaped for a regex replacement.
+fn expand_tokens(
+    text: &str,
+    item: &RenameItem,
+    number: usize,
+    name: Option<&str>,
+    escape_dollar: bool,
+) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+
+        let end = rest[start..]
+            .find('}')
+            .map(|e| start + e)
+            .ok_or_else(|| format!("Missing }} after {}", &rest[start..]))?;
+        let token = &rest[start + 1..end];
+
+        let value = match (token, name) {
+            ("name", Some(name)) => name.to_string(),
+            ("grade", _) => pretty_grade(item.grade),
+            ("labels", _) => item.labels.join(", "),
+            ("n", _) => number.to_string(),
+            _ => {
+                let width = token
+                    .strip_prefix("n:")
+                    .and_then(|w| w.parse::<usize>().ok())
+                    .ok_or_else(|| format!("Unknown token {{{}}}", token))?;
+                format!("{:0width$}", number, width = width)
+            }
+        };
+
+        if escape_dollar {
+            result.push_str(&value.replace('$', "$$"));
+        } else {
+            result.push_str(&value);
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn new_name(
+    item: &RenameItem,
+    number: usize,
+    rule: &RenameRule,
+    regex: Option<&Regex>,
+) -> Result<String, String> {
+    let old_name = file_name(&item.path);
+
+    let replaced = if rule.find.is_empty() {
+        old_name
+    } else {
+        match regex {
+            Some(regex) => {
+                let replace = expand_tokens(&rule.replace, item, number, None, true)?;
+                regex.replace_all(&old_name, replace.as_str()).to_string()
+            }
+            None => {
+                let replace = expand_tokens(&rule.replace, item, number, None, false)?;
+                old_name.replace(&rule.find, &replace)
+            }
+        }
+    };
+
+    let pattern = if rule.pattern.trim().is_empty() {
+        "{name}"
+    } else {
+        &rule.pattern
+    };
+    let name = expand_tokens(pattern, item, number, Some(&replaced), false)?;
+
+    Ok(rule.case.apply(&name))
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.trim().is_empty() && name != "." && name != ".." && !name.contains('/')
+}
+
+/// Builds the new names and checks them. `exists` tells if a path is already taken on disk.
+pub fn preview(
+    items: &[RenameItem],
+    rule: &RenameRule,
+    exists: &dyn Fn(&str) -> bool,
+) -> Result<Vec<RenamePreview>, String> {
+    let regex = if rule.use_regex && !rule.find.is_empty() {
+        Some(Regex::new(&rule.find).map_err(|err| format!("Invalid regex: {}", err))?)
+    } else {
+        None
+    };
+
+    let mut previews = Vec::new();
+    for (ix, item) in items.iter().enumerate() {
+        let new_name = new_name(item, rule.start + ix, rule, regex.as_ref())?;
+        previews.push(RenamePreview {
+            old_path: item.path.clone(),
+            old_name: file_name(&item.path),
+            new_name,
+            status: RenameStatus::Ok,
+        });
+    }
+
+    let mut targets: HashMap<String, usize> = HashMap::new();
+    for preview in previews.iter() {
+        *targets.entry(preview.new_path()).or_insert(0) += 1;
+    }
+
+    for preview in previews.iter_mut() {
+        let new_path = preview.new_path();
+        preview.status = if !is_valid_name(&preview.new_name) {
+            RenameStatus::Invalid
+        } else if preview.new_name == preview.old_name {
+            RenameStatus::Unchanged
+        } else if targets.get(&new_path).copied().unwrap_or(0) > 1 {
+            RenameStatus::Collision
+        } else if exists(&new_path) {
+            RenameStatus::Exists
+        } else {
+            RenameStatus::Ok
+        };
+    }
+
+    Ok(previews)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table_utils::Grade;
+
+    fn item(path: &str) -> RenameItem {
+        RenameItem {
+            path: path.to_string(),
+            grade: None,
+            labels: Vec::new(),
+        }
+    }
+
+    fn names(items: &[RenameItem], rule: &RenameRule) -> Vec<String> {
+        preview(items, rule, &|_| false)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.new_name)
+            .collect()
+    }
+
+    #[test]
+    fn test_plain_replace() {
+        let rule = RenameRule {
+            find: ".".to_string(),
+            replace: " ".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(names(&[item("/m/A.Movie")], &rule), vec!["A Movie"]);
+    }
+
+    #[test]
+    fn test_regex_capture_groups() {
+        let rule = RenameRule {
+            find: r"^(.+)\.(\d{4})\..*$".to_string(),
+            replace: "$1 ($2)".to_string(),
+            use_regex: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&[item("/m/Some.Movie.2001.1080p.x264")], &rule),
+            vec!["Some.Movie (2001)"]
+        );
+    }
+
+    #[test]
+    fn test_numbering_and_tokens() {
+        let mut first = item("/m/a.mkv");
+        first.grade = Some(Grade::A as i32);
+        first.labels = vec!["Drama".to_string(), "Old".to_string()];
+
+        let rule = RenameRule {
+            pattern: "{n:03} {name} [{labels}]".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&[first, item("/m/b.mkv")], &rule),
+            vec!["001 a.mkv [Drama, Old]", "002 b.mkv []"]
+        );
+
+        let rule = RenameRule {
+            pattern: "{grade}-{name}".to_string(),
+            start: 5,
+            ..Default::default()
+        };
+        let mut graded = item("/m/a");
+        graded.grade = Some(Grade::A as i32);
+        assert_eq!(names(&[graded], &rule), vec!["A-a"]);
+    }
+
+    #[test]
+    fn test_inserted_dollar_is_not_a_group() {
+        let mut money = item("/m/a");
+        money.labels = vec!["$1".to_string()];
+
+        let rule = RenameRule {
+            find: "(a)".to_string(),
+            replace: "$1 {labels}".to_string(),
+            use_regex: true,
+            ..Default::default()
+        };
+        assert_eq!(names(&[money], &rule), vec!["a $1"]);
+    }
+
+    #[test]
+    fn test_case_change() {
+        assert_eq!(
+            CaseChange::Title.apply("the big_movie.mkv"),
+            "The Big_Movie.mkv"
+        );
+        assert_eq!(CaseChange::Upper.apply("ab"), "AB");
+        assert_eq!(CaseChange::Lower.apply("AB"), "ab");
+    }
+
+    #[test]
+    fn test_errors() {
+        let bad_regex = RenameRule {
+            find: "(".to_string(),
+            use_regex: true,
+            ..Default::default()
+        };
+        assert!(preview(&[item("/m/a")], &bad_regex, &|_| false).is_err());
+
+        let bad_token = RenameRule {
+            pattern: "{nope}".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            preview(&[item("/m/a")], &bad_token, &|_| false),
+            Err("Unknown token {nope}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_status() {
+        let rule = RenameRule {
+            pattern: "same".to_string(),
+            ..Default::default()
+        };
+        let result = preview(&[item("/m/a"), item("/m/b")], &rule, &|_| false).unwrap();
+        assert!(result.iter().all(|p| p.status == RenameStatus::Collision));
+
+        let result = preview(&[item("/m/a"), item("/n/b")], &rule, &|p| p == "/n/same").unwrap();
+        assert_eq!(result[0].status, RenameStatus::Ok);
+        assert_eq!(result[1].status, RenameStatus::Exists);
+
+        let result = preview(&[item("/m/same")], &rule, &|_| true).unwrap();
+        assert_eq!(result[0].status, RenameStatus::Unchanged);
+
+        let rule = RenameRule {
+            pattern: "a/b".to_string(),
+            ..Default::default()
+        };
+        let result = preview(&[item("/m/a")], &rule, &|_| false).unwrap();
+        assert_eq!(result[0].status, RenameStatus::Invalid);
+    }
+}
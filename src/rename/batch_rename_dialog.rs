@@ -0,0 +1,383 @@
+use fltk::app::channel;
+use fltk::enums::{Align, CallbackTrigger, Color};
+use fltk::frame::Frame;
+use fltk::input::{Input, IntInput};
+use fltk::menu::Choice;
+use fltk::{button::*, window::*};
+
+use fltk::prelude::*;
+
+use parking_lot::Mutex;
+use serious_organizer_lib::lens::Lens;
+use serious_organizer_lib::models::{Entry, File};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::error_dialog::ErrorDialog;
+use crate::grade_store::GradeStore;
+use crate::history::journal::{History, Operation};
+use crate::location::location_table::LocationTable;
+use crate::rename::batch_rename::{
+    self, CaseChange, RenameItem, RenamePreview, RenameRule, RenameStatus,
+};
+use crate::scan::scanner::Scanner;
+
+#[derive(Clone, Debug)]
+pub enum BatchRenameMessage {
+    RuleChanged,
+    RenameClicked,
+    ExitDialog,
+}
+
+/// What is renamed, entries through the lens or files directly on disk
+pub enum RenameTarget {
+    Entries(Vec<Entry>),
+    /// Files with the entry they belong to, its grade and labels are used for the tokens
+    Files(Vec<File>, Option<Entry>),
+}
+
+pub struct BatchRenameDialog {
+    lens: Arc<Mutex<Lens>>,
+    grades: Arc<Mutex<GradeStore>>,
+    history: Arc<Mutex<History>>,
+    scanner: Scanner,
+    target: RenameTarget,
+    items: Vec<RenameItem>,
+    previews: Arc<Mutex<Vec<RenamePreview>>>,
+}
+
+fn label_names(lens: &Lens, entry: &Entry) -> Vec<String> {
+    let label_ids = lens.entry_labels(entry.id as u32);
+
+    lens.get_labels()
+        .iter()
+        .filter(|l| label_ids.contains(&l.id))
+        .map(|l| l.name.clone())
+        .collect()
+}
+
+impl BatchRenameDialog {
+    pub fn new(
+        lens: Arc<Mutex<Lens>>,
+        grades: Arc<Mutex<GradeStore>>,
+        target: RenameTarget,
+        history: Arc<Mutex<History>>,
+        scanner: Scanner,
+    ) -> Self {
+        let items = {
+            let lens = lens.lock();
+            let grades = grades.lock();
+            match target {
+                RenameTarget::Entries(ref entries) => entries
+                    .iter()
+                    .map(|e| RenameItem {
+                        path: e.path.clone(),
+                        grade: grades.grade(e),
+                        labels: label_names(&lens, e),
+                    })
+                    .collect(),
+                RenameTarget::Files(ref files, ref entry) => {
+                    let grade = entry.as_ref().and_then(|e| grades.grade(e));
+                    let labels = entry
+                        .as_ref()
+                        .map(|e| label_names(&lens, e))
+                        .unwrap_or_default();
+
+                    files
+                        .iter()
+                        .map(|f| RenameItem {
+                            path: f.path.clone(),
+                            grade,
+                            labels: labels.clone(),
+                        })
+                        .collect()
+                }
+            }
+        };
+
+        BatchRenameDialog {
+            lens,
+            grades,
+            history,
+            scanner,
+            target,
+            items,
+            previews: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn show(&self) {
+        let (sender, reciever) = channel::<BatchRenameMessage>();
+
+        let title = match self.target {
+            RenameTarget::Entries(_) => "Batch Rename Entries",
+            RenameTarget::Files(..) => "Batch Rename Files",
+        };
+        let mut dialog = Window::new(300, 100, 600, 480, title);
+        dialog.make_modal(true);
+
+        let mut input_find = Input::new(70, 10, 200, 25, "Find");
+        let mut check_regex = CheckButton::new(280, 10, 70, 25, "Regex");
+        let mut input_start = IntInput::new(420, 10, 60, 25, "Start");
+        input_start.set_value("1");
+
+        let mut input_replace = Input::new(70, 40, 200, 25, "Replace");
+        input_replace.set_tooltip("With regex, $1 inserts the first capture group");
+
+        let mut input_pattern = Input::new(70, 70, 200, 25, "Pattern");
+        input_pattern.set_value("{name}");
+        let mut choice_case = Choice::new(420, 70, 120, 25, "Case");
+        for case in CaseChange::ALL.iter() {
+            choice_case.add_choice(case.label());
+        }
+        choice_case.set_value(0);
+
+        let mut frame_help = Frame::new(10, 100, 580, 20, None);
+        frame_help.set_label("Tokens: {name} {n} {n:03} {grade} {labels}");
+        frame_help.set_label_size(10);
+        frame_help.set_align(Align::Left | Align::Inside);
+
+        let mut frame_error = Frame::new(10, 120, 580, 20, None);
+        frame_error.set_label_size(10);
+        frame_error.set_label_color(Color::Red);
+        frame_error.set_align(Align::Left | Align::Inside);
+
+        let headers = vec![
+            "Old name".to_string(),
+            "New name".to_string(),
+            "Status".to_string(),
+        ];
+        let previews_c = self.previews.clone();
+        let mut table = LocationTable::new(
+            5,
+            145,
+            590,
+            290,
+            headers,
+            0,
+            Box::new(move |row, col| {
+                let previews = previews_c.lock();
+                let preview = match previews.get(row as usize) {
+                    Some(preview) => preview,
+                    None => return (String::new(), Align::Left, Color::Gray0),
+                };
+
+                let color = if preview.status.is_error() {
+                    Color::Red
+                } else if preview.status == RenameStatus::Unchanged {
+                    Color::Inactive
+                } else {
+                    Color::Gray0
+                };
+
+                match col {
+                    0 => (preview.old_name.clone(), Align::Left, color),
+                    1 => (preview.new_name.clone(), Align::Left, color),
+                    _ => (preview.status.label().to_string(), Align::Left, color),
+                }
+            }),
+        );
+
+        for (col, width) in [250, 250, 85].iter().enumerate() {
+            table.set_col_width(col as i32, *width);
+        }
+
+        let mut but_rename = Button::new(10, 445, 70, 25, "Rename");
+        let mut but_cancel = Button::new(90, 445, 70, 25, "Cancel");
+
+        dialog.end();
+        dialog.show();
+
+        but_rename.emit(sender.clone(), BatchRenameMessage::RenameClicked);
+        but_cancel.emit(sender.clone(), BatchRenameMessage::ExitDialog);
+
+        for input in [&mut input_find, &mut input_replace, &mut input_pattern] {
+            let sender_c = sender.clone();
+            input.set_trigger(CallbackTrigger::Changed);
+            input.set_callback(move |_| sender_c.send(BatchRenameMessage::RuleChanged));
+        }
+
+        let sender_c = sender.clone();
+        input_start.set_trigger(CallbackTrigger::Changed);
+        input_start.set_callback(move |_| sender_c.send(BatchRenameMessage::RuleChanged));
+
+        let sender_c = sender.clone();
+        check_regex.set_callback(move |_| sender_c.send(BatchRenameMessage::RuleChanged));
+
+        choice_case.set_callback(move |_| sender.send(BatchRenameMessage::RuleChanged));
+
+        let read_rule = || RenameRule {
+            find: input_find.value(),
+            replace: input_replace.value(),
+            use_regex: check_regex.is_checked(),
+            pattern: input_pattern.value(),
+            case: CaseChange::ALL
+                .get(choice_case.value().max(0) as usize)
+                .copied()
+                .unwrap_or(CaseChange::Keep),
+            start: input_start.value().parse().unwrap_or(1),
+        };
+
+        self.update_preview(&read_rule(), &mut table, &mut frame_error, &mut but_rename);
+
+        while dialog.shown() {
+            while fltk::app::wait() {
+                if let Some(msg) = reciever.recv() {
+                    match msg {
+                        BatchRenameMessage::RuleChanged => {
+                            self.update_preview(
+                                &read_rule(),
+                                &mut table,
+                                &mut frame_error,
+                                &mut but_rename,
+                            );
+                        }
+                        BatchRenameMessage::RenameClicked => {
+                            // Names on disk might have changed since the last preview
+                            self.update_preview(
+                                &read_rule(),
+                                &mut table,
+                                &mut frame_error,
+                                &mut but_rename,
+                            );
+                            if !but_rename.active() {
+                                continue;
+                            }
+
+                            dialog.hide();
+                            self.rename();
+                            break;
+                        }
+                        BatchRenameMessage::ExitDialog => {
+                            dialog.hide();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        println!("Exit batch rename dialog");
+    }
+
+    /// Rebuilds the preview, renaming is only allowed when no new name has a problem
+    fn update_preview(
+        &self,
+        rule: &RenameRule,
+        table: &mut LocationTable,
+        frame_error: &mut Frame,
+        but_rename: &mut Button,
+    ) {
+        let result = batch_rename::preview(&self.items, rule, &|path| Path::new(path).exists());
+
+        let mut previews = self.previews.lock();
+        match result {
+            Ok(new_previews) => {
+                let errors = new_previews.iter().filter(|p| p.status.is_error()).count();
+                let changes = new_previews
+                    .iter()
+                    .filter(|p| p.status == RenameStatus::Ok)
+                    .count();
+
+                if errors > 0 {
+                    frame_error.set_label(&format!("{} names have problems", errors));
+                } else {
+                    frame_error.set_label("");
+                }
+
+                if errors == 0 && changes > 0 {
+                    but_rename.activate();
+                } else {
+                    but_rename.deactivate();
+                }
+
+                *previews = new_previews;
+            }
+            Err(err) => {
+                frame_error.set_label(&err);
+                but_rename.deactivate();
+                previews.clear();
+            }
+        }
+
+        table.set_rows(previews.len() as i32);
+        table.redraw();
+        frame_error.redraw();
+    }
+
+    fn rename(&self) {
+        let previews: Vec<RenamePreview> = self
+            .previews
+            .lock()
+            .iter()
+            .filter(|p| p.status == RenameStatus::Ok)
+            .cloned()
+            .collect();
+
+        let mut errors = Vec::new();
+        let mut renamed = Vec::new();
+
+        match self.target {
+            RenameTarget::Entries(ref entries) => {
+                let mut lens = self.lens.lock();
+                let mut grades = self.grades.lock();
+                for preview in previews.iter() {
+                    let entry = match entries.iter().find(|e| e.path == preview.old_path) {
+                        Some(entry) => entry,
+                        None => continue,
+                    };
+
+                    println!("Rename from {} to {}", entry.name, preview.new_name);
+                    match lens.rename_entry(entry.clone(), &preview.new_name) {
+                        Ok(_) => {
+                            let new_path = preview.new_path();
+                            if let Err(err) = grades.move_path(&preview.old_path, &new_path) {
+                                errors.push(format!("{}: {}", preview.old_path, err));
+                            }
+                            renamed.push((preview.old_path.clone(), new_path));
+                        }
+                        Err(err) => errors.push(format!("{}: {}", preview.old_path, err)),
+                    }
+                }
+
+                self.scanner.rescan_paths(
+                    renamed
+                        .iter()
+                        .flat_map(|(old, new)| [Path::new(old.as_str()), Path::new(new.as_str())]),
+                );
+            }
+            RenameTarget::Files(..) => {
+                for preview in previews.iter() {
+                    let new_path = preview.new_path();
+                    println!("Rename file from {} to {}", preview.old_path, new_path);
+                    match fs::rename(&preview.old_path, &new_path) {
+                        Ok(_) => renamed.push((preview.old_path.clone(), new_path)),
+                        Err(err) => errors.push(format!("{}: {}", preview.old_path, err)),
+                    }
+                }
+
+                self.scanner
+                    .rescan_paths(previews.iter().map(|p| Path::new(p.old_path.as_str())));
+            }
+        }
+
+        if !renamed.is_empty() {
+            let operation = match self.target {
+                RenameTarget::Entries(_) => Operation::RenameEntries { renames: renamed },
+                RenameTarget::Files(..) => Operation::RenameFiles { renames: renamed },
+            };
+            self.history.lock().push(operation);
+        }
+
+        if !errors.is_empty() {
+            let err_dialog = ErrorDialog::new(format!(
+                "Failed to rename {} of {}:\n{}",
+                errors.len(),
+                previews.len(),
+                errors.join("\n")
+            ));
+            err_dialog.show();
+        }
+    }
+}
@@ -0,0 +1,2 @@
+pub mod batch_rename;
+pub mod batch_rename_dialog;
@@ -3,33 +3,118 @@ use fltk::{button::*, input::*, window::*};
 use fltk::{enums::*, prelude::*};
 use parking_lot::Mutex;
 use serious_organizer_lib::lens::Lens;
-use serious_organizer_lib::models::Entry;
+use serious_organizer_lib::models::{Entry, File};
 use std::path::Path;
 use std::sync::Arc;
 
 use crate::error_dialog::ErrorDialog;
+use crate::file_ops;
+use crate::grade_store::GradeStore;
+use crate::history::journal::{History, Operation};
+use crate::scan::scanner::Scanner;
+
+/// Entries are renamed through the lens and files on disk, either is followed by a rescan
+/// so the scan index has the new path
+#[derive(Clone)]
+enum Renamed {
+    Entry(Entry, Arc<Mutex<GradeStore>>, Scanner),
+    File(File, Scanner),
+}
+
+impl Renamed {
+    fn path(&self) -> &str {
+        match self {
+            Renamed::Entry(entry, ..) => &entry.path,
+            Renamed::File(file, _) => &file.path,
+        }
+    }
+
+    fn rename(
+        &self,
+        lens: &Arc<Mutex<Lens>>,
+        name: &str,
+        history: &Arc<Mutex<History>>,
+    ) -> Result<(), String> {
+        let old_path = self.path().to_string();
+        let new_path = Path::new(&old_path)
+            .with_file_name(name)
+            .to_string_lossy()
+            .to_string();
+
+        match self {
+            Renamed::Entry(entry, grades, scanner) => {
+                lens.lock()
+                    .rename_entry(entry.clone(), name)
+                    .map_err(|err| err.to_string())?;
+                scanner.rescan_paths([Path::new(&old_path), Path::new(&new_path)].into_iter());
+                grades.lock().move_path(&old_path, &new_path)?;
+
+                history
+                    .lock()
+                    .push(Operation::RenameEntry { old_path, new_path });
+            }
+            Renamed::File(_, scanner) => {
+                file_ops::move_file(Path::new(&old_path), Path::new(&new_path))
+                    .map_err(|err| err.to_string())?;
+                scanner.rescan_paths([Path::new(&old_path)].into_iter());
+
+                history.lock().push(Operation::RenameFiles {
+                    renames: vec![(old_path, new_path)],
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
 
 pub struct RenameDialog {
     lens: Arc<Mutex<Lens>>,
+    history: Arc<Mutex<History>>,
     label: Arc<Mutex<Option<String>>>,
-    entry: Entry,
+    renamed: Renamed,
 }
 
 impl RenameDialog {
-    pub fn new(lens: Arc<Mutex<Lens>>, entry: Entry) -> Self {
+    pub fn new(
+        lens: Arc<Mutex<Lens>>,
+        grades: Arc<Mutex<GradeStore>>,
+        entry: Entry,
+        history: Arc<Mutex<History>>,
+        scanner: Scanner,
+    ) -> Self {
+        RenameDialog {
+            lens,
+            history,
+            label: Arc::new(Mutex::new(None)),
+            renamed: Renamed::Entry(entry, grades, scanner),
+        }
+    }
+
+    pub fn for_file(
+        lens: Arc<Mutex<Lens>>,
+        file: File,
+        history: Arc<Mutex<History>>,
+        scanner: Scanner,
+    ) -> Self {
         RenameDialog {
             lens,
+            history,
             label: Arc::new(Mutex::new(None)),
-            entry,
+            renamed: Renamed::File(file, scanner),
         }
     }
 
     pub fn show(&self) {
         // let title = format!("Rename Entry: {}", self.entry.path).as_str();
-        let mut dialog = Window::new(300, 325, 450, 120, "Rename Entry");
+        let title = match self.renamed {
+            Renamed::Entry(..) => "Rename Entry",
+            Renamed::File(..) => "Rename File",
+        };
+        let mut dialog = Window::new(300, 325, 450, 120, title);
         dialog.make_modal(true);
 
-        let path = Path::new(&self.entry.path);
+        let path = Path::new(self.renamed.path());
         let name = path.file_name().unwrap().to_string_lossy();
 
         let mut output_name = Frame::new(60, 10, 390, 25, None);
@@ -45,21 +130,18 @@ impl RenameDialog {
         // Button save callback
         let lens_c = self.lens.clone();
         let label_c = self.label.clone();
-        let entry_c = self.entry.clone();
+        let renamed_c = self.renamed.clone();
+        let history_c = self.history.clone();
 
         let mut dialog_c = dialog.clone();
         but_save.set_callback(move |_| {
             let lbl = label_c.lock();
             if let Some(ref name) = *lbl {
-                {
-                    println!("Rename from {} to {}", entry_c.name, &name);
-                    let mut lens = lens_c.lock();
-                    let result = lens.rename_entry(entry_c.clone(), name);
-                    if let Err(err) = result {
-                        println!("Error while renaming file: {:?}", err);
-                        let err_dialog = ErrorDialog::new(err.to_string());
-                        err_dialog.show();
-                    }
+                println!("Rename from {} to {}", renamed_c.path(), &name);
+                if let Err(err) = renamed_c.rename(&lens_c, name, &history_c) {
+                    println!("Error while renaming file: {:?}", err);
+                    let err_dialog = ErrorDialog::new(err);
+                    err_dialog.show();
                 }
                 dialog_c.hide();
             }
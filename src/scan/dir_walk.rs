@@ -0,0 +1,277 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serious_organizer_lib::dir_search::{EntryData, FileData};
+
+use crate::scan::scan_rules::ScanRules;
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path_string(path))
+}
+
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "Scan cancelled")
+}
+
+#[cfg(unix)]
+fn dir_id(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Reads a top level entry of a location. A file is an entry with itself as the only file,
+/// a directory holds every file below it that the scan rules allow. A file the rules do
+/// not allow gives an entry without files.
+///
+/// `visit` gets every directory with the files read directly in it, a file entry is
+/// visited as its own directory. The walk stops with an `Interrupted` error when `visit`
+/// returns false.
+pub fn walk_entry(
+    location_id: i32,
+    root: &str,
+    path: &Path,
+    rules: &ScanRules,
+    visit: &mut dyn FnMut(&Path, &[FileData]) -> bool,
+) -> io::Result<EntryData> {
+    let meta = fs::metadata(path)?;
+
+    let files = if meta.is_dir() {
+        let mut walk = Walk {
+            root,
+            rules,
+            visit,
+            seen: HashSet::new(),
+            files: Vec::new(),
+        };
+        walk.dir(path, &meta)?;
+        walk.files
+    } else {
+        let mut files = Vec::new();
+        if rules.is_default() || rules.allows_file(root, &path_string(path)) {
+            files.push(FileData {
+                name: file_name(path),
+                path: path_string(path),
+                size: meta.len() as i64,
+            });
+        }
+        if !visit(path, &files) {
+            return Err(cancelled());
+        }
+        files
+    };
+
+    Ok(EntryData {
+        location_id,
+        name: file_name(path),
+        path: path_string(path),
+        size: files.iter().map(|f| f.size).sum(),
+        files,
+    })
+}
+
+struct Walk<'a> {
+    root: &'a str,
+    rules: &'a ScanRules,
+    visit: &'a mut dyn FnMut(&Path, &[FileData]) -> bool,
+    /// Directories already read, a link back up the tree would otherwise never end
+    seen: HashSet<(u64, u64)>,
+    files: Vec<FileData>,
+}
+
+impl Walk<'_> {
+    /// Default rules allow everything, so the paths are not matched
+    fn allows(&self, check: impl FnOnce(&ScanRules, &str) -> bool) -> bool {
+        self.rules.is_default() || check(self.rules, self.root)
+    }
+
+    fn dir(&mut self, dir: &Path, meta: &fs::Metadata) -> io::Result<()> {
+        if !self.allows(|rules, root| rules.allows_dir(root, &path_string(dir))) {
+            return Ok(());
+        }
+
+        if let Some(id) = dir_id(meta) {
+            if !self.seen.insert(id) {
+                println!("Skipping {:?}, it was already read", dir);
+                return Ok(());
+            }
+        }
+
+        let read_dir = match fs::read_dir(dir) {
+            Ok(read_dir) => read_dir,
+            Err(err) => {
+                println!("Failed to read dir {:?}: {}", dir, err);
+                return Ok(());
+            }
+        };
+
+        let first = self.files.len();
+        let mut sub_dirs = Vec::new();
+        for child in read_dir.flatten() {
+            let path = child.path();
+
+            let is_link = child.file_type().map(|t| t.is_symlink()).unwrap_or(false);
+            if is_link && !self.rules.follow_symlinks {
+                continue;
+            }
+
+            match fs::metadata(&path) {
+                Ok(meta) if meta.is_dir() => sub_dirs.push((path, meta)),
+                Ok(meta) => {
+                    if self.allows(|rules, root| rules.allows_file(root, &path_string(&path))) {
+                        self.files.push(FileData {
+                            name: file_name(&path),
+                            path: path_string(&path),
+                            size: meta.len() as i64,
+                        });
+                    }
+                }
+                Err(err) => println!("Failed to read meta data for {:?}: {}", path, err),
+            }
+        }
+
+        if !(self.visit)(dir, &self.files[first..]) {
+            return Err(cancelled());
+        }
+
+        for (sub_dir, meta) in sub_dirs.iter() {
+            self.dir(sub_dir, meta)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    #[test]
+    fn test_walk_entry() {
+        let root = temp_dir("dir_walk_entry");
+        fs::create_dir_all(root.join("movie/subs")).unwrap();
+        fs::write(root.join("movie/movie.mkv"), "12345").unwrap();
+        fs::write(root.join("movie/subs/en.srt"), "123").unwrap();
+        fs::write(root.join("single.txt"), "12").unwrap();
+
+        let loc = root.to_string_lossy().to_string();
+        let rules = ScanRules::default();
+
+        let mut visited = 0;
+        let dir = walk_entry(3, &loc, &root.join("movie"), &rules, &mut |_, _| {
+            visited += 1;
+            true
+        })
+        .unwrap();
+        let file = walk_entry(3, &loc, &root.join("single.txt"), &rules, &mut |_, _| true);
+        let file = file.unwrap();
+        let stopped = walk_entry(3, &loc, &root.join("movie"), &rules, &mut |_, _| false);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(visited, 2);
+        assert_eq!(stopped.unwrap_err().kind(), io::ErrorKind::Interrupted);
+
+        assert_eq!(dir.location_id, 3);
+        assert_eq!(dir.name, "movie");
+        assert_eq!(dir.size, 8);
+        assert_eq!(dir.files.len(), 2);
+
+        assert_eq!(file.name, "single.txt");
+        assert_eq!(file.size, 2);
+        assert_eq!(file.files.len(), 1);
+        assert_eq!(file.files[0].path, file.path);
+    }
+
+    #[test]
+    fn test_walk_rules() {
+        let root = temp_dir("dir_walk_rules");
+        fs::create_dir_all(root.join("movie/extras/deep")).unwrap();
+        fs::create_dir_all(root.join("movie/.git")).unwrap();
+        fs::write(root.join("movie/movie.mkv"), "12345").unwrap();
+        fs::write(root.join("movie/movie.part"), "1").unwrap();
+        fs::write(root.join("movie/.git/config"), "1").unwrap();
+        fs::write(root.join("movie/extras/trailer.mkv"), "123").unwrap();
+        fs::write(root.join("movie/extras/deep/file.mkv"), "1").unwrap();
+
+        let loc = root.to_string_lossy().to_string();
+        let rules = ScanRules {
+            exclude: vec!["*.part".to_string(), ".git/".to_string()],
+            max_depth: Some(3),
+            ..Default::default()
+        };
+
+        let mut dirs = Vec::new();
+        let entry = walk_entry(1, &loc, &root.join("movie"), &rules, &mut |dir, _| {
+            dirs.push(dir.to_path_buf());
+            true
+        })
+        .unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(dirs.len(), 2);
+        assert_eq!(entry.size, 8);
+        let mut names: Vec<_> = entry.files.iter().map(|f| f.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["movie.mkv", "trailer.mkv"]);
+    }
+
+    #[test]
+    fn test_walk_include_top_level_file() {
+        let root = temp_dir("dir_walk_include");
+        fs::write(root.join("movie.mkv"), "12345").unwrap();
+        fs::write(root.join("notes.txt"), "12").unwrap();
+
+        let loc = root.to_string_lossy().to_string();
+        let rules = ScanRules {
+            include: vec!["*.mkv".to_string()],
+            ..Default::default()
+        };
+
+        let movie = walk_entry(1, &loc, &root.join("movie.mkv"), &rules, &mut |_, _| true);
+        let notes = walk_entry(1, &loc, &root.join("notes.txt"), &rules, &mut |_, _| true);
+        let allowed = rules.allows_entry(&loc, &root.join("notes.txt").to_string_lossy());
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(movie.unwrap().files.len(), 1);
+        let notes = notes.unwrap();
+        assert!(notes.files.is_empty());
+        assert_eq!(notes.size, 0);
+        assert!(!allowed);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_symlink_loop() {
+        let root = temp_dir("dir_walk_loop");
+        fs::create_dir_all(root.join("movie/sub")).unwrap();
+        fs::write(root.join("movie/movie.mkv"), "12345").unwrap();
+        std::os::unix::fs::symlink(root.join("movie"), root.join("movie/sub/back")).unwrap();
+
+        let loc = root.to_string_lossy().to_string();
+        let follow = ScanRules::default();
+        let skip = ScanRules {
+            follow_symlinks: false,
+            ..Default::default()
+        };
+
+        let followed = walk_entry(1, &loc, &root.join("movie"), &follow, &mut |_, _| true);
+        let skipped = walk_entry(1, &loc, &root.join("movie"), &skip, &mut |_, _| true);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(followed.unwrap().files.len(), 1);
+        assert_eq!(skipped.unwrap().files.len(), 1);
+    }
+}
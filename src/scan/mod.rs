@@ -0,0 +1,5 @@
+pub mod dir_walk;
+pub mod scan_index;
+pub mod scan_rules;
+pub mod scanner;
+pub mod watcher;
@@ -0,0 +1,620 @@
+use rusqlite::{params, Connection};
+use serious_organizer_lib::dir_search::{EntryData, FileData};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Fingerprint of a top level entry in a location, used to find what changed since the last scan.
+/// For directories the modified time is the newest mtime of the directory and everything in it,
+/// and the size is the size of all files in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryStamp {
+    pub modified: u64,
+    pub size: i64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LocationIndex {
+    pub last_scan: Option<u64>,
+    pub entries: BTreeMap<String, EntryStamp>,
+}
+
+/// What was stored for each location after the last scan, kept in the library database
+#[derive(Debug, Clone, Default)]
+pub struct ScanIndex {
+    pub locations: HashMap<i32, LocationIndex>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+impl IndexDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl ScanIndex {
+    pub fn load(conn: &Connection) -> rusqlite::Result<ScanIndex> {
+        let mut index = ScanIndex::default();
+
+        let mut stmt = conn.prepare("SELECT id, last_scan FROM scan_location")?;
+        let locations = stmt.query_map([], |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, Option<i64>>(1)?))
+        })?;
+        for location in locations {
+            let (id, last_scan) = location?;
+            index.locations.insert(
+                id,
+                LocationIndex {
+                    last_scan: last_scan.map(|t| t as u64),
+                    entries: BTreeMap::new(),
+                },
+            );
+        }
+
+        let mut stmt = conn.prepare("SELECT location_id, path, modified, size FROM scan_stamp")?;
+        let stamps = stmt.query_map([], |row| {
+            let stamp = EntryStamp {
+                modified: row.get::<_, i64>(2)? as u64,
+                size: row.get(3)?,
+            };
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?, stamp))
+        })?;
+        for stamp in stamps {
+            let (id, path, stamp) = stamp?;
+            index
+                .locations
+                .entry(id)
+                .or_default()
+                .entries
+                .insert(path, stamp);
+        }
+
+        Ok(index)
+    }
+
+    pub fn location(&self, location_id: i32) -> Option<&LocationIndex> {
+        self.locations.get(&location_id)
+    }
+
+    /// Zeroes the modified times of a location, so every entry differs from what is on disk
+    pub fn invalidate(&mut self, location_id: i32) {
+        if let Some(location) = self.locations.get_mut(&location_id) {
+            for stamp in location.entries.values_mut() {
+                stamp.modified = 0;
+            }
+        }
+    }
+
+    pub fn remove_location(&mut self, location_id: i32) {
+        self.locations.remove(&location_id);
+    }
+
+    /// Moves the stored entries of a location to a new root path
+    pub fn move_location(&mut self, location_id: i32, old_root: &str, new_root: &str) {
+        if let Some(location) = self.locations.get_mut(&location_id) {
+            location.entries = std::mem::take(&mut location.entries)
+                .into_iter()
+                .map(|(path, stamp)| {
+                    let path = rebase_path(&path, old_root, new_root).unwrap_or(path);
+                    (path, stamp)
+                })
+                .collect();
+        }
+    }
+}
+
+/// Writes what a scan found in a location in one transaction. Every path in `stamps` was
+/// scanned and `entries` holds what is left of them after the scan rules, the paths in
+/// `removed` are gone. `last_scan` is only written when set.
+pub fn save_scan(
+    conn: &mut Connection,
+    location_id: i32,
+    last_scan: Option<u64>,
+    stamps: &BTreeMap<String, EntryStamp>,
+    entries: &[EntryData],
+    removed: &[String],
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+
+    match last_scan {
+        Some(last_scan) => tx.execute(
+            "INSERT OR REPLACE INTO scan_location (id, last_scan) VALUES (?1, ?2)",
+            params![location_id, last_scan as i64],
+        )?,
+        None => tx.execute(
+            "INSERT OR IGNORE INTO scan_location (id, last_scan) VALUES (?1, NULL)",
+            params![location_id],
+        )?,
+    };
+
+    for path in removed.iter().chain(stamps.keys()) {
+        tx.execute(
+            "DELETE FROM scan_stamp WHERE location_id = ?1 AND path = ?2",
+            params![location_id, path],
+        )?;
+        tx.execute("DELETE FROM scan_entry WHERE path = ?1", params![path])?;
+        tx.execute("DELETE FROM scan_file WHERE entry_path = ?1", params![path])?;
+    }
+
+    for (path, stamp) in stamps.iter() {
+        tx.execute(
+            "INSERT INTO scan_stamp (location_id, path, modified, size) VALUES (?1, ?2, ?3, ?4)",
+            params![location_id, path, stamp.modified as i64, stamp.size],
+        )?;
+    }
+
+    for entry in entries.iter() {
+        tx.execute(
+            "INSERT INTO scan_entry (path, location_id, name, size) VALUES (?1, ?2, ?3, ?4)",
+            params![entry.path, location_id, entry.name, entry.size],
+        )?;
+        for file in entry.files.iter() {
+            tx.execute(
+                "INSERT OR REPLACE INTO scan_file (entry_path, location_id, path, name, size)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![entry.path, location_id, file.path, file.name, file.size],
+            )?;
+        }
+    }
+
+    tx.commit()
+}
+
+/// The stored entries of the locations with their files, in the form the lens takes them
+pub fn load_entries(
+    conn: &Connection,
+    location_ids: &[i32],
+) -> rusqlite::Result<Vec<(String, EntryData)>> {
+    let mut entries = BTreeMap::new();
+
+    let mut stmt = conn.prepare("SELECT path, location_id, name, size FROM scan_entry")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(EntryData {
+            path: row.get(0)?,
+            location_id: row.get(1)?,
+            name: row.get(2)?,
+            size: row.get(3)?,
+            files: Vec::new(),
+        })
+    })?;
+    for entry in rows {
+        let entry = entry?;
+        if location_ids.contains(&entry.location_id) {
+            entries.insert(entry.path.clone(), entry);
+        }
+    }
+
+    let mut stmt = conn.prepare("SELECT entry_path, path, name, size FROM scan_file")?;
+    let rows = stmt.query_map([], |row| {
+        let file = FileData {
+            path: row.get(1)?,
+            name: row.get(2)?,
+            size: row.get(3)?,
+        };
+        Ok((row.get::<_, String>(0)?, file))
+    })?;
+    for file in rows {
+        let (entry_path, file) = file?;
+        if let Some(entry) = entries.get_mut(&entry_path) {
+            entry.files.push(file);
+        }
+    }
+
+    Ok(entries.into_iter().collect())
+}
+
+/// Zeroes the stored modified times of a location, see `ScanIndex::invalidate`
+pub fn invalidate_rows(conn: &Connection, location_id: i32) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE scan_stamp SET modified = 0 WHERE location_id = ?1",
+        params![location_id],
+    )?;
+    Ok(())
+}
+
+/// Deletes every stored row of a location, for when it is removed from the lens
+pub fn remove_rows(conn: &mut Connection, location_id: i32) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "DELETE FROM scan_location WHERE id = ?1",
+        params![location_id],
+    )?;
+    for table in ["scan_stamp", "scan_entry", "scan_file"] {
+        tx.execute(
+            &format!("DELETE FROM {} WHERE location_id = ?1", table),
+            params![location_id],
+        )?;
+    }
+
+    tx.commit()
+}
+
+/// Moves the stored rows of a location to a new root path
+pub fn move_rows(
+    conn: &mut Connection,
+    location_id: i32,
+    old_root: &str,
+    new_root: &str,
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+
+    for (table, column) in [
+        ("scan_stamp", "path"),
+        ("scan_entry", "path"),
+        ("scan_file", "path"),
+        ("scan_file", "entry_path"),
+    ] {
+        let paths: Vec<String> = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT {} FROM {} WHERE location_id = ?1",
+                column, table
+            ))?;
+            let rows = stmt.query_map(params![location_id], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        for path in paths.iter() {
+            if let Some(new_path) = rebase_path(path, old_root, new_root) {
+                tx.execute(
+                    &format!(
+                        "UPDATE {} SET {} = ?1 WHERE location_id = ?2 AND {} = ?3",
+                        table, column, column
+                    ),
+                    params![new_path, location_id, path],
+                )?;
+            }
+        }
+    }
+
+    tx.commit()
+}
+
+/// Replaces the `old_root` prefix of a path with `new_root`, None if the path is not below `old_root`
+pub fn rebase_path(path: &str, old_root: &str, new_root: &str) -> Option<String> {
+    let relative = Path::new(path).strip_prefix(old_root).ok()?;
+
+    if relative.as_os_str().is_empty() {
+        return Some(new_root.to_string());
+    }
+
+    Some(Path::new(new_root).join(relative).to_string_lossy().to_string())
+}
+
+fn modified_millis(meta: &fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Stamp of a directory from the metadata of everything below it, so a file changed in
+/// place shows as well. Every file is stat:ed but none is read, links are not followed.
+fn tree_stamp(path: &Path) -> EntryStamp {
+    let mut stamp = EntryStamp {
+        modified: fs::metadata(path).map(|m| modified_millis(&m)).unwrap_or(0),
+        size: 0,
+    };
+
+    if let Ok(read_dir) = fs::read_dir(path) {
+        for child in read_dir.flatten() {
+            let meta = match child.metadata() {
+                Ok(meta) => meta,
+                Err(_) => continue,
+            };
+
+            if meta.is_dir() {
+                let dir = tree_stamp(&child.path());
+                stamp.modified = stamp.modified.max(dir.modified);
+                stamp.size += dir.size;
+            } else {
+                stamp.modified = stamp.modified.max(modified_millis(&meta));
+                stamp.size += meta.len() as i64;
+            }
+        }
+    }
+
+    stamp
+}
+
+/// Stamps a top level entry
+pub fn stamp_entry(path: &Path) -> io::Result<EntryStamp> {
+    let meta = fs::metadata(path)?;
+
+    Ok(if meta.is_dir() {
+        tree_stamp(path)
+    } else {
+        EntryStamp {
+            modified: modified_millis(&meta),
+            size: meta.len() as i64,
+        }
+    })
+}
+
+/// Stamps every top level entry in a location
+pub fn stamp_location(location_path: &str) -> io::Result<BTreeMap<String, EntryStamp>> {
+    let mut stamps = BTreeMap::new();
+
+    for child in fs::read_dir(location_path)?.flatten() {
+        let path = child.path();
+        match stamp_entry(&path) {
+            Ok(stamp) => {
+                stamps.insert(path.to_string_lossy().to_string(), stamp);
+            }
+            Err(err) => println!("Failed to read meta data for {:?}: {}", path, err),
+        }
+    }
+
+    Ok(stamps)
+}
+
+/// Compares the stored stamps with the current ones
+pub fn diff(
+    stored: &BTreeMap<String, EntryStamp>,
+    current: &BTreeMap<String, EntryStamp>,
+) -> IndexDiff {
+    let mut result = IndexDiff::default();
+
+    for (path, stamp) in current.iter() {
+        match stored.get(path) {
+            None => result.added.push(path.clone()),
+            Some(old) => {
+                if old != stamp {
+                    result.modified.push(path.clone());
+                }
+            }
+        }
+    }
+
+    for path in stored.keys() {
+        if !current.contains_key(path) {
+            result.removed.push(path.clone());
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_db;
+    use crate::test_utils::temp_dir;
+
+    fn stamp(modified: u64, size: i64) -> EntryStamp {
+        EntryStamp { modified, size }
+    }
+
+    #[test]
+    fn test_diff_unchanged() {
+        let mut stored = BTreeMap::new();
+        stored.insert("/a".to_string(), stamp(1, 10));
+        stored.insert("/b".to_string(), stamp(2, 20));
+
+        let mut current = BTreeMap::new();
+        current.insert("/a".to_string(), stamp(1, 10));
+        current.insert("/b".to_string(), stamp(2, 20));
+
+        assert!(diff(&stored, &current).is_empty());
+    }
+
+    #[test]
+    fn test_diff_changes() {
+        let mut stored = BTreeMap::new();
+        stored.insert("/a".to_string(), stamp(1, 10));
+        stored.insert("/b".to_string(), stamp(2, 20));
+        stored.insert("/c".to_string(), stamp(3, 30));
+
+        let mut current = BTreeMap::new();
+        current.insert("/a".to_string(), stamp(5, 0));
+        current.insert("/b".to_string(), stamp(2, 25));
+        current.insert("/d".to_string(), stamp(4, 40));
+
+        let result = diff(&stored, &current);
+
+        assert_eq!(result.added, vec!["/d".to_string()]);
+        assert_eq!(result.removed, vec!["/c".to_string()]);
+        assert_eq!(result.modified, vec!["/a".to_string(), "/b".to_string()]);
+    }
+
+    #[test]
+    fn test_rebase_path() {
+        assert_eq!(
+            rebase_path("/mnt/old/movies/a", "/mnt/old", "/media/new"),
+            Some("/media/new/movies/a".to_string())
+        );
+        assert_eq!(
+            rebase_path("/mnt/old", "/mnt/old/", "/media/new"),
+            Some("/media/new".to_string())
+        );
+        assert_eq!(rebase_path("/mnt/older/a", "/mnt/old", "/media/new"), None);
+    }
+
+    #[test]
+    fn test_move_location() {
+        let mut index = ScanIndex::default();
+        let mut location = LocationIndex::default();
+        location.entries.insert("/old/a".to_string(), stamp(1, 10));
+        location.entries.insert("/old/b".to_string(), stamp(2, 20));
+        index.locations.insert(1, location);
+
+        index.move_location(1, "/old", "/new");
+
+        let entries = &index.location(1).unwrap().entries;
+        assert_eq!(entries.get("/new/a"), Some(&stamp(1, 10)));
+        assert_eq!(entries.get("/new/b"), Some(&stamp(2, 20)));
+        assert!(!entries.contains_key("/old/a"));
+    }
+
+    fn entry(path: &str, sizes: &[i64]) -> EntryData {
+        EntryData {
+            location_id: 1,
+            name: path.rsplit('/').next().unwrap().to_string(),
+            path: path.to_string(),
+            size: sizes.iter().sum(),
+            files: sizes
+                .iter()
+                .enumerate()
+                .map(|(ix, size)| FileData {
+                    name: format!("{}", ix),
+                    path: format!("{}/{}", path, ix),
+                    size: *size,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_save_scan() {
+        let path = temp_dir("scan_index_save").join("Library.sqlite3");
+        let mut conn = app_db::open(&path.to_string_lossy()).unwrap();
+
+        let mut stamps = BTreeMap::new();
+        stamps.insert("/loc/a".to_string(), stamp(1, 10));
+        stamps.insert("/loc/b".to_string(), stamp(2, 20));
+        let entries = [entry("/loc/a", &[4, 6]), entry("/loc/b", &[20])];
+        save_scan(&mut conn, 1, Some(100), &stamps, &entries, &[]).unwrap();
+
+        // Rescan b, which the rules now leave empty, and remove a
+        let mut changed = BTreeMap::new();
+        changed.insert("/loc/b".to_string(), stamp(3, 30));
+        let removed = ["/loc/a".to_string()];
+        save_scan(&mut conn, 1, None, &changed, &[], &removed).unwrap();
+        save_scan(&mut conn, 2, Some(200), &BTreeMap::new(), &[], &[]).unwrap();
+
+        let index = ScanIndex::load(&conn).unwrap();
+        let location = index.location(1).unwrap();
+        assert_eq!(location.last_scan, Some(100));
+        assert_eq!(location.entries.len(), 1);
+        assert_eq!(location.entries.get("/loc/b"), Some(&stamp(3, 30)));
+        assert_eq!(index.location(2).unwrap().last_scan, Some(200));
+
+        assert!(load_entries(&conn, &[1]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_load_entries() {
+        let path = temp_dir("scan_index_load").join("Library.sqlite3");
+        let mut conn = app_db::open(&path.to_string_lossy()).unwrap();
+
+        let mut stamps = BTreeMap::new();
+        stamps.insert("/loc/a".to_string(), stamp(1, 10));
+        let entries = [entry("/loc/a", &[4, 6])];
+        save_scan(&mut conn, 1, Some(100), &stamps, &entries, &[]).unwrap();
+
+        let loaded = load_entries(&conn, &[1]).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].0, "/loc/a");
+        assert_eq!(loaded[0].1.size, 10);
+        assert_eq!(loaded[0].1.files.len(), 2);
+
+        assert!(load_entries(&conn, &[2]).unwrap().is_empty());
+
+        invalidate_rows(&conn, 1).unwrap();
+        let index = ScanIndex::load(&conn).unwrap();
+        assert_eq!(index.location(1).unwrap().entries["/loc/a"].modified, 0);
+
+        move_rows(&mut conn, 1, "/loc", "/moved").unwrap();
+        let loaded = load_entries(&conn, &[1]).unwrap();
+        assert_eq!(loaded[0].0, "/moved/a");
+        assert_eq!(loaded[0].1.location_id, 1);
+        assert_eq!(loaded[0].1.files[0].path, "/moved/a/0");
+        let index = ScanIndex::load(&conn).unwrap();
+        assert_eq!(index.location(1).unwrap().last_scan, Some(100));
+        assert!(index.location(1).unwrap().entries.contains_key("/moved/a"));
+        assert!(!index.location(1).unwrap().entries.contains_key("/loc/a"));
+    }
+
+    #[test]
+    fn test_remove_rows() {
+        let path = temp_dir("scan_index_remove").join("Library.sqlite3");
+        let mut conn = app_db::open(&path.to_string_lossy()).unwrap();
+
+        for (id, root) in [(1, "/loc"), (2, "/other")] {
+            let mut stamps = BTreeMap::new();
+            stamps.insert(format!("{}/a", root), stamp(1, 10));
+            let entries = [entry(&format!("{}/a", root), &[4, 6])];
+            save_scan(&mut conn, id, Some(100), &stamps, &entries, &[]).unwrap();
+        }
+
+        remove_rows(&mut conn, 1).unwrap();
+
+        let index = ScanIndex::load(&conn).unwrap();
+        assert!(index.location(1).is_none());
+        assert!(index.location(2).is_some());
+        assert!(load_entries(&conn, &[1]).unwrap().is_empty());
+        assert_eq!(load_entries(&conn, &[2]).unwrap().len(), 1);
+        let files: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM scan_file WHERE location_id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(files, 0);
+    }
+
+    #[test]
+    fn test_stamp_location() {
+        let root = temp_dir("scan_index_stamp");
+        fs::create_dir_all(root.join("dir/sub")).unwrap();
+        fs::write(root.join("file.txt"), "hello").unwrap();
+
+        let stamps = stamp_location(&root.to_string_lossy()).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(stamps.len(), 2);
+
+        let file = stamps[&root.join("file.txt").to_string_lossy().to_string()];
+        assert_eq!(file.size, 5);
+
+        let dir = stamps[&root.join("dir").to_string_lossy().to_string()];
+        assert_eq!(dir.size, 0);
+        assert!(dir.modified > 0);
+    }
+
+    #[test]
+    fn test_stamp_sees_files_in_dirs() {
+        let root = temp_dir("scan_index_stamp_files");
+        let file = root.join("movie/subs/en.srt");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(root.join("movie/movie.mkv"), "12345").unwrap();
+        fs::write(&file, "123").unwrap();
+
+        let before = stamp_entry(&root.join("movie")).unwrap();
+
+        // Same size, only the mtime of the file changes
+        let later = SystemTime::now() + std::time::Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let touched = stamp_entry(&root.join("movie")).unwrap();
+
+        fs::write(&file, "1234").unwrap();
+        let grown = stamp_entry(&root.join("movie")).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(before.size, 8);
+        assert!(touched.modified > before.modified);
+        assert_eq!(grown.size, 9);
+    }
+}
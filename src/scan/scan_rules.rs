@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path};
+
+/// Rules for what to include when scanning a location.
+///
+/// Patterns support `*` (anything but `/`), `?` (one character) and `**` (anything).
+/// A pattern ending with `/` only matches directories, a pattern without `/` is matched
+/// against every name in the path and a pattern with `/` against the whole relative path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanRules {
+    pub exclude: Vec<String>,
+    pub include: Vec<String>,
+    pub max_depth: Option<usize>,
+    pub follow_symlinks: bool,
+    pub skip_hidden: bool,
+}
+
+impl Default for ScanRules {
+    fn default() -> Self {
+        ScanRules {
+            exclude: Vec::new(),
+            include: Vec::new(),
+            max_depth: None,
+            follow_symlinks: true,
+            skip_hidden: false,
+        }
+    }
+}
+
+/// Splits a comma separated list of patterns
+pub fn parse_patterns(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| p.to_string())
+        .collect()
+}
+
+pub fn format_patterns(patterns: &[String]) -> String {
+    patterns.join(", ")
+}
+
+/// Glob match on chars, see `ScanRules` for the syntax
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match_chars(&pattern, &text)
+}
+
+fn glob_match_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => {
+            let double = pattern.get(1) == Some(&'*');
+            let rest = if double { &pattern[2..] } else { &pattern[1..] };
+
+            for skip in 0..=text.len() {
+                if glob_match_chars(rest, &text[skip..]) {
+                    return true;
+                }
+
+                if !double && text.get(skip) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => {
+            !text.is_empty() && text[0] != '/' && glob_match_chars(&pattern[1..], &text[1..])
+        }
+        Some(ch) => text.first() == Some(ch) && glob_match_chars(&pattern[1..], &text[1..]),
+    }
+}
+
+fn names(relative: &Path) -> Vec<String> {
+    relative
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Matches a pattern against a path relative to the location, `is_dir` tells if the last name is a directory
+fn pattern_matches(pattern: &str, relative: &Path, is_dir: bool) -> bool {
+    let names = names(relative);
+
+    if let Some(dir_pattern) = pattern.strip_suffix('/') {
+        // All names except the last are directories
+        let dir_count = if is_dir {
+            names.len()
+        } else {
+            names.len().saturating_sub(1)
+        };
+        return names[..dir_count]
+            .iter()
+            .any(|name| glob_match(dir_pattern, name));
+    }
+
+    if pattern.contains('/') {
+        return glob_match(pattern.trim_start_matches('/'), &names.join("/"));
+    }
+
+    names.iter().any(|name| glob_match(pattern, name))
+}
+
+fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or(false)
+}
+
+impl ScanRules {
+    pub fn is_default(&self) -> bool {
+        *self == ScanRules::default()
+    }
+
+    fn allows(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let names = names(relative);
+
+        if self.skip_hidden && names.iter().any(|n| n.starts_with('.')) {
+            return false;
+        }
+
+        !self
+            .exclude
+            .iter()
+            .any(|p| pattern_matches(p, relative, is_dir))
+    }
+
+    /// Checks a top level entry of a location, a file entry is checked like a file inside
+    /// an entry. Links inside an entry are left to the walk, which does not follow them
+    /// unless `follow_symlinks` is set.
+    pub fn allows_entry(&self, root: &str, entry_path: &str) -> bool {
+        let path = Path::new(entry_path);
+
+        if !self.follow_symlinks && is_symlink(path) {
+            return false;
+        }
+
+        if !path.is_dir() {
+            return self.allows_file(root, entry_path);
+        }
+
+        self.allows(Path::new(root), path, true)
+    }
+
+    /// Checks a directory inside an entry, nothing below a directory that is not allowed
+    /// is read
+    pub fn allows_dir(&self, root: &str, dir_path: &str) -> bool {
+        let root = Path::new(root);
+        let path = Path::new(dir_path);
+
+        if let Some(max_depth) = self.max_depth {
+            // The files in the directory are one level deeper
+            let depth = names(path.strip_prefix(root).unwrap_or(path)).len();
+            if depth >= max_depth {
+                return false;
+            }
+        }
+
+        self.allows(root, path, true)
+    }
+
+    /// Checks a file inside an entry
+    pub fn allows_file(&self, root: &str, file_path: &str) -> bool {
+        let root = Path::new(root);
+        let path = Path::new(file_path);
+
+        if let Some(max_depth) = self.max_depth {
+            let depth = names(path.strip_prefix(root).unwrap_or(path)).len();
+            if depth > max_depth {
+                return false;
+            }
+        }
+
+        if !self.include.is_empty() {
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            if !self
+                .include
+                .iter()
+                .any(|p| pattern_matches(p, relative, false))
+            {
+                return false;
+            }
+        }
+
+        self.allows(root, path, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_simple() {
+        assert!(glob_match("*.part", "movie.mkv.part"));
+        assert!(!glob_match("*.part", "movie.mkv"));
+        assert!(glob_match("file?.txt", "file1.txt"));
+        assert!(!glob_match("file?.txt", "file10.txt"));
+        assert!(glob_match("exact", "exact"));
+    }
+
+    #[test]
+    fn test_glob_star_stops_at_slash() {
+        assert!(!glob_match("a/*.txt", "a/b/c.txt"));
+        assert!(glob_match("a/**.txt", "a/b/c.txt"));
+        assert!(glob_match("a/**/c.txt", "a/b/c.txt"));
+    }
+
+    #[test]
+    fn test_parse_patterns() {
+        assert_eq!(
+            parse_patterns(" *.part, .git/ ,,"),
+            vec!["*.part".to_string(), ".git/".to_string()]
+        );
+        assert_eq!(format_patterns(&parse_patterns("*.a,*.b")), "*.a, *.b");
+    }
+
+    #[test]
+    fn test_exclude_patterns() {
+        let rules = ScanRules {
+            exclude: parse_patterns("*.part, .git/"),
+            ..Default::default()
+        };
+
+        assert!(!rules.allows_file("/loc", "/loc/movie/movie.mkv.part"));
+        assert!(!rules.allows_file("/loc", "/loc/repo/.git/config"));
+        assert!(rules.allows_file("/loc", "/loc/repo/.gitignore"));
+        assert!(rules.allows_file("/loc", "/loc/movie/movie.mkv"));
+    }
+
+    #[test]
+    fn test_dir_pattern_does_not_match_file() {
+        let rules = ScanRules {
+            exclude: parse_patterns("tmp/"),
+            ..Default::default()
+        };
+
+        assert!(rules.allows_file("/loc", "/loc/a/tmp"));
+        assert!(!rules.allows_file("/loc", "/loc/a/tmp/file"));
+    }
+
+    #[test]
+    fn test_include_patterns() {
+        let rules = ScanRules {
+            include: parse_patterns("*.mkv, *.srt"),
+            ..Default::default()
+        };
+
+        assert!(rules.allows_file("/loc", "/loc/movie/movie.mkv"));
+        assert!(rules.allows_file("/loc", "/loc/movie/movie.srt"));
+        assert!(!rules.allows_file("/loc", "/loc/movie/movie.nfo"));
+
+        assert!(rules.allows_entry("/loc", "/loc/movie.mkv"));
+        assert!(!rules.allows_entry("/loc", "/loc/notes.txt"));
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let rules = ScanRules {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+
+        assert!(rules.allows_file("/loc", "/loc/file"));
+        assert!(rules.allows_file("/loc", "/loc/a/file"));
+        assert!(!rules.allows_file("/loc", "/loc/a/b/file"));
+    }
+
+    #[test]
+    fn test_allows_dir() {
+        let rules = ScanRules {
+            exclude: parse_patterns(".git/"),
+            max_depth: Some(2),
+            ..Default::default()
+        };
+
+        assert!(rules.allows_dir("/loc", "/loc/a"));
+        assert!(!rules.allows_dir("/loc", "/loc/a/b"));
+        assert!(!rules.allows_dir("/loc", "/loc/.git"));
+    }
+
+    #[test]
+    fn test_skip_hidden() {
+        let rules = ScanRules {
+            skip_hidden: true,
+            ..Default::default()
+        };
+
+        assert!(!rules.allows_file("/loc", "/loc/a/.hidden"));
+        assert!(!rules.allows_file("/loc", "/loc/.cache/file"));
+        assert!(rules.allows_file("/loc", "/loc/a/visible"));
+        assert!(!rules.allows_entry("/loc", "/loc/.trash"));
+    }
+
+    #[test]
+    fn test_default_allows_everything() {
+        let rules = ScanRules::default();
+        assert!(rules.is_default());
+        assert!(rules.allows_file("/loc", "/loc/.a/b/c/d/e.part"));
+    }
+}
@@ -0,0 +1,704 @@
+use fltk::app::Sender;
+use parking_lot::Mutex;
+use rusqlite::Connection;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serious_organizer_lib::dir_search::{EntryData, FileData};
+use serious_organizer_lib::lens::Lens;
+use serious_organizer_lib::models::Entry;
+
+use crate::location::location_options::LocationOptionsStore;
+use crate::location::location_path::is_online;
+use crate::model::message::Message;
+use crate::scan::dir_walk;
+use crate::scan::scan_index::{self, EntryStamp, ScanIndex};
+use crate::scan::scan_rules::ScanRules;
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanProgress {
+    pub location: String,
+    pub location_ix: usize,
+    pub location_count: usize,
+    pub dirs: usize,
+    pub files: usize,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanSummary {
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub skipped_locations: usize,
+    pub offline_locations: usize,
+}
+
+/// How often the walk sends progress to the loading dialog
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanMode {
+    /// Rescan every location
+    Full,
+    /// Only rescan the entries whose stamps changed since the last scan. Stamping still
+    /// stats every file, what is saved is reading the entries, storing them and updating
+    /// the lens.
+    Incremental,
+}
+
+/// What a scan found in one location, nothing is written until the scan is done
+struct LocationChanges {
+    location_id: i32,
+    /// Set when the whole location was scanned
+    last_scan: Option<u64>,
+    stamps: BTreeMap<String, EntryStamp>,
+    entries: Vec<EntryData>,
+    removed: Vec<String>,
+}
+
+/// Work for the scanner thread, done one job at a time in the order it was queued
+enum ScanJob {
+    Reload(ScanMode),
+    /// Rescans the top level entries that contain the paths, then puts the labels back on
+    /// the entries at their paths
+    Rescan {
+        paths: Vec<PathBuf>,
+        labels: Vec<(String, Vec<u32>)>,
+    },
+    /// Moves what is stored for a location to the path it got when it was edited, then
+    /// puts the labels back on the entries at their new paths
+    MoveLocation {
+        location_id: i32,
+        old_root: String,
+        new_root: String,
+        labels: Vec<(String, Vec<u32>)>,
+    },
+    /// Marks every stored entry of a location as modified
+    Invalidate(i32),
+    /// Forgets what is stored for a location that was removed
+    RemoveLocation(i32),
+    /// Ends the thread once the jobs queued before it are done
+    Stop,
+}
+
+/// Handle to the one thread that scans the locations of a library. The thread owns
+/// the scan index, so scans, rescans and changes to the index never overlap.
+///
+/// The stamps and the files of every scanned entry are kept in the library database.
+/// Only entries whose stamps changed are read from disk again, the lens gets the rest
+/// from the database.
+#[derive(Clone)]
+pub struct Scanner {
+    jobs: mpsc::Sender<ScanJob>,
+    cancel: Arc<AtomicBool>,
+    sender: Sender<Message>,
+    index: Arc<Mutex<ScanIndex>>,
+    thread: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl Scanner {
+    /// Starts the scanner thread, `cancel` stops a running reload
+    pub fn new(
+        lens: Arc<Mutex<Lens>>,
+        db: Arc<Mutex<Connection>>,
+        sender: Sender<Message>,
+        cancel: Arc<AtomicBool>,
+    ) -> Self {
+        let (jobs, receiver) = mpsc::channel();
+        let index = ScanIndex::load(&db.lock()).unwrap_or_else(|err| {
+            println!("Failed to load scan index: {}", err);
+            ScanIndex::default()
+        });
+        let index = Arc::new(Mutex::new(index));
+
+        let worker = Worker {
+            lens,
+            db,
+            sender: sender.clone(),
+            cancel: cancel.clone(),
+            index: index.clone(),
+        };
+        let thread = thread::spawn(move || worker.run(receiver));
+
+        Scanner {
+            jobs,
+            cancel,
+            sender,
+            index,
+            thread: Arc::new(Mutex::new(Some(thread))),
+        }
+    }
+
+    /// Lets the thread finish the queued jobs and waits for it, jobs queued after this
+    /// are dropped. Done before the lens or the database it works on is replaced.
+    pub fn stop(&self) {
+        let thread = match self.thread.lock().take() {
+            Some(thread) => thread,
+            None => return,
+        };
+
+        self.queue(ScanJob::Stop);
+        if thread.join().is_err() {
+            println!("Scanner thread panicked");
+        }
+    }
+
+    fn queue(&self, job: ScanJob) {
+        if self.jobs.send(job).is_err() {
+            println!("Scanner has stopped");
+        }
+    }
+
+    /// Scans the locations, progress is shown in the loading dialog
+    pub fn reload(&self, mode: ScanMode) {
+        self.cancel.store(false, Ordering::Relaxed);
+        self.sender.send(Message::ShowLoading);
+        self.queue(ScanJob::Reload(mode));
+    }
+
+    /// Rescans the top level entries that contain any of the paths, a location path
+    /// rescans the whole location
+    pub fn rescan_paths<'a>(&self, paths: impl Iterator<Item = &'a Path>) {
+        self.rescan_with_labels(paths, Vec::new());
+    }
+
+    /// Rescans like `rescan_paths` and then adds the labels to the entries at the paths
+    pub fn rescan_with_labels<'a>(
+        &self,
+        paths: impl Iterator<Item = &'a Path>,
+        labels: Vec<(String, Vec<u32>)>,
+    ) {
+        self.queue(ScanJob::Rescan {
+            paths: paths.map(|p| p.to_path_buf()).collect(),
+            labels,
+        });
+    }
+
+    /// Keeps the index in step with a location whose path was edited, so its entries
+    /// come back without being read again. `labels` are the labels of the entries by
+    /// their new path.
+    pub fn move_location(
+        &self,
+        location_id: i32,
+        old_root: &str,
+        new_root: &str,
+        labels: Vec<(String, Vec<u32>)>,
+    ) {
+        self.queue(ScanJob::MoveLocation {
+            location_id,
+            old_root: old_root.to_string(),
+            new_root: new_root.to_string(),
+            labels,
+        });
+    }
+
+    /// Has the next scan read every entry of the location again, for when its scan rules
+    /// change
+    pub fn invalidate(&self, location_id: i32) {
+        self.queue(ScanJob::Invalidate(location_id));
+    }
+
+    /// Deletes what is stored for a removed location, so nothing of it comes back if the
+    /// lens gives its id to a new location
+    pub fn remove_location(&self, location_id: i32) {
+        self.queue(ScanJob::RemoveLocation(location_id));
+    }
+
+    /// Reads the scan index, it is only written by the scanner thread
+    pub fn with_index<T>(&self, read: impl FnOnce(&ScanIndex) -> T) -> T {
+        read(&self.index.lock())
+    }
+}
+
+struct Worker {
+    lens: Arc<Mutex<Lens>>,
+    db: Arc<Mutex<Connection>>,
+    sender: Sender<Message>,
+    cancel: Arc<AtomicBool>,
+    index: Arc<Mutex<ScanIndex>>,
+}
+
+impl Worker {
+    /// Ends once every Scanner handle is dropped
+    fn run(&self, receiver: mpsc::Receiver<ScanJob>) {
+        self.seed_index();
+
+        while let Ok(job) = receiver.recv() {
+            match job {
+                ScanJob::Reload(mode) => self.reload(mode),
+                ScanJob::Rescan { paths, labels } => self.rescan(&paths, &labels),
+                ScanJob::MoveLocation {
+                    location_id,
+                    old_root,
+                    new_root,
+                    labels,
+                } => self.move_location(location_id, &old_root, &new_root, &labels),
+                ScanJob::Invalidate(location_id) => {
+                    self.index.lock().invalidate(location_id);
+                    if let Err(err) = scan_index::invalidate_rows(&self.db.lock(), location_id) {
+                        println!(
+                            "Failed to invalidate scan index of {}: {}",
+                            location_id, err
+                        );
+                    }
+                }
+                ScanJob::RemoveLocation(location_id) => {
+                    self.index.lock().remove_location(location_id);
+                    if let Err(err) = scan_index::remove_rows(&mut self.db.lock(), location_id) {
+                        println!("Failed to remove scan index of {}: {}", location_id, err);
+                    }
+                }
+                ScanJob::Stop => break,
+            }
+        }
+
+        println!("Scanner stopped");
+    }
+
+    /// Stores what the lens has for the locations that were never scanned, as in a library
+    /// from before the scan index, so a rescan does not drop it. The entries are stamped as
+    /// modified and read again by the next scan.
+    fn seed_index(&self) {
+        let changes: Vec<LocationChanges> = {
+            let lens = self.lens.lock();
+            let index = self.index.lock();
+            lens.get_locations()
+                .iter()
+                .filter(|l| index.location(l.id).is_none())
+                .map(|l| (l.id, lens_entries(&lens, l.id, &l.path)))
+                .filter(|(_, entries)| !entries.is_empty())
+                .map(|(location_id, entries)| LocationChanges {
+                    location_id,
+                    last_scan: None,
+                    stamps: entries
+                        .iter()
+                        .map(|e| {
+                            let stamp = EntryStamp {
+                                modified: 0,
+                                size: e.size,
+                            };
+                            (e.path.clone(), stamp)
+                        })
+                        .collect(),
+                    entries,
+                    removed: Vec::new(),
+                })
+                .collect()
+        };
+
+        if !changes.is_empty() {
+            println!("Seed scan index of {} locations", changes.len());
+            self.save(changes);
+        }
+    }
+
+    fn reload(&self, mode: ScanMode) {
+        println!("Start update data {:?}", mode);
+
+        let locations: Vec<_> = {
+            self.lens
+                .lock()
+                .get_locations()
+                .iter()
+                .map(|l| (l.id, l.name.clone(), l.path.clone()))
+                .collect()
+        };
+
+        let summary = self.scan_locations(locations, mode, true);
+        if self.cancel.load(Ordering::Relaxed) {
+            println!("Update data cancelled");
+            self.sender.send(Message::HideLoading);
+            return;
+        }
+
+        self.sender.send(Message::ScanFinished(summary));
+        self.sender.send(Message::EntryTableInvalidated);
+        self.sender.send(Message::LabelTableInvalidated);
+        println!("Done update data");
+    }
+
+    fn move_location(
+        &self,
+        location_id: i32,
+        old_root: &str,
+        new_root: &str,
+        labels: &[(String, Vec<u32>)],
+    ) {
+        self.index
+            .lock()
+            .move_location(location_id, old_root, new_root);
+        let moved = scan_index::move_rows(&mut self.db.lock(), location_id, old_root, new_root);
+        if let Err(err) = moved {
+            println!("Failed to move scan index of {}: {}", old_root, err);
+        }
+
+        // The stored entries are back right away, what differs at the new path follows
+        self.update_lens();
+        let location: Vec<_> = {
+            self.lens
+                .lock()
+                .get_locations()
+                .iter()
+                .filter(|l| l.id == location_id)
+                .map(|l| (l.id, l.name.clone(), l.path.clone()))
+                .collect()
+        };
+        let summary = self.scan_locations(location, ScanMode::Incremental, false);
+        println!("Moved location scanned {:?}", summary);
+
+        self.add_labels(labels);
+        self.sender.send(Message::EntryTableInvalidated);
+        self.sender.send(Message::FileTableInvalidated);
+        self.sender.send(Message::LabelTableInvalidated);
+    }
+
+    /// Adds the labels to the entries at the paths
+    fn add_labels(&self, labels: &[(String, Vec<u32>)]) {
+        if labels.is_empty() {
+            return;
+        }
+
+        let mut lens = self.lens.lock();
+        for (path, label_ids) in labels.iter() {
+            match entry_by_path(&lens, path).map(|e| e.id as u32) {
+                Some(id) => lens.add_entry_labels(vec![id], label_ids.clone()),
+                None => println!("Entry not found after rescan: {}", path),
+            }
+        }
+    }
+
+    fn rescan(&self, paths: &[PathBuf], labels: &[(String, Vec<u32>)]) {
+        let locations: Vec<_> = {
+            self.lens
+                .lock()
+                .get_locations()
+                .iter()
+                .map(|l| (l.id, l.name.clone(), l.path.clone()))
+                .collect()
+        };
+
+        // A path is rescanned through the top level entry it is in
+        let mut whole: Vec<(i32, String, String)> = Vec::new();
+        let mut targets: BTreeMap<i32, (String, BTreeSet<PathBuf>)> = BTreeMap::new();
+        for path in paths.iter() {
+            let location = locations.iter().find(|l| path.starts_with(&l.2));
+            let (id, name, root) = match location {
+                Some(location) => location,
+                None => continue,
+            };
+
+            let first = path
+                .strip_prefix(root)
+                .ok()
+                .and_then(|p| p.components().next());
+            match first {
+                Some(first) => {
+                    targets
+                        .entry(*id)
+                        .or_insert_with(|| (root.clone(), BTreeSet::new()))
+                        .1
+                        .insert(Path::new(root).join(first));
+                }
+                None => {
+                    if !whole.iter().any(|l| l.0 == *id) {
+                        whole.push((*id, name.clone(), root.clone()));
+                    }
+                }
+            }
+        }
+
+        if !whole.is_empty() {
+            let summary = self.scan_locations(whole.clone(), ScanMode::Full, false);
+            println!("Rescan done {:?}", summary);
+        }
+
+        let options = LocationOptionsStore::load(&self.db.lock());
+        let mut changes = Vec::new();
+        for (id, (root, paths)) in targets.into_iter() {
+            if whole.iter().any(|l| l.0 == id) || !is_online(&root) {
+                continue;
+            }
+
+            let rules = options.get(id).rules;
+            let mut stamps = BTreeMap::new();
+            let mut removed = Vec::new();
+            for path in paths.iter() {
+                let path_str = path.to_string_lossy().to_string();
+                let stamp = scan_index::stamp_entry(path)
+                    .ok()
+                    .filter(|_| rules.allows_entry(&root, &path_str));
+                match stamp {
+                    Some(stamp) => {
+                        stamps.insert(path_str, stamp);
+                    }
+                    None => removed.push(path_str),
+                }
+            }
+
+            let (stamps, entries) = walk_entries(id, &root, &rules, stamps, &mut |_, _| true);
+            changes.push(LocationChanges {
+                location_id: id,
+                last_scan: None,
+                stamps,
+                entries,
+                removed,
+            });
+        }
+
+        if !changes.is_empty() {
+            self.apply(changes);
+        }
+
+        self.add_labels(labels);
+        self.sender.send(Message::EntryTableInvalidated);
+        self.sender.send(Message::FileTableInvalidated);
+        self.sender.send(Message::LabelTableInvalidated);
+    }
+
+    /// Scans the locations and writes the ones that changed to the lens, shared by the
+    /// reload and the rescans. Only the top level entries that were added or modified
+    /// since the last scan are read, unless the mode is Full. Nothing is written when
+    /// cancelled.
+    fn scan_locations(
+        &self,
+        locations: Vec<(i32, String, String)>,
+        mode: ScanMode,
+        report: bool,
+    ) -> ScanSummary {
+        let options = LocationOptionsStore::load(&self.db.lock());
+        let mut summary = ScanSummary::default();
+        let mut progress = ScanProgress {
+            location_count: locations.len(),
+            ..Default::default()
+        };
+
+        let mut changes = Vec::new();
+
+        for (ix, (id, name, path)) in locations.into_iter().enumerate() {
+            if report && self.cancel.load(Ordering::Relaxed) {
+                return summary;
+            }
+
+            progress.location = name;
+            progress.location_ix = ix;
+            if report {
+                self.sender.send(Message::ScanProgress(progress.clone()));
+            }
+
+            if !is_online(&path) {
+                // Keep the entries of unreachable locations instead of wiping them
+                println!("Location {} is offline, skipping", path);
+                summary.offline_locations += 1;
+                continue;
+            }
+
+            let mut stamps = match scan_index::stamp_location(&path) {
+                Ok(stamps) => stamps,
+                Err(err) => {
+                    println!("Failed to read location {}: {}", path, err);
+                    Default::default()
+                }
+            };
+
+            let rules = options.get(id).rules;
+            stamps.retain(|entry_path, _| rules.allows_entry(&path, entry_path));
+
+            let diff = {
+                let index = self.index.lock();
+                match index.location(id) {
+                    Some(stored) => scan_index::diff(&stored.entries, &stamps),
+                    None => scan_index::diff(&Default::default(), &stamps),
+                }
+            };
+
+            if mode == ScanMode::Incremental && diff.is_empty() {
+                println!("Location {} unchanged, skipping", path);
+                summary.skipped_locations += 1;
+                continue;
+            }
+
+            summary.added += diff.added.len();
+            summary.removed += diff.removed.len();
+            summary.modified += diff.modified.len();
+
+            if mode == ScanMode::Incremental {
+                stamps.retain(|entry_path, _| {
+                    diff.added.contains(entry_path) || diff.modified.contains(entry_path)
+                });
+            }
+
+            let mut last_report = Instant::now();
+            let (stamps, entries) =
+                walk_entries(id, &path, &rules, stamps, &mut |_, files: &[FileData]| {
+                    progress.dirs += 1;
+                    progress.files += files.len();
+                    progress.bytes += files.iter().map(|f| f.size).sum::<i64>();
+
+                    if report && last_report.elapsed() >= PROGRESS_INTERVAL {
+                        last_report = Instant::now();
+                        self.sender.send(Message::ScanProgress(progress.clone()));
+                    }
+
+                    !(report && self.cancel.load(Ordering::Relaxed))
+                });
+
+            progress.location_ix = ix + 1;
+            if report {
+                self.sender.send(Message::ScanProgress(progress.clone()));
+            }
+
+            changes.push(LocationChanges {
+                location_id: id,
+                last_scan: Some(scan_index::now()),
+                stamps,
+                entries,
+                removed: diff.removed,
+            });
+        }
+
+        if report && self.cancel.load(Ordering::Relaxed) {
+            return summary;
+        }
+
+        if !changes.is_empty() {
+            self.apply(changes);
+        }
+
+        summary
+    }
+
+    /// Stores the changes and updates the lens
+    fn apply(&self, changes: Vec<LocationChanges>) {
+        self.save(changes);
+        self.update_lens();
+    }
+
+    fn save(&self, changes: Vec<LocationChanges>) {
+        let mut db = self.db.lock();
+        let mut index = self.index.lock();
+        for change in changes.into_iter() {
+            let saved = scan_index::save_scan(
+                &mut db,
+                change.location_id,
+                change.last_scan,
+                &change.stamps,
+                &change.entries,
+                &change.removed,
+            );
+            if let Err(err) = saved {
+                println!("Failed to save scan of {}: {}", change.location_id, err);
+                continue;
+            }
+
+            let location = index.locations.entry(change.location_id).or_default();
+            for path in change.removed.iter() {
+                location.entries.remove(path);
+            }
+            location.entries.extend(change.stamps);
+            if change.last_scan.is_some() {
+                location.last_scan = change.last_scan;
+            }
+        }
+    }
+
+    /// Gives the lens the stored entries of every location
+    fn update_lens(&self) {
+        // A location with nothing in the index, like one that was offline since it was
+        // added, keeps the entries the lens has for it
+        let (location_ids, kept): (Vec<i32>, Vec<EntryData>) = {
+            let lens = self.lens.lock();
+            let index = self.index.lock();
+            let locations = lens.get_locations();
+            let kept = locations
+                .iter()
+                .filter(|l| index.location(l.id).is_none())
+                .flat_map(|l| lens_entries(&lens, l.id, &l.path))
+                .collect();
+            (locations.iter().map(|l| l.id).collect(), kept)
+        };
+        let mut data = match scan_index::load_entries(&self.db.lock(), &location_ids) {
+            Ok(data) => data,
+            Err(err) => {
+                println!("Failed to load scanned entries: {}", err);
+                return;
+            }
+        };
+        data.extend(kept.into_iter().map(|e| (e.path.clone(), e)));
+
+        self.lens.lock().update_data(&mut data);
+    }
+}
+
+/// Reads the entries with the stamps from disk, entries that could not be read are left
+/// out of both. `visit` is passed on to the walk, the entries
+/// after a cancelled one are not read.
+fn walk_entries(
+    location_id: i32,
+    root: &str,
+    rules: &ScanRules,
+    mut stamps: BTreeMap<String, EntryStamp>,
+    visit: &mut dyn FnMut(&Path, &[FileData]) -> bool,
+) -> (BTreeMap<String, EntryStamp>, Vec<EntryData>) {
+    let mut data = Vec::new();
+    let mut cancelled = false;
+    stamps.retain(|path, _| {
+        if cancelled {
+            return false;
+        }
+
+        match dir_walk::walk_entry(location_id, root, Path::new(path), rules, visit) {
+            Ok(entry) => {
+                data.push((path.clone(), entry));
+                true
+            }
+            Err(err) => {
+                cancelled = err.kind() == io::ErrorKind::Interrupted;
+                println!("Failed to read {}: {}", path, err);
+                false
+            }
+        }
+    });
+
+    // An entry without any included file is left out, its stamp is kept so it is not read again
+    if !rules.include.is_empty() {
+        data.retain(|d| !d.1.files.is_empty());
+    }
+
+    (stamps, data.into_iter().map(|d| d.1).collect())
+}
+
+/// The entries the lens has below a location with their files, in the form the lens takes them
+fn lens_entries(lens: &Lens, location_id: i32, root: &str) -> Vec<EntryData> {
+    (0..lens.get_dir_count())
+        .filter_map(|ix| Some((lens.get_dir_entry(ix)?, lens.get_dir_files(ix))))
+        .filter(|(entry, _)| Path::new(&entry.path).starts_with(root))
+        .map(|(entry, files)| EntryData {
+            location_id,
+            name: entry.name.clone(),
+            path: entry.path.clone(),
+            size: entry.size,
+            files: files
+                .into_iter()
+                .flatten()
+                .map(|f| FileData {
+                    name: f.name.clone(),
+                    path: f.path.clone(),
+                    size: f.size,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// The entry at the path, the lens has no index by path so this goes through all entries
+pub fn entry_by_path<'a>(lens: &'a Lens, path: &str) -> Option<&'a Entry> {
+    (0..lens.get_dir_count())
+        .filter_map(|ix| lens.get_dir_entry(ix))
+        .find(|e| e.path == path)
+}
@@ -0,0 +1,135 @@
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::scan::scanner::Scanner;
+
+/// Events are collected until nothing has happened for this long
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Watches locations and has the scanner rescan where files are created, removed or renamed
+pub struct LocationWatcher {
+    watcher: Option<RecommendedWatcher>,
+    roots: Arc<Mutex<Vec<(i32, PathBuf)>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+fn is_relevant(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+    )
+}
+
+impl LocationWatcher {
+    pub fn new(scanner: Scanner) -> Self {
+        let roots: Arc<Mutex<Vec<(i32, PathBuf)>>> = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = mpsc::channel();
+
+        let watcher = match notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let _ = tx.send(res);
+        }) {
+            Ok(watcher) => Some(watcher),
+            Err(err) => {
+                println!("Failed to start file watcher: {}", err);
+                None
+            }
+        };
+
+        let roots_c = roots.clone();
+        let thread = thread::spawn(move || {
+            let mut pending: HashSet<PathBuf> = HashSet::new();
+
+            loop {
+                let timeout = if pending.is_empty() {
+                    Duration::from_secs(3600)
+                } else {
+                    DEBOUNCE
+                };
+
+                match rx.recv_timeout(timeout) {
+                    Ok(Ok(event)) => {
+                        if !is_relevant(&event.kind) {
+                            continue;
+                        }
+
+                        let roots = roots_c.lock();
+                        for path in event.paths.iter() {
+                            if roots.iter().any(|(_, r)| path.starts_with(r)) {
+                                pending.insert(path.clone());
+                            }
+                        }
+                    }
+                    Ok(Err(err)) => println!("File watcher error: {}", err),
+                    Err(RecvTimeoutError::Timeout) => {
+                        if pending.is_empty() {
+                            continue;
+                        }
+
+                        // The scanner applies the batch while new events are collected
+                        println!("Watcher rescan {} paths", pending.len());
+                        scanner.rescan_paths(pending.iter().map(|p| p.as_path()));
+                        pending.clear();
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+
+            println!("File watcher stopped");
+        });
+
+        LocationWatcher {
+            watcher,
+            roots,
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops watching and waits for the thread, a rescan it is sending is finished first
+    pub fn stop(&mut self) {
+        // The event channel closes with the watcher, which ends the thread
+        self.watcher = None;
+        self.roots.lock().clear();
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                println!("File watcher thread panicked");
+            }
+        }
+    }
+
+    /// Replaces the watched locations
+    pub fn set_locations(&mut self, locations: Vec<(i32, PathBuf)>) {
+        let watcher = if let Some(watcher) = self.watcher.as_mut() {
+            watcher
+        } else {
+            return;
+        };
+
+        let mut roots = self.roots.lock();
+
+        for (_, path) in roots.iter() {
+            if let Err(err) = watcher.unwatch(path) {
+                println!("Failed to unwatch {:?}: {}", path, err);
+            }
+        }
+
+        roots.clear();
+
+        for (id, path) in locations.into_iter() {
+            match watcher.watch(&path, RecursiveMode::Recursive) {
+                Ok(()) => {
+                    println!("Watching location {:?}", path);
+                    roots.push((id, path));
+                }
+                Err(err) => println!("Failed to watch {:?}: {}", path, err),
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use serious_organizer_lib::lens::Lens;
+use serious_organizer_lib::models::{Entry, File};
+
+use crate::search::search_query::{EntryFiles, SearchItem, SearchQuery};
+use crate::view::grade_filter::GradeFilter;
+
+/// Search query and grade filter, applied together by the search worker
+#[derive(Debug, Clone, Default)]
+pub struct EntryFilter {
+    pub query: SearchQuery,
+    pub grade: GradeFilter,
+    /// Order by fuzzy relevance, only used while no column sort is active
+    pub rank: bool,
+}
+
+impl EntryFilter {
+    /// Ordered by relevance, a fuzzy search with text while no column sort is active
+    pub fn ranked(&self) -> bool {
+        self.rank && self.query.fuzzy && self.query.has_text()
+    }
+
+    pub fn matches(&self, item: &SearchItem) -> bool {
+        self.grade.matches(item.grade) && self.query.matches(item)
+    }
+}
+
+impl EntryFiles for Vec<File> {
+    fn file_count(&self) -> usize {
+        self.len()
+    }
+
+    fn any_file(&self, predicate: &mut dyn FnMut(&str) -> bool) -> bool {
+        self.iter().any(|f| predicate(&f.name))
+    }
+}
+
+/// Borrows everything from the lens, the files are only looked at by terms that need them
+pub fn search_item<'a>(
+    entry: &'a Entry,
+    grade: Option<i32>,
+    files: &'a dyn EntryFiles,
+    location: &'a str,
+) -> SearchItem<'a> {
+    SearchItem {
+        name: &entry.name,
+        path: &entry.path,
+        size: entry.size,
+        grade,
+        location,
+        files,
+    }
+}
+
+/// Location names by path, entries belong to the location their path starts with
+#[derive(Debug, Clone, Default)]
+pub struct Locations {
+    locations: Vec<(String, String)>,
+}
+
+impl Locations {
+    pub fn new(lens: &Lens) -> Self {
+        Locations {
+            locations: lens
+                .get_locations()
+                .iter()
+                .map(|l| (l.path.clone(), l.name.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn name_of(&self, path: &str) -> &str {
+        self.locations
+            .iter()
+            .find(|(location, _)| Path::new(path).starts_with(location))
+            .map(|(_, name)| name.as_str())
+            .unwrap_or_default()
+    }
+}
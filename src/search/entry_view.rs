@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use serious_organizer_lib::lens::Lens;
+use serious_organizer_lib::models::Entry;
+
+/// Entries shown in the entry table, kept by id so the rows survive lens changes.
+///
+/// The lens itself stays unfiltered, a search only decides which of its entries
+/// are shown and in which order.
+#[derive(Debug, Clone, Default)]
+pub struct EntryView {
+    /// Entry ids in display order
+    rows: Vec<u32>,
+    /// Lens index of every entry id in the lens
+    ixs: HashMap<u32, usize>,
+    /// Entries in the lens
+    total: usize,
+}
+
+impl EntryView {
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Lens index of a table row
+    pub fn lens_ix(&self, row: usize) -> Option<usize> {
+        self.rows.get(row).and_then(|id| self.ixs.get(id)).copied()
+    }
+
+    /// Lens indexes of table rows, rows that are gone are left out
+    pub fn lens_ixs(&self, rows: &[u32]) -> Vec<u32> {
+        rows.iter()
+            .filter_map(|row| self.lens_ix(*row as usize))
+            .map(|ix| ix as u32)
+            .collect()
+    }
+
+    pub fn entry<'a>(&self, lens: &'a Lens, row: usize) -> Option<&'a Entry> {
+        self.lens_ix(row).and_then(|ix| lens.get_dir_entry(ix))
+    }
+
+    pub fn set_rows(&mut self, rows: Vec<u32>) {
+        self.rows = rows;
+    }
+
+    /// Stable sort of the rows by a key of their entries
+    pub fn sort_by_key<K: Ord>(
+        &mut self,
+        lens: &Lens,
+        key: impl Fn(&Entry) -> K,
+        descending: bool,
+    ) {
+        let ixs = &self.ixs;
+        let key_of = |id: &u32| lens.get_dir_entry(ixs[id]).map(&key);
+
+        self.rows.sort_by(|a, b| {
+            let ord = key_of(a).cmp(&key_of(b));
+            if descending {
+                ord.reverse()
+            } else {
+                ord
+            }
+        });
+    }
+
+    /// Looks up the lens indexes again after the lens changed, entries that are gone are
+    /// dropped. With `lens_order` the rows follow the lens order, as after a column sort.
+    pub fn reindex(&mut self, lens: &Lens, lens_order: bool) {
+        self.total = lens.get_dir_count();
+        self.ixs.clear();
+        self.ixs.reserve(self.total);
+
+        for ix in 0..self.total {
+            if let Some(entry) = lens.get_dir_entry(ix) {
+                self.ixs.insert(entry.id as u32, ix);
+            }
+        }
+
+        let ixs = &self.ixs;
+        self.rows.retain(|id| ixs.contains_key(id));
+        if lens_order {
+            self.rows.sort_by_key(|id| ixs[id]);
+        }
+    }
+}
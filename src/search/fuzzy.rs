@@ -0,0 +1,177 @@
+/// Fuzzy match of a pattern as a subsequence of a text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Char indices in the text that matched the pattern
+    pub positions: Vec<usize>,
+}
+
+const MATCH: i64 = 16;
+const BOUNDARY: i64 = 10;
+const CONSECUTIVE: i64 = 8;
+const GAP: i64 = 1;
+
+fn lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Start of the text, after a separator or a lower to upper case change
+fn is_boundary(prev: Option<char>, c: char) -> bool {
+    match prev {
+        None => true,
+        Some(prev) => {
+            !prev.is_alphanumeric()
+                || (prev.is_lowercase() && c.is_uppercase())
+                || (prev.is_alphabetic() && c.is_numeric())
+        }
+    }
+}
+
+/// Case insensitive match where every pattern char appears in order in the text.
+/// Matches at word boundaries and runs of consecutive chars score higher, gaps cost.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let pattern: Vec<char> = pattern.chars().map(lower).collect();
+    let chars: Vec<char> = text.chars().collect();
+    let lowered: Vec<char> = chars.iter().map(|c| lower(*c)).collect();
+
+    if pattern.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+
+    let bonus: Vec<i64> = (0..chars.len())
+        .map(|j| {
+            let prev = if j == 0 { None } else { Some(chars[j - 1]) };
+            if is_boundary(prev, chars[j]) {
+                BOUNDARY
+            } else {
+                0
+            }
+        })
+        .collect();
+
+    // best[i][j] is the best score with pattern[i] matched at text[j], from[i][j] the
+    // position pattern[i - 1] was matched at
+    let mut best: Vec<Vec<Option<i64>>> = vec![vec![None; chars.len()]; pattern.len()];
+    let mut from = vec![vec![0; chars.len()]; pattern.len()];
+
+    for (i, p) in pattern.iter().enumerate() {
+        // Best earlier match for a gap, stored as score + k * GAP so the gap cost
+        // to j is a single subtraction
+        let mut gap_best: Option<(i64, usize)> = None;
+
+        for j in 0..chars.len() {
+            if i > 0 && j >= 2 {
+                if let Some(score) = best[i - 1][j - 2] {
+                    let candidate = score + (j - 2) as i64 * GAP;
+                    if gap_best.is_none_or(|(s, _)| candidate > s) {
+                        gap_best = Some((candidate, j - 2));
+                    }
+                }
+            }
+
+            if lowered[j] != *p {
+                continue;
+            }
+
+            let gain = MATCH + bonus[j];
+            if i == 0 {
+                best[i][j] = Some(gain - j as i64 * GAP);
+                continue;
+            }
+
+            let consecutive = if j >= 1 {
+                best[i - 1][j - 1].map(|s| (s + CONSECUTIVE, j - 1))
+            } else {
+                None
+            };
+            let gap = gap_best.map(|(s, k)| (s - (j - 1) as i64 * GAP, k));
+
+            let prev = match (consecutive, gap) {
+                (Some(c), Some(g)) => Some(if c.0 >= g.0 { c } else { g }),
+                (c, g) => c.or(g),
+            };
+
+            if let Some((score, k)) = prev {
+                best[i][j] = Some(score + gain);
+                from[i][j] = k;
+            }
+        }
+    }
+
+    let last = pattern.len() - 1;
+    let (mut j, score) = best[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, s)| s.map(|s| (j, s)))
+        .max_by_key(|(j, s)| (*s, std::cmp::Reverse(*j)))?;
+
+    let mut positions = vec![0; pattern.len()];
+    for i in (0..pattern.len()).rev() {
+        positions[i] = j;
+        j = from[i][j];
+    }
+
+    Some(FuzzyMatch { score, positions })
+}
+
+/// Char indices of the first case insensitive occurrence of pattern in text
+pub fn substring_positions(pattern: &str, text: &str) -> Vec<usize> {
+    let pattern: Vec<char> = pattern.chars().map(lower).collect();
+    let lowered: Vec<char> = text.chars().map(lower).collect();
+
+    if pattern.is_empty() || pattern.len() > lowered.len() {
+        return Vec::new();
+    }
+
+    (0..=lowered.len() - pattern.len())
+        .find(|start| lowered[*start..*start + pattern.len()] == pattern[..])
+        .map(|start| (start..start + pattern.len()).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsequence() {
+        let m = fuzzy_match("bbb", "Big Buck Bunny").unwrap();
+        assert_eq!(m.positions, vec![0, 4, 9]);
+
+        assert!(fuzzy_match("bunny big", "Big Buck Bunny").is_none());
+        assert!(fuzzy_match("xyz", "Big Buck Bunny").is_none());
+        assert_eq!(fuzzy_match("", "anything").unwrap().score, 0);
+    }
+
+    #[test]
+    fn test_prefers_boundaries_and_runs() {
+        // "buck" as a word beats letters scattered through "bubble truck"
+        let word = fuzzy_match("buck", "The Buck Stops").unwrap();
+        let scattered = fuzzy_match("buck", "bubble truck").unwrap();
+        assert!(word.score > scattered.score);
+        assert_eq!(word.positions, vec![4, 5, 6, 7]);
+
+        // Camel case and digits count as word starts
+        let m = fuzzy_match("ss", "SeriousShows").unwrap();
+        assert_eq!(m.positions, vec![0, 7]);
+
+        // Earlier, tighter matches win for equal patterns
+        let near = fuzzy_match("ab", "ab______").unwrap();
+        let far = fuzzy_match("ab", "a______b").unwrap();
+        assert!(near.score > far.score);
+    }
+
+    #[test]
+    fn test_substring_positions() {
+        assert_eq!(
+            substring_positions("BUCK", "Big Buck Bunny"),
+            vec![4, 5, 6, 7]
+        );
+        assert!(substring_positions("duck", "Big Buck Bunny").is_empty());
+        assert!(substring_positions("", "Big").is_empty());
+        assert_eq!(substring_positions("é", "Café"), vec![3]);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod entry_filter;
+pub mod entry_view;
+pub mod fuzzy;
+pub mod search_query;
+pub mod search_worker;
@@ -0,0 +1,467 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::search::fuzzy::{fuzzy_match, substring_positions};
+use crate::table_utils::Grade;
+
+/// Search text such as `"big buck" size:>2GB grade:>=B -ext:nfo`.
+///
+/// Plain words and quoted phrases match the name, qualifiers are `size:`, `grade:`,
+/// `loc:`, `ext:`, `path:` and `files:`. A leading `-` negates a term.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SearchQuery {
+    pub clauses: Vec<Clause>,
+    /// Text terms match as a subsequence instead of a substring
+    pub fuzzy: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clause {
+    pub term: Term,
+    pub negated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Text(String),
+    Size(Cmp, u64),
+    /// Grade value, higher is better. None matches ungraded entries
+    Grade(Cmp, Option<i32>),
+    Location(String),
+    Ext(String),
+    Path(String),
+    Files(Cmp, usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+impl Cmp {
+    pub fn test<T: PartialOrd>(&self, value: T, limit: T) -> bool {
+        match self {
+            Cmp::Lt => value < limit,
+            Cmp::Le => value <= limit,
+            Cmp::Eq => value == limit,
+            Cmp::Ge => value >= limit,
+            Cmp::Gt => value > limit,
+        }
+    }
+
+    /// Splits a leading comparison from the value, no operator means equal
+    fn split(value: &str) -> (Cmp, &str) {
+        for (prefix, cmp) in [
+            (">=", Cmp::Ge),
+            ("<=", Cmp::Le),
+            (">", Cmp::Gt),
+            ("<", Cmp::Lt),
+            ("=", Cmp::Eq),
+        ] {
+            if let Some(rest) = value.strip_prefix(prefix) {
+                return (cmp, rest);
+            }
+        }
+        (Cmp::Eq, value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    /// Column of the problem, starting at 1
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (column {})", self.message, self.column)
+    }
+}
+
+/// Files of an entry, only looked at by `ext:` and `files:` terms
+pub trait EntryFiles {
+    fn file_count(&self) -> usize;
+
+    /// True when the predicate holds for any file name
+    fn any_file(&self, predicate: &mut dyn FnMut(&str) -> bool) -> bool;
+}
+
+/// What a query is matched against
+#[derive(Clone)]
+pub struct SearchItem<'a> {
+    pub name: &'a str,
+    pub path: &'a str,
+    pub size: i64,
+    pub grade: Option<i32>,
+    pub location: &'a str,
+    pub files: &'a dyn EntryFiles,
+}
+
+/// A word of the search text with its column, quotes removed
+struct Word {
+    text: String,
+    column: usize,
+    /// Byte offset in text where the first quote was
+    quote_at: Option<usize>,
+}
+
+fn split_words(text: &str) -> Result<Vec<Word>, ParseError> {
+    let mut words = Vec::new();
+    let mut current: Option<Word> = None;
+    let mut quote_start = None;
+
+    for (ix, c) in text.chars().enumerate() {
+        if c.is_whitespace() && quote_start.is_none() {
+            words.extend(current.take());
+            continue;
+        }
+
+        let word = current.get_or_insert(Word {
+            text: String::new(),
+            column: ix + 1,
+            quote_at: None,
+        });
+
+        if c == '"' {
+            word.quote_at.get_or_insert(word.text.len());
+            quote_start = match quote_start {
+                Some(_) => None,
+                None => Some(ix + 1),
+            };
+        } else {
+            word.text.push(c);
+        }
+    }
+
+    if let Some(column) = quote_start {
+        return Err(ParseError {
+            message: "Missing closing quote".to_string(),
+            column,
+        });
+    }
+
+    words.extend(current.take());
+    Ok(words)
+}
+
+/// Parses sizes like `700MB`, `2.5g` or `1024`, units are powers of 1024
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim().to_lowercase();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+
+    let factor: u64 = match unit {
+        "" | "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        "t" | "tb" => 1 << 40,
+        _ => return None,
+    };
+
+    let number: f64 = number.parse().ok()?;
+    Some((number * factor as f64) as u64)
+}
+
+fn parse_term(key: &str, value: &str, column: usize) -> Result<Term, ParseError> {
+    let error = |message: String| ParseError { message, column };
+
+    if value.is_empty() {
+        return Err(error(format!("Expected a value after '{}:'", key)));
+    }
+
+    let term = match key.to_lowercase().as_str() {
+        "size" => {
+            let (cmp, size) = Cmp::split(value);
+            let size = parse_size(size).ok_or_else(|| error(format!("Invalid size '{}'", size)))?;
+            Term::Size(cmp, size)
+        }
+        "grade" => {
+            let (cmp, grade) = Cmp::split(value);
+            if grade.eq_ignore_ascii_case("none") {
+                Term::Grade(cmp, None)
+            } else {
+                let grade = Grade::from_label(grade)
+                    .ok_or_else(|| error(format!("Unknown grade '{}', use S to F", grade)))?;
+                Term::Grade(cmp, Some(grade as i32))
+            }
+        }
+        "files" => {
+            let (cmp, count) = Cmp::split(value);
+            let count = count
+                .parse()
+                .map_err(|_| error(format!("Invalid file count '{}'", count)))?;
+            Term::Files(cmp, count)
+        }
+        "loc" | "location" => Term::Location(value.to_lowercase()),
+        "ext" => Term::Ext(value.trim_start_matches('.').to_lowercase()),
+        "path" => Term::Path(normalize_path(value)),
+        _ => return Err(error(format!("Unknown qualifier '{}:'", key))),
+    };
+
+    Ok(term)
+}
+
+/// Parses search text, empty text gives a query that matches everything
+pub fn parse(text: &str) -> Result<SearchQuery, ParseError> {
+    let mut clauses = Vec::new();
+
+    for word in split_words(text)? {
+        let mut body = word.text.as_str();
+        let mut quote_at = word.quote_at;
+
+        // `"-name"` searches for the dash, `-"name"` negates
+        let negated = body.len() > 1 && body.starts_with('-') && quote_at != Some(0);
+        if negated {
+            body = &body[1..];
+            quote_at = quote_at.map(|q| q - 1);
+        }
+
+        // Only a colon before any quote makes a qualifier, `"size:1"` is a name
+        let term = match body.find(':') {
+            Some(colon) if quote_at.is_none_or(|q| colon < q) => {
+                parse_term(&body[..colon], &body[colon + 1..], word.column)?
+            }
+            _ => Term::Text(body.to_lowercase()),
+        };
+
+        clauses.push(Clause { term, negated });
+    }
+
+    Ok(SearchQuery {
+        clauses,
+        fuzzy: false,
+    })
+}
+
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/").to_lowercase()
+}
+
+fn extension(name: &str) -> Option<String> {
+    Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+}
+
+impl Term {
+    fn matches(&self, item: &SearchItem, fuzzy: bool) -> bool {
+        match self {
+            Term::Text(text) if fuzzy => fuzzy_match(text, item.name).is_some(),
+            Term::Text(text) => item.name.to_lowercase().contains(text),
+            Term::Size(cmp, size) => cmp.test(item.size.max(0) as u64, *size),
+            Term::Grade(cmp, None) => (*cmp == Cmp::Eq) == item.grade.is_none(),
+            Term::Grade(cmp, Some(grade)) => item.grade.is_some_and(|g| cmp.test(g, *grade)),
+            Term::Location(loc) => item.location.to_lowercase().contains(loc),
+            Term::Ext(ext) => {
+                let mut is_ext = |name: &str| extension(name).as_deref() == Some(ext);
+                is_ext(item.name) || item.files.any_file(&mut is_ext)
+            }
+            Term::Path(path) => normalize_path(item.path).contains(path),
+            Term::Files(cmp, count) => cmp.test(item.files.file_count(), *count),
+        }
+    }
+}
+
+impl SearchQuery {
+    pub fn matches(&self, item: &SearchItem) -> bool {
+        self.clauses
+            .iter()
+            .all(|c| c.term.matches(item, self.fuzzy) != c.negated)
+    }
+
+    fn texts(&self) -> impl Iterator<Item = &str> {
+        self.clauses.iter().filter_map(|c| match &c.term {
+            Term::Text(text) if !c.negated => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn has_text(&self) -> bool {
+        self.texts().next().is_some()
+    }
+
+    /// Relevance of a matching item in fuzzy mode, higher is better
+    pub fn score(&self, item: &SearchItem) -> i64 {
+        self.texts()
+            .filter_map(|text| fuzzy_match(text, item.name))
+            .map(|m| m.score)
+            .sum()
+    }
+
+    fn text_positions(&self, text: &str, pattern: &str) -> Vec<usize> {
+        if self.fuzzy {
+            fuzzy_match(pattern, text)
+                .map(|m| m.positions)
+                .unwrap_or_default()
+        } else {
+            substring_positions(pattern, text)
+        }
+    }
+
+    /// Char indices in the name to highlight
+    pub fn name_highlights(&self, name: &str) -> Vec<usize> {
+        let mut positions: Vec<usize> = self
+            .texts()
+            .flat_map(|text| self.text_positions(name, text))
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    }
+
+    /// Char indices in the path to highlight, text terms and `path:` qualifiers
+    pub fn path_highlights(&self, path: &str) -> Vec<usize> {
+        let normalized = path.replace('\\', "/");
+        let mut positions: Vec<usize> = self
+            .clauses
+            .iter()
+            .filter(|c| !c.negated)
+            .flat_map(|c| match &c.term {
+                Term::Text(text) => self.text_positions(path, text),
+                Term::Path(part) => substring_positions(part, &normalized),
+                _ => Vec::new(),
+            })
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl EntryFiles for Vec<&str> {
+        fn file_count(&self) -> usize {
+            self.len()
+        }
+
+        fn any_file(&self, predicate: &mut dyn FnMut(&str) -> bool) -> bool {
+            self.iter().any(|name| predicate(name))
+        }
+    }
+
+    fn item<'a>(
+        name: &'a str,
+        size: i64,
+        grade: Option<Grade>,
+        files: &'a Vec<&'a str>,
+    ) -> SearchItem<'a> {
+        SearchItem {
+            name,
+            path: "D:\\Archive\\Movies\\Big Buck Bunny",
+            size,
+            grade: grade.map(|g| g as i32),
+            location: "Movies",
+            files,
+        }
+    }
+
+    fn matches(query: &str, item: &SearchItem) -> bool {
+        parse(query).unwrap().matches(item)
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("2KB"), Some(2048));
+        assert_eq!(parse_size("1.5m"), Some(1572864));
+        assert_eq!(parse_size("2GB"), Some(2 << 30));
+        assert_eq!(parse_size("2XB"), None);
+        assert_eq!(parse_size("GB"), None);
+    }
+
+    #[test]
+    fn test_qualifiers() {
+        let files = vec!["bunny.mkv", "bunny.nfo"];
+        let movie = item("Big Buck Bunny", 3 << 30, Some(Grade::A), &files);
+
+        assert!(matches("size:>2GB", &movie));
+        assert!(!matches("size:<2GB", &movie));
+        assert!(matches("grade:>=B", &movie));
+        assert!(!matches("grade:S", &movie));
+        assert!(!matches("grade:none", &movie));
+        assert!(matches("loc:movie", &movie));
+        assert!(matches("ext:.MKV", &movie));
+        assert!(!matches("ext:avi", &movie));
+        assert!(matches("path:/archive/", &movie));
+        assert!(matches("files:2", &movie));
+        assert!(!matches("files:>10", &movie));
+
+        let no_files = Vec::new();
+        let ungraded = item("Notes.txt", 10, None, &no_files);
+        assert!(matches("grade:none ext:txt", &ungraded));
+        assert!(!matches("grade:>=F", &ungraded));
+    }
+
+    #[test]
+    fn test_text_phrase_and_negation() {
+        let files = vec!["bunny.nfo"];
+        let movie = item("Big Buck Bunny", 100, None, &files);
+
+        assert!(matches("big bunny", &movie));
+        assert!(!matches("\"bunny big\"", &movie));
+        assert!(matches("\"buck bunny\"", &movie));
+        assert!(!matches("-bunny", &movie));
+        assert!(!matches("-ext:nfo", &movie));
+        assert!(matches("-\"big bunny\" -loc:shows", &movie));
+        assert!(matches("\"big buck\" size:<1KB", &movie));
+        assert!(matches("loc:\"mov\"", &movie));
+        assert!(matches("", &movie));
+
+        let no_files = Vec::new();
+        let odd = item("size:1 -draft", 100, None, &no_files);
+        assert!(matches("\"size:1\"", &odd));
+        assert!(matches("\"-draft\"", &odd));
+    }
+
+    #[test]
+    fn test_fuzzy_mode() {
+        let no_files = Vec::new();
+        let movie = item("Big Buck Bunny", 100, None, &no_files);
+        let mut query = parse("bgbun").unwrap();
+        assert!(!query.matches(&movie));
+
+        query.fuzzy = true;
+        assert!(query.matches(&movie));
+        assert!(query.score(&movie) > 0);
+        assert_eq!(
+            query.name_highlights("Big Buck Bunny"),
+            vec![0, 2, 9, 10, 11]
+        );
+
+        let query = parse("buck -big path:/movies/").unwrap();
+        assert_eq!(query.name_highlights("Big Buck Bunny"), vec![4, 5, 6, 7]);
+        assert_eq!(
+            query.path_highlights("D:\\Movies\\Buck"),
+            vec![2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text: &str| parse(text).unwrap_err();
+
+        assert_eq!(
+            error("bunny size:>2XB"),
+            ParseError {
+                message: "Invalid size '2XB'".to_string(),
+                column: 7
+            }
+        );
+        assert_eq!(error("grade:>=Q").message, "Unknown grade 'Q', use S to F");
+        assert_eq!(error("files:many").message, "Invalid file count 'many'");
+        assert_eq!(error("color:red").message, "Unknown qualifier 'color:'");
+        assert_eq!(error("size:").message, "Expected a value after 'size:'");
+        assert_eq!(error("big \"buck").column, 5);
+    }
+}
@@ -0,0 +1,182 @@
+use fltk::app::Sender;
+use parking_lot::Mutex;
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serious_organizer_lib::lens::Lens;
+use serious_organizer_lib::models::File;
+
+use crate::grade_store::GradeStore;
+use crate::label::label_expr::LabelExpr;
+use crate::model::message::Message;
+use crate::search::entry_filter::{search_item, EntryFilter, Locations};
+
+/// Wait for typing to pause before filtering
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Entries filtered per lock of the lens
+const CHUNK: usize = 2048;
+
+static NO_FILES: Vec<File> = Vec::new();
+
+/// Filters to search with
+#[derive(Debug, Clone, Default)]
+pub struct SearchRequest {
+    pub filter: EntryFilter,
+    pub labels: Option<LabelExpr>,
+}
+
+/// Matching entry ids in display order
+#[derive(Debug, Clone, Default)]
+pub struct SearchResult {
+    pub rows: Vec<u32>,
+}
+
+/// Filters the lens entries on one long-lived thread. Each schedule supersedes the
+/// searches before it, they stop early and their results are dropped.
+pub struct SearchWorker {
+    requests: mpsc::Sender<(u64, SearchRequest)>,
+    sender: Sender<Message>,
+    generation: Arc<AtomicU64>,
+}
+
+impl SearchWorker {
+    pub fn new(
+        lens: Arc<Mutex<Lens>>,
+        grades: Arc<Mutex<GradeStore>>,
+        sender: Sender<Message>,
+    ) -> Self {
+        let (requests, receiver) = mpsc::channel();
+        let generation = Arc::new(AtomicU64::new(0));
+
+        let generation_c = generation.clone();
+        let sender_c = sender.clone();
+        thread::spawn(move || run(lens, grades, receiver, generation_c, sender_c));
+
+        SearchWorker {
+            requests,
+            sender,
+            generation,
+        }
+    }
+
+    /// Searches once typing pauses, sends SearchFinished
+    pub fn schedule(&self, request: SearchRequest) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+
+        self.sender.send(Message::SearchStarted);
+        if self.requests.send((generation, request)).is_err() {
+            println!("Search worker has stopped");
+        }
+    }
+
+    /// True while no newer search has been scheduled
+    pub fn is_current(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::SeqCst) == generation
+    }
+}
+
+fn run(
+    lens: Arc<Mutex<Lens>>,
+    grades: Arc<Mutex<GradeStore>>,
+    receiver: mpsc::Receiver<(u64, SearchRequest)>,
+    latest: Arc<AtomicU64>,
+    sender: Sender<Message>,
+) {
+    // Ends once the SearchWorker is dropped
+    while let Ok(mut next) = receiver.recv() {
+        // A newer request restarts the wait
+        loop {
+            match receiver.recv_timeout(DEBOUNCE) {
+                Ok(newer) => next = newer,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        let (generation, request) = next;
+        let superseded = || latest.load(Ordering::SeqCst) != generation;
+
+        match search(&lens, &grades, &request, &superseded) {
+            Some(rows) if !superseded() => {
+                sender.send(Message::SearchFinished(generation, SearchResult { rows }))
+            }
+            _ => println!("Search {} superseded", generation),
+        }
+    }
+}
+
+/// Ids of the matching entries, in lens order or by relevance when ranked.
+/// None when a newer search came in.
+///
+/// The lens is only locked per chunk. Any change of the lens data in between
+/// schedules a new search, which supersedes this one.
+fn search(
+    lens: &Mutex<Lens>,
+    grades: &Mutex<GradeStore>,
+    request: &SearchRequest,
+    superseded: &dyn Fn() -> bool,
+) -> Option<Vec<u32>> {
+    let ranked = request.filter.ranked();
+    let mut matches: Vec<(u32, i64)> = Vec::new();
+    let mut start = 0;
+
+    loop {
+        if superseded() {
+            return None;
+        }
+
+        let lens = lens.lock();
+        let grades = grades.lock();
+        let count = lens.get_dir_count();
+        if start >= count {
+            break;
+        }
+
+        let locations = Locations::new(&lens);
+        for ix in start..count.min(start + CHUNK) {
+            let entry = match lens.get_dir_entry(ix) {
+                Some(entry) => entry,
+                None => continue,
+            };
+
+            if let Some(expr) = &request.labels {
+                let labels: Vec<u32> = lens
+                    .entry_labels(entry.id as u32)
+                    .into_iter()
+                    .map(|l| l as u32)
+                    .collect();
+                if !expr.matches(&labels) {
+                    continue;
+                }
+            }
+
+            let files = lens.get_dir_files(ix).unwrap_or(&NO_FILES);
+            let grade = grades.grade(entry);
+            let item = search_item(entry, grade, files, locations.name_of(&entry.path));
+            if !request.filter.matches(&item) {
+                continue;
+            }
+
+            let score = if ranked {
+                request.filter.query.score(&item)
+            } else {
+                0
+            };
+            matches.push((entry.id as u32, score));
+        }
+
+        start += CHUNK;
+    }
+
+    // Stable, equal scores keep the lens order
+    if ranked {
+        matches.sort_by_key(|(_, score)| Reverse(*score));
+    }
+
+    Some(matches.into_iter().map(|(id, _)| id).collect())
+}
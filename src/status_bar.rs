@@ -0,0 +1,150 @@
+use fltk::enums::*;
+use fltk::frame::Frame;
+use fltk::prelude::*;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use serious_organizer_lib::lens::{Lens, SortOrder};
+
+use crate::entry_table::EntryTable;
+use crate::get_selected_index;
+use crate::label::label_expr::LabelExpr;
+use crate::table_utils::pretty_size;
+
+const SORT_COLUMNS: [&str; 4] = ["Name", "Path", "Size", "Grade"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatusInfo {
+    pub visible: usize,
+    pub total: usize,
+    pub selected: usize,
+    pub selected_size: i64,
+    pub labels_included: usize,
+    pub labels_excluded: usize,
+    pub sort: String,
+    pub searching: bool,
+}
+
+pub fn status_text(info: &StatusInfo) -> String {
+    let mut parts = vec![format!("{} of {} entries", info.visible, info.total)];
+
+    if info.selected > 0 {
+        parts.push(format!(
+            "{} selected, {}",
+            info.selected,
+            pretty_size(info.selected_size)
+        ));
+    }
+
+    if info.labels_included > 0 || info.labels_excluded > 0 {
+        parts.push(format!(
+            "Labels: {} included, {} excluded",
+            info.labels_included, info.labels_excluded
+        ));
+    }
+
+    parts.push(format!("Sort: {}", info.sort));
+
+    if info.searching {
+        parts.push("Searching...".to_string());
+    }
+
+    parts.join("  |  ")
+}
+
+/// Bottom line of the main window with counts, selection size and filter summary
+pub struct StatusBar {
+    pub frame: Frame,
+    info: StatusInfo,
+}
+
+impl StatusBar {
+    pub fn new(w: i32, h: i32) -> Self {
+        let mut frame = Frame::default().with_size(w, h);
+        frame.set_label_size(12);
+        frame.set_align(Align::Left | Align::Inside | Align::Clip);
+
+        StatusBar {
+            frame,
+            info: StatusInfo::default(),
+        }
+    }
+
+    pub fn set_searching(&mut self, searching: bool) {
+        self.info.searching = searching;
+        self.frame.set_label(&status_text(&self.info));
+    }
+
+    /// Reads the counts from the lens and entry table, `ranked` when ordered by relevance
+    pub fn refresh(
+        &mut self,
+        lens: &Arc<Mutex<Lens>>,
+        dir_tbl: &mut EntryTable,
+        label_expr: Option<&LabelExpr>,
+        ranked: bool,
+    ) {
+        let selection = get_selected_index(&mut dir_tbl.wid);
+
+        {
+            let lens = lens.lock();
+            let view = dir_tbl.view();
+            self.info.visible = view.len();
+            self.info.total = view.total();
+            self.info.selected = selection.len();
+            self.info.selected_size = selection
+                .iter()
+                .filter_map(|row| view.entry(&lens, *row as usize))
+                .map(|e| e.size)
+                .sum();
+        }
+
+        let (included, excluded) = label_expr.map(|e| e.label_counts()).unwrap_or_default();
+        self.info.labels_included = included;
+        self.info.labels_excluded = excluded;
+
+        self.info.sort = match dir_tbl.sort() {
+            Some((col, ord)) => {
+                let order = if ord == SortOrder::Desc {
+                    "desc"
+                } else {
+                    "asc"
+                };
+                let name = SORT_COLUMNS.get(col as usize).unwrap_or(&"?");
+                format!("{} {}", name, order)
+            }
+            None if ranked => "Relevance".to_string(),
+            None => "None".to_string(),
+        };
+
+        self.frame.set_label(&status_text(&self.info));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_text() {
+        let mut info = StatusInfo {
+            visible: 12,
+            total: 340,
+            sort: "None".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(status_text(&info), "12 of 340 entries  |  Sort: None");
+
+        info.selected = 3;
+        info.selected_size = 1536;
+        info.labels_included = 2;
+        info.sort = "Size desc".to_string();
+        info.searching = true;
+        assert_eq!(
+            status_text(&info),
+            format!(
+                "12 of 340 entries  |  3 selected, {}  |  Labels: 2 included, 0 excluded  |  Sort: Size desc  |  Searching...",
+                pretty_size(1536)
+            )
+        );
+    }
+}
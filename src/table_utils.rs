@@ -1,5 +1,6 @@
 #![allow(clippy::too_many_arguments)]
 use std::cmp::{self};
+use std::sync::OnceLock;
 
 use fltk::enums::*;
 use fltk::prelude::{TableExt, WidgetExt};
@@ -42,17 +43,66 @@ pub fn draw_data_color(
     draw::pop_clip();
 }
 
-pub fn get_file_color(file_name: &str) -> Color {
-    const VIDEO_FORMATS: [&str; 23] = [
-        ".mkv", ".webm", ".flv", ".vob", ".ogg", ".ogv", ".avi", ".mov", ".qt", ".wmv", ".rm",
-        ".rmvb", ".asf", ".amv", ".mp4", ".m4p", ".m4v", ".mpg", ".mp2", ".mpeg", ".mpe", ".mpv",
-        ".m2v",
-    ];
+/// Left aligned text with the chars at `positions` drawn in bold and colour
+pub fn draw_data_highlight(
+    s: &str,
+    positions: &[usize],
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    selected: bool,
+) {
+    if positions.is_empty() {
+        return draw_data(s, x, y, w, h, selected, Align::Left);
+    }
+
+    draw_data("", x, y, w, h, selected, Align::Left);
+
+    draw::push_clip(x, y, w, h);
+    let size = draw::size();
+    let font = draw::font();
+    let baseline = y + (h + draw::height()) / 2 - draw::descent();
+
+    // Draw runs of highlighted and plain chars one after another
+    let chars: Vec<char> = s.chars().collect();
+    let mut cx = x;
+    let mut start = 0;
+    while start < chars.len() {
+        let highlight = positions.contains(&start);
+        let end = (start..chars.len())
+            .find(|ix| positions.contains(ix) != highlight)
+            .unwrap_or(chars.len());
+        let run: String = chars[start..end].iter().collect();
+
+        if highlight {
+            draw::set_font(Font::HelveticaBold, size);
+            draw::set_draw_color(Color::DarkRed);
+        } else {
+            draw::set_font(font, size);
+            draw::set_draw_color(Color::Gray0);
+        }
+        draw::draw_text(&run, cx, baseline);
+        cx += draw::width(&run) as i32;
+        start = end;
+    }
 
+    draw::set_font(font, size);
+    draw::set_draw_color(Color::Gray0);
+    draw::pop_clip();
+}
+
+/// File endings shown in the video colour, unless the config lists others
+pub const VIDEO_FORMATS: [&str; 23] = [
+    ".mkv", ".webm", ".flv", ".vob", ".ogg", ".ogv", ".avi", ".mov", ".qt", ".wmv", ".rm", ".rmvb",
+    ".asf", ".amv", ".mp4", ".m4p", ".m4v", ".mpg", ".mp2", ".mpeg", ".mpe", ".mpv", ".m2v",
+];
+
+pub fn get_file_color(file_name: &str, video_extensions: &[String]) -> Color {
     let file_name = file_name.to_lowercase();
 
-    for fmt in VIDEO_FORMATS {
-        if file_name.ends_with(fmt) {
+    for fmt in video_extensions {
+        if file_name.ends_with(fmt.as_str()) {
             return Color::Blue;
         }
     }
@@ -60,6 +110,22 @@ pub fn get_file_color(file_name: &str) -> Color {
     Color::Gray0
 }
 
+/// Font size and row height of the entry and file tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableStyle {
+    pub font_size: i32,
+    pub row_height: i32,
+}
+
+impl Default for TableStyle {
+    fn default() -> Self {
+        TableStyle {
+            font_size: 14,
+            row_height: 20,
+        }
+    }
+}
+
 const KB: i64 = 1000;
 const MB: i64 = KB * KB;
 const GB: i64 = KB * KB * KB;
@@ -76,7 +142,40 @@ pub fn pretty_size(size: i64) -> String {
     }
 }
 
-#[derive(Debug)]
+fn format_date_time(date_time: time::OffsetDateTime) -> String {
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}",
+        date_time.year(),
+        date_time.month() as u8,
+        date_time.day(),
+        date_time.hour(),
+        date_time.minute()
+    )
+}
+
+static LOCAL_OFFSET: OnceLock<time::UtcOffset> = OnceLock::new();
+
+/// Reads the local UTC offset, call before any other thread is started. The offset can
+/// not be read safely once the process has more than one thread.
+pub fn init_local_offset() {
+    let offset = time::UtcOffset::current_local_offset().unwrap_or(time::UtcOffset::UTC);
+    let _ = LOCAL_OFFSET.set(offset);
+}
+
+/// The offset read by `init_local_offset`, UTC before that
+pub fn local_offset() -> time::UtcOffset {
+    LOCAL_OFFSET.get().copied().unwrap_or(time::UtcOffset::UTC)
+}
+
+/// Formats seconds since the unix epoch as local time
+pub fn pretty_time(timestamp: u64) -> String {
+    match time::OffsetDateTime::from_unix_timestamp(timestamp as i64) {
+        Ok(date_time) => format_date_time(date_time.to_offset(local_offset())),
+        Err(_) => String::new(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Grade {
     S = 255,
     A = 254,
@@ -104,16 +203,42 @@ impl TryFrom<i32> for Grade {
     }
 }
 
+impl Grade {
+    /// All grades, best first
+    pub const ALL: [Grade; 7] = [
+        Grade::S,
+        Grade::A,
+        Grade::B,
+        Grade::C,
+        Grade::D,
+        Grade::E,
+        Grade::F,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Grade::S => "S",
+            Grade::A => "A",
+            Grade::B => "B",
+            Grade::C => "C",
+            Grade::D => "D",
+            Grade::E => "E",
+            Grade::F => "F",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Grade> {
+        Grade::ALL
+            .iter()
+            .find(|g| g.label().eq_ignore_ascii_case(label.trim()))
+            .copied()
+    }
+}
+
 pub fn pretty_grade(grade: Option<i32>) -> String {
     if let Some(grade) = grade {
-        return match grade.try_into() {
-            Ok(Grade::S) => "S".to_owned(),
-            Ok(Grade::A) => "A".to_owned(),
-            Ok(Grade::B) => "B".to_owned(),
-            Ok(Grade::C) => "C".to_owned(),
-            Ok(Grade::D) => "D".to_owned(),
-            Ok(Grade::E) => "E".to_owned(),
-            Ok(Grade::F) => "F".to_owned(),
+        return match Grade::try_from(grade) {
+            Ok(grade) => grade.label().to_owned(),
             Err(_) => "".to_owned(),
         };
     }
@@ -166,6 +291,22 @@ pub fn resize_column(table: &mut TableRow, columns: &Vec<ColHeader>) {
     // );
 }
 
+/// Current width of every column
+pub fn column_widths(table: &TableRow) -> Vec<i32> {
+    (0..table.cols()).map(|col| table.col_width(col)).collect()
+}
+
+/// Restores saved widths, ignored when the column count has changed
+pub fn set_column_widths(table: &mut TableRow, widths: &[i32]) {
+    if widths.len() != table.cols() as usize {
+        return;
+    }
+
+    for (col, width) in widths.iter().enumerate() {
+        table.set_col_width(col as i32, *width);
+    }
+}
+
 fn resize_column_internal(
     table_width: i32,
     min_width: i32,
@@ -225,6 +366,30 @@ fn resize_column_internal(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_grade_label_roundtrip() {
+        for grade in Grade::ALL {
+            assert_eq!(Grade::from_label(grade.label()), Some(grade));
+        }
+
+        assert_eq!(Grade::from_label("b"), Some(Grade::B));
+        assert_eq!(Grade::from_label("X"), None);
+    }
+
+    #[test]
+    fn test_pretty_grade() {
+        assert_eq!(pretty_grade(Some(Grade::S as i32)), "S");
+        assert_eq!(pretty_grade(Some(Grade::F as i32)), "F");
+        assert_eq!(pretty_grade(Some(12)), "");
+        assert_eq!(pretty_grade(None), "");
+    }
+
+    #[test]
+    fn test_format_date_time() {
+        let date_time = time::OffsetDateTime::from_unix_timestamp(1_600_000_000).unwrap();
+        assert_eq!(format_date_time(date_time), "2020-09-13 12:26");
+    }
+
     #[test]
     fn test_fixed_simple() {
         let headers = vec![
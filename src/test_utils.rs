@@ -0,0 +1,10 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// Empty directory for a test, named after the test and the process so parallel runs do not collide
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("so_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
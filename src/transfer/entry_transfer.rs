@@ -0,0 +1,704 @@
+use fltk::app::Sender;
+use parking_lot::Mutex;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use serious_organizer_lib::lens::Lens;
+
+use crate::file_ops;
+use crate::grade_store::GradeStore;
+use crate::history::journal::Operation;
+use crate::model::message::Message;
+use crate::scan::scanner::{entry_by_path, Scanner};
+use crate::trash;
+
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferMode {
+    Move,
+    Copy,
+}
+
+impl TransferMode {
+    pub fn verb(&self) -> &'static str {
+        match self {
+            TransferMode::Move => "Move",
+            TransferMode::Copy => "Copy",
+        }
+    }
+}
+
+/// What a transfer is for, decides what the history gets once it is finished
+#[derive(Debug, Clone)]
+pub enum TransferOrigin {
+    Entries,
+    Files,
+    /// Undoing the operation at the index of the history
+    Undo(usize, Operation),
+    /// Redoing the operation at the index of the history
+    Redo(usize, Operation),
+}
+
+impl TransferOrigin {
+    /// Entries carry their grades and labels along, files are only rescanned
+    pub fn moves_entries(&self) -> bool {
+        match self {
+            TransferOrigin::Entries => true,
+            TransferOrigin::Files => false,
+            TransferOrigin::Undo(_, operation) | TransferOrigin::Redo(_, operation) => !matches!(
+                operation,
+                Operation::MoveFiles { .. } | Operation::CopyFiles { .. }
+            ),
+        }
+    }
+
+    /// The operation a new transfer adds to the history, undo and redo add none
+    pub fn operation(&self, mode: TransferMode, done: Vec<(String, String)>) -> Option<Operation> {
+        match (self, mode) {
+            (TransferOrigin::Entries, TransferMode::Move) => {
+                Some(Operation::MoveEntries { moves: done })
+            }
+            (TransferOrigin::Entries, TransferMode::Copy) => {
+                Some(Operation::CopyEntries { copies: done })
+            }
+            (TransferOrigin::Files, TransferMode::Move) => {
+                Some(Operation::MoveFiles { moves: done })
+            }
+            (TransferOrigin::Files, TransferMode::Copy) => {
+                Some(Operation::CopyFiles { copies: done })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn what(&self) -> &'static str {
+        if self.moves_entries() {
+            "entries"
+        } else {
+            "files"
+        }
+    }
+}
+
+/// How to handle an entry whose name is already taken in the target location
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictAction {
+    Skip,
+    /// The existing entry is moved to the trash first
+    Overwrite,
+    /// The entry gets a free name like "name (2)"
+    Rename,
+}
+
+/// Grade and labels of an entry, put back on the entry at its new path after the rescan
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryMeta {
+    pub grade: Option<i32>,
+    pub labels: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferItem {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub overwrite: bool,
+    pub meta: EntryMeta,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TransferProgress {
+    pub name: String,
+    pub item_ix: usize,
+    pub item_count: usize,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TransferSummary {
+    /// Source and target path of each entry that was moved or copied
+    pub done: Vec<(String, String)>,
+    pub skipped: usize,
+    pub errors: Vec<String>,
+    pub cancelled: bool,
+}
+
+pub fn entry_meta(lens: &Lens, grades: &GradeStore, path: &str) -> EntryMeta {
+    match entry_by_path(lens, path) {
+        Some(entry) => EntryMeta {
+            grade: grades.grade(entry),
+            labels: lens
+                .entry_labels(entry.id as u32)
+                .into_iter()
+                .map(|id| id as u32)
+                .collect(),
+        },
+        None => EntryMeta::default(),
+    }
+}
+
+/// Free path next to `target`, "name (2).ext" for files and "name (2)" for dirs
+pub fn unique_target(target: &Path, is_dir: bool) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let (stem, ext) = match name.rfind('.') {
+        Some(dot) if !is_dir && dot > 0 => (name[..dot].to_string(), name[dot..].to_string()),
+        _ => (name.clone(), String::new()),
+    };
+
+    (2..)
+        .map(|n| target.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|p| fs::symlink_metadata(p).is_err())
+        .unwrap()
+}
+
+/// Decides the target of each entry in `dir`, `resolve` is asked when the name is taken
+pub fn plan(
+    sources: Vec<(PathBuf, EntryMeta)>,
+    dir: &Path,
+    resolve: &mut dyn FnMut(&Path, &Path) -> Option<ConflictAction>,
+) -> Result<Vec<TransferItem>, String> {
+    let mut items = Vec::new();
+
+    for (source, meta) in sources.into_iter() {
+        let name = source
+            .file_name()
+            .ok_or_else(|| format!("Invalid path: {:?}", source))?;
+        let mut target = dir.join(name);
+
+        if target == source {
+            println!("{:?} is already in {:?}, skipping", source, dir);
+            continue;
+        }
+
+        if dir.starts_with(&source) {
+            return Err(format!("Can not put {:?} inside itself", source));
+        }
+
+        let mut overwrite = false;
+        if fs::symlink_metadata(&target).is_ok() {
+            match resolve(&source, &target) {
+                Some(ConflictAction::Skip) => continue,
+                Some(ConflictAction::Overwrite) => overwrite = true,
+                Some(ConflictAction::Rename) => target = unique_target(&target, source.is_dir()),
+                None => return Ok(Vec::new()),
+            }
+        }
+
+        items.push(TransferItem {
+            source,
+            target,
+            overwrite,
+            meta,
+        });
+    }
+
+    Ok(items)
+}
+
+/// Total size of the files under `path`, symlinks are not followed
+pub fn tree_size(path: &Path) -> u64 {
+    let meta = match fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(_) => return 0,
+    };
+
+    if meta.is_dir() {
+        fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| tree_size(&e.path()))
+                    .sum()
+            })
+            .unwrap_or(0)
+    } else {
+        meta.len()
+    }
+}
+
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "Cancelled")
+}
+
+fn copy_file(
+    from: &Path,
+    to: &Path,
+    progress: &mut dyn FnMut(u64),
+    cancel: &AtomicBool,
+) -> io::Result<()> {
+    let mut reader = File::open(from)?;
+    let mut writer = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(to)?;
+    let mut buffer = vec![0; BUFFER_SIZE];
+
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(cancelled());
+        }
+
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+
+        writer.write_all(&buffer[..read])?;
+        progress(read as u64);
+    }
+
+    fs::set_permissions(to, fs::metadata(from)?.permissions())?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, _to: &Path) -> io::Result<()> {
+    println!("Skipping symlink {:?}", from);
+    Ok(())
+}
+
+fn copy_tree_inner(
+    from: &Path,
+    to: &Path,
+    progress: &mut dyn FnMut(u64),
+    cancel: &AtomicBool,
+) -> io::Result<()> {
+    let file_type = fs::symlink_metadata(from)?.file_type();
+
+    if file_type.is_symlink() {
+        copy_symlink(from, to)
+    } else if file_type.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_tree_inner(&entry.path(), &to.join(entry.file_name()), progress, cancel)?;
+        }
+        Ok(())
+    } else {
+        copy_file(from, to, progress, cancel)
+    }
+}
+
+fn remove_tree(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Copies a file or dir, a partial copy is removed if it fails or is cancelled
+pub fn copy_tree(
+    from: &Path,
+    to: &Path,
+    progress: &mut dyn FnMut(u64),
+    cancel: &AtomicBool,
+) -> io::Result<()> {
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{:?} already exists", to),
+        ));
+    }
+
+    let result = copy_tree_inner(from, to, progress, cancel);
+    if result.is_err() {
+        let _ = remove_tree(to);
+    }
+    result
+}
+
+/// Moves a file or dir. When the target is on another device it is copied and the
+/// source removed once the copy is complete.
+pub fn move_tree(
+    from: &Path,
+    to: &Path,
+    progress: &mut dyn FnMut(u64),
+    cancel: &AtomicBool,
+) -> io::Result<()> {
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{:?} already exists", to),
+        ));
+    }
+
+    let size = tree_size(from);
+    match fs::rename(from, to) {
+        Ok(_) => {
+            progress(size);
+            Ok(())
+        }
+        Err(err) if file_ops::is_cross_device(&err) => {
+            println!("{:?} is on another device, copying it instead", to);
+            copy_tree(from, to, progress, cancel)?;
+            remove_tree(from)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Hidden free name next to `target`, an overwrite goes there until the old one is trashed
+fn temp_target(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let temp = target.with_file_name(format!(".{}.part", name));
+    if fs::symlink_metadata(&temp).is_err() {
+        temp
+    } else {
+        unique_target(&temp, true)
+    }
+}
+
+/// Trashes the existing target and puts the transferred `temp` in its place
+fn replace_target(temp: &Path, target: &Path, mode: TransferMode) -> Result<(), String> {
+    if let Err(err) = trash::trash(target) {
+        return match mode {
+            TransferMode::Copy => {
+                let _ = remove_tree(temp);
+                Err(format!("{:?}: Failed to trash existing: {}", target, err))
+            }
+            // The source is gone, the moved entry stays at the temp name
+            TransferMode::Move => Err(format!(
+                "{:?}: Failed to trash existing, moved to {:?} instead: {}",
+                target, temp, err
+            )),
+        };
+    }
+
+    fs::rename(temp, target).map_err(|err| {
+        format!(
+            "{:?}: Existing was trashed, but {:?} could not take its place: {}",
+            target, temp, err
+        )
+    })
+}
+
+/// Moves or copies the items one by one, stops at the next item when cancelled
+pub fn run(
+    items: &[TransferItem],
+    mode: TransferMode,
+    cancel: &AtomicBool,
+    report: &mut dyn FnMut(&TransferProgress),
+) -> TransferSummary {
+    let mut summary = TransferSummary::default();
+    let mut progress = TransferProgress {
+        item_count: items.len(),
+        bytes_total: items.iter().map(|i| tree_size(&i.source)).sum(),
+        ..Default::default()
+    };
+
+    for (ix, item) in items.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            summary.cancelled = true;
+            summary.skipped += items.len() - ix;
+            break;
+        }
+
+        progress.item_ix = ix;
+        progress.name = item
+            .source
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        report(&progress);
+
+        // An overwrite leaves the existing target alone until the transfer has succeeded
+        let dest = if item.overwrite {
+            temp_target(&item.target)
+        } else {
+            item.target.clone()
+        };
+
+        let bytes_before = progress.bytes_done;
+        let mut last_report = bytes_before;
+        let mut on_bytes = |bytes: u64| {
+            progress.bytes_done += bytes;
+            // Keep the number of messages down for big files
+            if progress.bytes_done - last_report >= BUFFER_SIZE as u64 * 16 {
+                last_report = progress.bytes_done;
+                report(&progress);
+            }
+        };
+
+        let result = match mode {
+            TransferMode::Move => move_tree(&item.source, &dest, &mut on_bytes, cancel),
+            TransferMode::Copy => copy_tree(&item.source, &dest, &mut on_bytes, cancel),
+        };
+
+        match result {
+            Ok(_) if item.overwrite => match replace_target(&dest, &item.target, mode) {
+                Ok(_) => summary.done.push((
+                    item.source.to_string_lossy().to_string(),
+                    item.target.to_string_lossy().to_string(),
+                )),
+                Err(err) => summary.errors.push(err),
+            },
+            Ok(_) => summary.done.push((
+                item.source.to_string_lossy().to_string(),
+                item.target.to_string_lossy().to_string(),
+            )),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                summary.cancelled = true;
+                summary.skipped += items.len() - ix;
+                progress.bytes_done = bytes_before;
+                break;
+            }
+            Err(err) => summary.errors.push(format!("{:?}: {}", item.source, err)),
+        }
+    }
+
+    progress.item_ix = items.len();
+    report(&progress);
+
+    summary
+}
+
+/// Has both sides of the transfer rescanned and the grades and labels put back on the
+/// new entries
+fn update_lens(
+    grades: &Arc<Mutex<GradeStore>>,
+    scanner: &Scanner,
+    done: &[(String, String, EntryMeta)],
+) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    // Grades are kept by path, they can be set before the new entries are scanned
+    let graded: Vec<(String, Option<i32>)> = done
+        .iter()
+        .map(|(_, to, meta)| (to.clone(), meta.grade))
+        .collect();
+    if let Err(err) = grades.lock().set_all(&graded) {
+        errors.push(err);
+    }
+
+    // The scanner adds the labels once the new entries are in the lens
+    let labels: Vec<(String, Vec<u32>)> = done
+        .iter()
+        .filter(|(_, _, meta)| !meta.labels.is_empty())
+        .map(|(_, to, meta)| (to.clone(), meta.labels.clone()))
+        .collect();
+    scanner.rescan_with_labels(
+        done.iter()
+            .flat_map(|(from, to, _)| [Path::new(from.as_str()), Path::new(to.as_str())]),
+        labels,
+    );
+
+    errors
+}
+
+/// Moves or copies the entries or files on a background thread, progress is sent as messages
+pub fn start_transfer(
+    grades: Arc<Mutex<GradeStore>>,
+    sender: Sender<Message>,
+    cancel: Arc<AtomicBool>,
+    scanner: Scanner,
+    items: Vec<TransferItem>,
+    mode: TransferMode,
+    origin: TransferOrigin,
+) {
+    cancel.store(false, Ordering::Relaxed);
+    sender.send(Message::TransferStarted(mode, origin.clone()));
+
+    thread::spawn(move || {
+        println!("Start {:?} of {} {}", mode, items.len(), origin.what());
+
+        let mut summary = run(&items, mode, &cancel, &mut |progress| {
+            sender.send(Message::TransferProgress(progress.clone()))
+        });
+
+        if origin.moves_entries() {
+            let done: Vec<(String, String, EntryMeta)> = summary
+                .done
+                .iter()
+                .filter_map(|(from, to)| {
+                    items
+                        .iter()
+                        .find(|i| i.target == Path::new(to))
+                        .map(|i| (from.clone(), to.clone(), i.meta.clone()))
+                })
+                .collect();
+
+            summary.errors.extend(update_lens(&grades, &scanner, &done));
+        } else {
+            scanner.rescan_paths(
+                summary
+                    .done
+                    .iter()
+                    .flat_map(|(from, to)| [Path::new(from.as_str()), Path::new(to.as_str())]),
+            );
+        }
+
+        sender.send(Message::TransferFinished(mode, origin, summary));
+        sender.send(Message::EntryTableInvalidated);
+        sender.send(Message::FileTableInvalidated);
+        sender.send(Message::LabelTableInvalidated);
+        println!("Done {:?}", mode);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    #[test]
+    fn test_unique_target() {
+        let dir = temp_dir("transfer_unique");
+        fs::write(dir.join("a.mkv"), "").unwrap();
+        fs::write(dir.join("a (2).mkv"), "").unwrap();
+        fs::create_dir(dir.join("Movie.2001")).unwrap();
+
+        assert_eq!(
+            unique_target(&dir.join("a.mkv"), false),
+            dir.join("a (3).mkv")
+        );
+        assert_eq!(
+            unique_target(&dir.join("Movie.2001"), true),
+            dir.join("Movie.2001 (2)")
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_temp_target() {
+        let dir = temp_dir("transfer_temp");
+        fs::write(dir.join("a.mkv"), "").unwrap();
+
+        let temp = temp_target(&dir.join("a.mkv"));
+        assert_eq!(temp, dir.join(".a.mkv.part"));
+
+        fs::write(&temp, "").unwrap();
+        assert_eq!(temp_target(&dir.join("a.mkv")), dir.join(".a.mkv.part (2)"));
+
+        // A failed rename is not turned into a copy
+        let cancel = AtomicBool::new(false);
+        let missing = move_tree(&dir.join("b"), &dir.join("c"), &mut |_| (), &cancel);
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert!(!dir.join("c").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan_conflicts() {
+        let dir = temp_dir("transfer_plan");
+        let from = dir.join("from");
+        let to = dir.join("to");
+        fs::create_dir_all(&from).unwrap();
+        fs::create_dir_all(&to).unwrap();
+        for name in ["a", "b", "c"] {
+            fs::write(from.join(name), "new").unwrap();
+        }
+        fs::write(to.join("b"), "old").unwrap();
+        fs::write(to.join("c"), "old").unwrap();
+
+        let sources: Vec<(PathBuf, EntryMeta)> = ["a", "b", "c"]
+            .iter()
+            .map(|n| (from.join(n), EntryMeta::default()))
+            .collect();
+
+        let mut asked = Vec::new();
+        let items = plan(sources.clone(), &to, &mut |source, _| {
+            asked.push(source.to_path_buf());
+            if source.ends_with("b") {
+                Some(ConflictAction::Skip)
+            } else {
+                Some(ConflictAction::Rename)
+            }
+        })
+        .unwrap();
+
+        assert_eq!(asked, vec![from.join("b"), from.join("c")]);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].target, to.join("a"));
+        assert_eq!(items[1].target, to.join("c (2)"));
+
+        // Cancelling the conflict question cancels everything
+        assert!(plan(sources, &to, &mut |_, _| None).unwrap().is_empty());
+
+        // Entries already in the dir are left out
+        let here = vec![(to.join("b"), EntryMeta::default())];
+        assert!(plan(here, &to, &mut |_, _| None).unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_move_and_copy() {
+        let dir = temp_dir("transfer_run");
+        let source = dir.join("Movie");
+        fs::create_dir_all(source.join("Subs")).unwrap();
+        fs::write(source.join("movie.mkv"), "12345").unwrap();
+        fs::write(source.join("Subs/en.srt"), "123").unwrap();
+        assert_eq!(tree_size(&source), 8);
+
+        let copy = TransferItem {
+            source: source.clone(),
+            target: dir.join("Copy"),
+            overwrite: false,
+            meta: EntryMeta::default(),
+        };
+
+        let mut last = TransferProgress::default();
+        let cancel = AtomicBool::new(false);
+        let summary = run(&[copy], TransferMode::Copy, &cancel, &mut |p| {
+            last = p.clone()
+        });
+        assert!(summary.errors.is_empty());
+        assert_eq!(last.bytes_done, 8);
+        assert_eq!(last.bytes_total, 8);
+        assert_eq!(
+            fs::read_to_string(dir.join("Copy/Subs/en.srt")).unwrap(),
+            "123"
+        );
+        assert!(source.exists());
+
+        let moved = TransferItem {
+            source: source.clone(),
+            target: dir.join("Moved"),
+            overwrite: false,
+            meta: EntryMeta::default(),
+        };
+        let summary = run(&[moved], TransferMode::Move, &cancel, &mut |_| ());
+        assert_eq!(summary.done.len(), 1);
+        assert!(!source.exists());
+        assert!(dir.join("Moved/movie.mkv").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cancelled_copy_is_removed() {
+        let dir = temp_dir("transfer_cancel");
+        let source = dir.join("a");
+        fs::write(&source, "data").unwrap();
+
+        let cancel = AtomicBool::new(true);
+        let result = copy_tree(&source, &dir.join("b"), &mut |_| (), &cancel);
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Interrupted);
+        assert!(!dir.join("b").exists());
+        assert!(source.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,2 @@
+pub mod entry_transfer;
+pub mod transfer_dialog;
@@ -0,0 +1,173 @@
+use fltk::button::Button;
+use fltk::frame::Frame;
+use fltk::misc::Progress;
+use fltk::prelude::*;
+use fltk::{enums::*, window::*};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use crate::choice_dialog::ChoiceDialog;
+use crate::table_utils::{pretty_size, pretty_time};
+use crate::transfer::entry_transfer::{
+    tree_size, ConflictAction, TransferMode, TransferProgress, TransferSummary,
+};
+
+pub struct TransferDialog {
+    dialog: Window,
+    status: Frame,
+    counts: Frame,
+    progress: Progress,
+    but_cancel: Button,
+    finished: Arc<AtomicBool>,
+}
+
+impl TransferDialog {
+    pub fn new(cancel: Arc<AtomicBool>) -> Self {
+        let mut dialog = Window::new(300, 325, 450, 130, "Transfer");
+
+        let mut status = Frame::new(10, 10, 430, 25, None);
+        status.set_label_size(14);
+        status.set_align(Align::Left | Align::Inside | Align::Clip);
+
+        let mut counts = Frame::new(10, 35, 430, 20, None);
+        counts.set_label_size(10);
+        counts.set_align(Align::Left | Align::Inside);
+
+        let mut progress = Progress::new(10, 60, 430, 20, None);
+        progress.set_minimum(0.);
+        progress.set_maximum(1.);
+        progress.set_selection_color(Color::Blue);
+
+        // Cancels a running transfer, closes the dialog once it is finished
+        let finished = Arc::new(AtomicBool::new(false));
+        let mut but_cancel = Button::new(10, 95, 60, 25, "Cancel");
+        let mut status_c = status.clone();
+        let mut dialog_c = dialog.clone();
+        let finished_c = finished.clone();
+        but_cancel.set_callback(move |_| {
+            if finished_c.load(Ordering::Relaxed) {
+                dialog_c.hide();
+            } else {
+                cancel.store(true, Ordering::Relaxed);
+                status_c.set_label("Cancelling...");
+            }
+        });
+
+        dialog.end();
+
+        TransferDialog {
+            dialog,
+            status,
+            counts,
+            progress,
+            but_cancel,
+            finished,
+        }
+    }
+
+    pub fn show(&mut self, mode: TransferMode, what: &str) {
+        self.finished.store(false, Ordering::Relaxed);
+        self.dialog.set_label(&format!("{} {}", mode.verb(), what));
+        self.but_cancel.set_label("Cancel");
+        self.status.set_label("Starting...");
+        self.counts.set_label("");
+        self.progress.set_value(0.);
+        self.dialog.show();
+    }
+
+    pub fn update(&mut self, progress: &TransferProgress) {
+        self.status.set_label(&format!(
+            "{} ({}/{})",
+            progress.name,
+            (progress.item_ix + 1).min(progress.item_count),
+            progress.item_count
+        ));
+        self.counts.set_label(&format!(
+            "{} of {}",
+            pretty_size(progress.bytes_done as i64),
+            pretty_size(progress.bytes_total as i64)
+        ));
+
+        self.progress
+            .set_maximum(progress.bytes_total.max(1) as f64);
+        self.progress.set_value(progress.bytes_done as f64);
+        self.dialog.redraw();
+    }
+
+    pub fn finish(&mut self, mode: TransferMode, what: &str, summary: &TransferSummary) {
+        self.finished.store(true, Ordering::Relaxed);
+        self.but_cancel.set_label("Close");
+
+        let state = if summary.cancelled {
+            "Cancelled"
+        } else {
+            "Done"
+        };
+        self.status.set_label(&format!(
+            "{}. {} {} {}",
+            state,
+            mode.verb(),
+            summary.done.len(),
+            what
+        ));
+        self.counts.set_label(&format!(
+            "{} skipped, {} failed",
+            summary.skipped,
+            summary.errors.len()
+        ));
+        self.dialog.redraw();
+    }
+}
+
+fn describe(path: &Path) -> String {
+    let modified = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|d| pretty_time(d.as_secs()))
+        .unwrap_or_default();
+
+    format!("{} {}", pretty_size(tree_size(path) as i64), modified)
+}
+
+/// Asks what to do when `target` is taken, None cancels the whole transfer
+pub fn ask_conflict(source: &Path, target: &Path) -> Option<ConflictAction> {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut text = format!("{} already exists", name);
+    let mut choices = vec!["Skip", "Overwrite", "Rename", "Compare", "Cancel"];
+
+    loop {
+        let dialog = ChoiceDialog::new(
+            text.clone(),
+            choices.iter().map(|c| c.to_string()).collect(),
+        );
+        dialog.show();
+
+        let choice = usize::try_from(dialog.result())
+            .ok()
+            .and_then(|ix| choices.get(ix).copied());
+
+        match choice {
+            Some("Skip") => return Some(ConflictAction::Skip),
+            Some("Overwrite") => return Some(ConflictAction::Overwrite),
+            Some("Rename") => return Some(ConflictAction::Rename),
+            Some("Compare") => {
+                text = format!(
+                    "{}  New: {}  Existing: {}",
+                    name,
+                    describe(source),
+                    describe(target)
+                );
+                choices.retain(|c| *c != "Compare");
+            }
+            _ => return None,
+        }
+    }
+}
@@ -0,0 +1,349 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use crate::table_utils;
+use crate::transfer::entry_transfer::move_tree;
+
+/// How selected entries or files are deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteMode {
+    Trash,
+    Permanent,
+}
+
+impl DeleteMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeleteMode::Trash => "Trash",
+            DeleteMode::Permanent => "Delete",
+        }
+    }
+
+    /// Both modes in the order they are offered, this one first as the default
+    pub fn offered(&self) -> [DeleteMode; 2] {
+        match self {
+            DeleteMode::Trash => [DeleteMode::Trash, DeleteMode::Permanent],
+            DeleteMode::Permanent => [DeleteMode::Permanent, DeleteMode::Trash],
+        }
+    }
+}
+
+/// An item moved to the trash, with what is needed to put it back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashedItem {
+    pub original_path: PathBuf,
+    pub trash_path: PathBuf,
+    pub info_path: PathBuf,
+}
+
+/// The home trash, `$XDG_DATA_HOME/Trash` or `~/.local/share/Trash`
+pub fn home_trash_dir() -> Option<PathBuf> {
+    if let Some(data_home) = env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(data_home).join("Trash"));
+    }
+
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share/Trash"))
+}
+
+#[cfg(unix)]
+fn device(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_path: &Path) -> Option<u64> {
+    Some(0)
+}
+
+#[cfg(unix)]
+fn user_id() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+#[cfg(not(unix))]
+fn user_id() -> u32 {
+    0
+}
+
+/// Closest existing ancestor, the trash dir might not be created yet
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|p| p.exists())
+}
+
+/// Top directory of the mount that `path` is on
+fn mount_point(path: &Path) -> PathBuf {
+    let dev = device(path);
+    let mut top = path;
+
+    while let Some(parent) = top.parent() {
+        if device(parent) != dev {
+            break;
+        }
+        top = parent;
+    }
+
+    top.to_path_buf()
+}
+
+/// Files on the same device as the home trash go there, others to `$topdir/.Trash-$uid`
+pub fn trash_dir_for(path: &Path) -> io::Result<PathBuf> {
+    let home_trash = home_trash_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No home directory"))?;
+
+    let path_dev = device(path.parent().unwrap_or(path));
+    let home_dev = existing_ancestor(&home_trash).and_then(device);
+
+    if path_dev.is_some() && path_dev == home_dev {
+        Ok(home_trash)
+    } else {
+        Ok(mount_point(path).join(format!(".Trash-{}", user_id())))
+    }
+}
+
+/// Percent encodes a path for the `Path=` key of a trashinfo file
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+fn deletion_date() -> String {
+    let now = time::OffsetDateTime::now_utc().to_offset(table_utils::local_offset());
+
+    format!(
+        "{}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        now.year(),
+        now.month() as u8,
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    )
+}
+
+/// Absolute path without resolving a symlink in the last component, that is what gets trashed
+fn absolute(path: &Path) -> io::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::canonicalize(parent)?,
+        _ => env::current_dir()?,
+    };
+
+    Ok(parent.join(name))
+}
+
+/// Moves a file or directory into the given trash directory
+pub fn trash_into(path: &Path, trash_dir: &Path) -> io::Result<TrashedItem> {
+    let original_path = absolute(path)?;
+    fs::symlink_metadata(&original_path)?;
+
+    let files_dir = trash_dir.join("files");
+    let info_dir = trash_dir.join("info");
+    fs::create_dir_all(&files_dir)?;
+    fs::create_dir_all(&info_dir)?;
+
+    let name = original_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    for n in 1.. {
+        let trash_name = if n == 1 {
+            name.clone()
+        } else {
+            format!("{}.{}", name, n)
+        };
+
+        let info_path = info_dir.join(format!("{}.trashinfo", trash_name));
+        let trash_path = files_dir.join(&trash_name);
+        if trash_path.exists() {
+            continue;
+        }
+
+        // Creating the info file reserves the name
+        let mut info = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(info) => info,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        };
+
+        write!(
+            info,
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_path(&original_path),
+            deletion_date()
+        )?;
+
+        // A trash on another device, like through a bind mount, takes a copy
+        let no_cancel = AtomicBool::new(false);
+        if let Err(err) = move_tree(&original_path, &trash_path, &mut |_| (), &no_cancel) {
+            let _ = fs::remove_file(&info_path);
+            return Err(err);
+        }
+
+        return Ok(TrashedItem {
+            original_path,
+            trash_path,
+            info_path,
+        });
+    }
+
+    unreachable!()
+}
+
+/// Moves a file or directory to the trash for its device
+pub fn trash(path: &Path) -> io::Result<TrashedItem> {
+    let trash_dir = trash_dir_for(path)?;
+    trash_into(path, &trash_dir)
+}
+
+/// Puts a trashed item back where it was
+pub fn restore(item: &TrashedItem) -> io::Result<()> {
+    if item.original_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{:?} already exists", item.original_path),
+        ));
+    }
+
+    if let Some(parent) = item.original_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // The trash can be on another device than the original path, see `trash_into`
+    let no_cancel = AtomicBool::new(false);
+    move_tree(
+        &item.trash_path,
+        &item.original_path,
+        &mut |_| (),
+        &no_cancel,
+    )?;
+
+    if let Err(err) = fs::remove_file(&item.info_path) {
+        println!("Failed to remove trash info {:?}: {}", item.info_path, err);
+    }
+
+    Ok(())
+}
+
+/// Text for an error dialog listing the paths that failed to be deleted and why
+pub fn failure_summary(what: &str, total: usize, errors: &[String]) -> String {
+    format!(
+        "Failed to delete {} of {} {}:\n{}",
+        errors.len(),
+        total,
+        what,
+        errors.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    #[test]
+    fn test_encode_path() {
+        assert_eq!(
+            encode_path(Path::new("/movies/A film (2001).mkv")),
+            "/movies/A%20film%20%282001%29.mkv"
+        );
+        assert_eq!(encode_path(Path::new("/a/ö")), "/a/%C3%B6");
+    }
+
+    #[test]
+    fn test_failure_summary() {
+        let errors = vec!["/a: Permission denied".to_string()];
+        assert_eq!(
+            failure_summary("entries", 3, &errors),
+            "Failed to delete 1 of 3 entries:\n/a: Permission denied"
+        );
+    }
+
+    #[test]
+    fn test_delete_modes() {
+        assert_eq!(
+            DeleteMode::Trash.offered(),
+            [DeleteMode::Trash, DeleteMode::Permanent]
+        );
+        assert_eq!(DeleteMode::Permanent.offered()[0].label(), "Delete");
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
+        let dir = temp_dir("trash_restore");
+        let trash_dir = dir.join("Trash");
+        let file = dir.join("movie.mkv");
+        fs::write(&file, "data").unwrap();
+
+        let item = trash_into(&file, &trash_dir).unwrap();
+        assert!(!file.exists());
+        assert!(item.trash_path.exists());
+
+        let info = fs::read_to_string(&item.info_path).unwrap();
+        assert!(info.starts_with("[Trash Info]\nPath="));
+        assert!(info.contains("movie.mkv"));
+        assert!(info.contains("DeletionDate="));
+
+        restore(&item).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "data");
+        assert!(!item.info_path.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_trash_same_name_twice() {
+        let dir = temp_dir("trash_twice");
+        let trash_dir = dir.join("Trash");
+        let file = dir.join("a");
+
+        fs::write(&file, "1").unwrap();
+        let first = trash_into(&file, &trash_dir).unwrap();
+        fs::create_dir(&file).unwrap();
+        let second = trash_into(&file, &trash_dir).unwrap();
+
+        assert_ne!(first.trash_path, second.trash_path);
+        assert_eq!(second.trash_path, trash_dir.join("files").join("a.2"));
+        assert!(second.trash_path.is_dir());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_restore_does_not_overwrite() {
+        let dir = temp_dir("trash_overwrite");
+        let trash_dir = dir.join("Trash");
+        let file = dir.join("a");
+        fs::write(&file, "old").unwrap();
+
+        let item = trash_into(&file, &trash_dir).unwrap();
+        fs::write(&file, "new").unwrap();
+
+        assert!(restore(&item).is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+        assert!(item.trash_path.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
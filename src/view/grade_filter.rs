@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+use crate::table_utils::Grade;
+
+/// Which grades to show in the entry table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GradeFilter {
+    #[default]
+    Any,
+    /// Grade value, shows this grade or better
+    AtLeast(i32),
+    Ungraded,
+}
+
+impl GradeFilter {
+    /// Choices in the order they are listed in the grade menu
+    pub fn choices() -> Vec<GradeFilter> {
+        let mut choices = vec![GradeFilter::Any];
+        choices.extend(Grade::ALL.iter().map(|g| GradeFilter::AtLeast(*g as i32)));
+        choices.push(GradeFilter::Ungraded);
+        choices
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            GradeFilter::Any => "Any grade".to_string(),
+            GradeFilter::AtLeast(grade) => match Grade::try_from(*grade) {
+                Ok(Grade::S) => "S".to_string(),
+                Ok(grade) => format!("{} or better", grade.label()),
+                Err(_) => format!("{} or better", grade),
+            },
+            GradeFilter::Ungraded => "Ungraded".to_string(),
+        }
+    }
+
+    pub fn matches(&self, grade: Option<i32>) -> bool {
+        match self {
+            GradeFilter::Any => true,
+            GradeFilter::AtLeast(min) => grade.is_some_and(|g| g >= *min),
+            GradeFilter::Ungraded => grade.is_none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grade_filter() {
+        let b_or_better = GradeFilter::AtLeast(Grade::B as i32);
+        assert!(b_or_better.matches(Some(Grade::S as i32)));
+        assert!(b_or_better.matches(Some(Grade::B as i32)));
+        assert!(!b_or_better.matches(Some(Grade::C as i32)));
+        assert!(!b_or_better.matches(None));
+        assert_eq!(b_or_better.label(), "B or better");
+
+        assert!(GradeFilter::Ungraded.matches(None));
+        assert!(!GradeFilter::Ungraded.matches(Some(Grade::F as i32)));
+        assert_eq!(GradeFilter::choices().len(), 9);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod grade_filter;
+pub mod saved_view;
+pub mod saved_view_list;
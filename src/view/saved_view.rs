@@ -0,0 +1,156 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::view::grade_filter::GradeFilter;
+
+/// Entry table sort, column as in the entry table header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewSort {
+    pub column: i32,
+    pub descending: bool,
+}
+
+/// Everything a saved view restores, stored as json in the view data column
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ViewState {
+    #[serde(default)]
+    pub search: String,
+    #[serde(default)]
+    pub label_query: String,
+    #[serde(default)]
+    pub sort: Option<ViewSort>,
+    #[serde(default)]
+    pub grade: GradeFilter,
+    #[serde(default)]
+    pub fuzzy: bool,
+}
+
+impl ViewState {
+    pub fn to_data(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Unreadable data gives an empty view rather than failing, and a sort on a column
+    /// the entry table does not have is dropped
+    pub fn from_data(data: &str) -> ViewState {
+        let mut view: ViewState = serde_json::from_str(data).unwrap_or_else(|err| {
+            println!("Failed to read saved view '{}': {}", data, err);
+            ViewState::default()
+        });
+        view.sort = view.sort.filter(|s| (0..4).contains(&s.column));
+        view
+    }
+}
+
+/// Named view as stored in the library database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedView {
+    /// Row id, negative for a view that is not stored yet
+    pub id: i64,
+    pub name: String,
+    pub data: String,
+}
+
+/// Stored views ordered by name
+pub fn saved_views(conn: &Connection) -> rusqlite::Result<Vec<SavedView>> {
+    let mut stmt = conn.prepare("SELECT id, name, data FROM saved_view ORDER BY name, id")?;
+    let views = stmt.query_map([], |row| {
+        Ok(SavedView {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            data: row.get(2)?,
+        })
+    })?;
+    views.collect()
+}
+
+/// Inserts a new view or updates a stored one, returns its id
+pub fn save_view(conn: &Connection, view: &SavedView) -> rusqlite::Result<i64> {
+    if view.id < 0 {
+        conn.execute(
+            "INSERT INTO saved_view (name, data) VALUES (?1, ?2)",
+            params![view.name, view.data],
+        )?;
+        Ok(conn.last_insert_rowid())
+    } else {
+        conn.execute(
+            "UPDATE saved_view SET name = ?1, data = ?2 WHERE id = ?3",
+            params![view.name, view.data, view.id],
+        )?;
+        Ok(view.id)
+    }
+}
+
+pub fn delete_view(conn: &Connection, view_id: i64) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM saved_view WHERE id = ?1", params![view_id])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_db;
+    use crate::table_utils::Grade;
+    use crate::test_utils::temp_dir;
+
+    #[test]
+    fn test_view_round_trip() {
+        let view = ViewState {
+            search: "holiday".to_string(),
+            label_query: "Drama AND NOT Watched".to_string(),
+            sort: Some(ViewSort {
+                column: 2,
+                descending: true,
+            }),
+            grade: GradeFilter::AtLeast(Grade::B as i32),
+            fuzzy: true,
+        };
+
+        assert_eq!(ViewState::from_data(&view.to_data()), view);
+    }
+
+    #[test]
+    fn test_view_missing_and_bad_data() {
+        let view = ViewState::from_data(r#"{"search":"cats"}"#);
+        assert_eq!(view.search, "cats");
+        assert_eq!(view.grade, GradeFilter::Any);
+        assert_eq!(view.sort, None);
+
+        assert_eq!(ViewState::from_data("not json"), ViewState::default());
+
+        let view = ViewState::from_data(r#"{"sort":{"column":7,"descending":true}}"#);
+        assert_eq!(view.sort, None);
+        let view = ViewState::from_data(r#"{"sort":{"column":3,"descending":false}}"#);
+        assert_eq!(view.sort.map(|s| s.column), Some(3));
+    }
+
+    #[test]
+    fn test_store_views() {
+        let path = temp_dir("saved_views").join("Library.sqlite3");
+        let conn = app_db::open(&path.to_string_lossy()).unwrap();
+
+        let mut view = SavedView {
+            id: -1,
+            name: "Unwatched".to_string(),
+            data: ViewState::default().to_data(),
+        };
+        view.id = save_view(&conn, &view).unwrap();
+        let other = SavedView {
+            id: -1,
+            name: "Best".to_string(),
+            data: String::new(),
+        };
+        let other_id = save_view(&conn, &other).unwrap();
+
+        view.name = "Not watched".to_string();
+        assert_eq!(save_view(&conn, &view).unwrap(), view.id);
+
+        let views = saved_views(&conn).unwrap();
+        assert_eq!(views.len(), 2);
+        assert_eq!(views[0].id, other_id);
+        assert_eq!(views[1], view);
+
+        delete_view(&conn, other_id).unwrap();
+        assert_eq!(saved_views(&conn).unwrap(), vec![view]);
+    }
+}
@@ -0,0 +1,177 @@
+use fltk::app::Sender;
+use parking_lot::Mutex;
+use rusqlite::Connection;
+use std::sync::Arc;
+
+use fltk::menu::MenuItem;
+use fltk::table::*;
+use fltk::{enums::*, prelude::*, *};
+
+use crate::error_dialog::ErrorDialog;
+use crate::model::message::Message;
+use crate::table_utils::{draw_data, draw_header, resize_column, ColHeader, ColSize};
+use crate::view::saved_view::{delete_view, save_view, saved_views, SavedView, ViewState};
+
+/// Saved views above the labels, click restores a view
+#[derive(Clone)]
+pub struct SavedViewList {
+    pub wid: TableRow,
+    db: Arc<Mutex<Connection>>,
+    views: Arc<Mutex<Vec<SavedView>>>,
+    sender: Sender<Message>,
+}
+
+impl SavedViewList {
+    pub fn new(
+        w: i32,
+        h: i32,
+        db: Arc<Mutex<Connection>>,
+        sender: Sender<Message>,
+    ) -> SavedViewList {
+        let headers = vec![ColHeader::new("Views", ColSize::Greedy)];
+
+        let mut table = SavedViewList {
+            wid: TableRow::default().with_size(w, h),
+            db,
+            views: Arc::new(Mutex::new(Vec::new())),
+            sender,
+        };
+
+        table.set_row_height_all(20);
+        table.set_row_resize(true);
+        table.set_type(TableRowSelectMode::Single);
+        table.set_tooltip("Click to restore a view, right click to update, rename or delete");
+
+        // Cols
+        table.set_cols(headers.len() as i32);
+        table.set_col_header(true);
+        table.set_col_resize(true);
+
+        table.end();
+
+        table.update();
+
+        let mut table_c = table.clone();
+        table.handle(move |_, evt| table_c.handle_event(evt));
+
+        resize_column(&mut table, &headers);
+
+        let views_c = table.views.clone();
+
+        table
+            .wid
+            .draw_cell(move |t, ctx, row, col, x, y, w, h| match ctx {
+                table::TableContext::StartPage => draw::set_font(Font::Helvetica, 14),
+                table::TableContext::ColHeader => {
+                    draw_header(&headers[col as usize].label, x, y, w, h)
+                }
+                table::TableContext::Cell => {
+                    if let Some(view) = views_c.lock().get(row as usize) {
+                        draw_data(&view.name, x, y, w, h, t.row_selected(row), Align::Left);
+                    }
+                }
+                _ => (),
+            });
+        table
+    }
+
+    /// Reads the views from the database again
+    pub fn update(&mut self) {
+        let view_count = {
+            let mut views = self.views.lock();
+            *views = saved_views(&self.db.lock()).unwrap_or_else(|err| {
+                println!("Failed to read saved views: {}", err);
+                Vec::new()
+            });
+            views.len()
+        };
+        self.set_rows(view_count as i32);
+        self.redraw();
+    }
+
+    fn handle_event(&mut self, evt: Event) -> bool {
+        if evt != Event::Released || self.callback_context() != TableContext::Cell {
+            return false;
+        }
+
+        let view = { self.views.lock().get(self.callback_row() as usize).cloned() };
+
+        let view = match view {
+            Some(view) => view,
+            None => return false,
+        };
+
+        match app::event_mouse_button() {
+            app::MouseButton::Left => {
+                println!("Restore view {}", view.name);
+                self.sender
+                    .send(Message::ApplyView(ViewState::from_data(&view.data)));
+                true
+            }
+            app::MouseButton::Right => {
+                let menu = MenuItem::new(&["Update with current", "Rename", "Delete"]);
+                let label = menu
+                    .popup(app::event_x(), app::event_y())
+                    .and_then(|m| m.label());
+
+                match label.as_deref() {
+                    Some("Update with current") => {
+                        self.sender.send(Message::SaveView(Some(view.clone())));
+                    }
+                    Some("Rename") => {
+                        if let Some(name) = dialog::input_default("View name", &view.name) {
+                            let name = name.trim();
+                            if !name.is_empty() {
+                                let mut view = view.clone();
+                                view.name = name.to_string();
+                                let result = save_view(&self.db.lock(), &view);
+                                if let Err(err) = result {
+                                    let err_dialog =
+                                        ErrorDialog::new(format!("Failed to rename view: {}", err));
+                                    err_dialog.show();
+                                }
+                                self.update();
+                            }
+                        }
+                    }
+                    Some("Delete") => {
+                        let choice = dialog::choice2_default(
+                            &format!("Would you like to delete '{}'", view.name),
+                            "No",
+                            "Yes",
+                            "",
+                        );
+                        if choice.unwrap_or(0) == 1 {
+                            let result = delete_view(&self.db.lock(), view.id);
+                            if let Err(err) = result {
+                                let err_dialog =
+                                    ErrorDialog::new(format!("Failed to delete view: {}", err));
+                                err_dialog.show();
+                            }
+                            self.update();
+                        }
+                    }
+                    _ => (),
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+use std::ops::{Deref, DerefMut};
+
+impl Deref for SavedViewList {
+    type Target = TableRow;
+
+    fn deref(&self) -> &Self::Target {
+        &self.wid
+    }
+}
+
+impl DerefMut for SavedViewList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.wid
+    }
+}
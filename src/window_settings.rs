@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::view::saved_view::ViewSort;
+
+const MIN_WIDTH: i32 = 400;
+const MIN_HEIGHT: i32 = 300;
+const MIN_SIDEBAR: i32 = 120;
+const MIN_COLUMN: i32 = 20;
+
+/// Window layout saved on exit and restored on the next start
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    /// Column widths as left by the user, empty until the first save
+    pub entry_columns: Vec<i32>,
+    pub file_columns: Vec<i32>,
+    pub entry_sort: Option<ViewSort>,
+    pub file_sort: Option<ViewSort>,
+    /// Width of the label sidebar
+    pub sidebar_width: i32,
+    /// Share of the table height given to the entry table
+    pub split_ratio: f32,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        WindowSettings {
+            x: 100,
+            y: 100,
+            width: 715,
+            height: 800,
+            entry_columns: Vec::new(),
+            file_columns: Vec::new(),
+            entry_sort: None,
+            file_sort: None,
+            sidebar_width: 195,
+            split_ratio: 0.6,
+        }
+    }
+}
+
+/// Settings live next to the database, in the same data dir
+pub fn settings_path(db_path: &str) -> PathBuf {
+    Path::new(db_path).with_file_name("settings.json")
+}
+
+impl WindowSettings {
+    pub fn load(path: &Path) -> WindowSettings {
+        let settings = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|err| {
+                println!("Failed to parse window settings {:?}: {}", path, err);
+                WindowSettings::default()
+            }),
+            Err(_) => WindowSettings::default(),
+        };
+
+        settings.sanitized()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = serde_json::to_string_pretty(self)?;
+        fs::write(path, text)
+    }
+
+    /// Replaces sizes and sorts a hand edited or damaged file could hold
+    pub fn sanitized(mut self) -> WindowSettings {
+        let defaults = WindowSettings::default();
+
+        self.width = self.width.max(MIN_WIDTH);
+        self.height = self.height.max(MIN_HEIGHT);
+        self.sidebar_width = self.sidebar_width.clamp(MIN_SIDEBAR, self.width / 2);
+
+        if !self.split_ratio.is_finite() {
+            self.split_ratio = defaults.split_ratio;
+        }
+        self.split_ratio = self.split_ratio.clamp(0.1, 0.9);
+
+        for width in self.entry_columns.iter_mut().chain(self.file_columns.iter_mut()) {
+            *width = (*width).max(MIN_COLUMN);
+        }
+
+        self.entry_sort = self.entry_sort.filter(|s| (0..4).contains(&s.column));
+        self.file_sort = self.file_sort.filter(|s| (0..3).contains(&s.column));
+
+        self
+    }
+
+    /// Keeps the window on a screen that might be smaller than the one it was saved on
+    pub fn fit_screen(&mut self, screen_w: i32, screen_h: i32) {
+        self.width = self.width.min(screen_w);
+        self.height = self.height.min(screen_h);
+        self.x = self.x.clamp(0, screen_w - self.width);
+        self.y = self.y.clamp(0, screen_h - self.height);
+    }
+
+    /// Width of the table area and height of the entry table, for tiles of the given size
+    pub fn pane_sizes(&self, w: i32, h: i32) -> (i32, i32) {
+        let sidebar = self.sidebar_width.min(w / 2);
+        let entry_h = (h as f32 * self.split_ratio) as i32;
+        (w - sidebar, entry_h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let settings: WindowSettings =
+            serde_json::from_str(r#"{"width": 1024, "entry_columns": [300, 200]}"#).unwrap();
+
+        assert_eq!(settings.width, 1024);
+        assert_eq!(settings.entry_columns, vec![300, 200]);
+        assert_eq!(settings.height, WindowSettings::default().height);
+        assert_eq!(settings.entry_sort, None);
+    }
+
+    #[test]
+    fn test_sanitized() {
+        let settings = WindowSettings {
+            width: 10,
+            height: -5,
+            sidebar_width: 5000,
+            split_ratio: f32::NAN,
+            entry_columns: vec![0, 150],
+            entry_sort: Some(ViewSort {
+                column: 9,
+                descending: true,
+            }),
+            file_sort: Some(ViewSort {
+                column: 2,
+                descending: true,
+            }),
+            ..Default::default()
+        }
+        .sanitized();
+
+        assert_eq!(settings.width, MIN_WIDTH);
+        assert_eq!(settings.height, MIN_HEIGHT);
+        assert_eq!(settings.sidebar_width, MIN_WIDTH / 2);
+        assert_eq!(settings.split_ratio, 0.6);
+        assert_eq!(settings.entry_columns, vec![MIN_COLUMN, 150]);
+        assert_eq!(settings.entry_sort, None);
+        assert_eq!(settings.file_sort.map(|s| s.column), Some(2));
+    }
+
+    #[test]
+    fn test_fit_screen_and_panes() {
+        let mut settings = WindowSettings {
+            x: 2500,
+            y: -40,
+            width: 900,
+            height: 1400,
+            ..Default::default()
+        };
+        settings.fit_screen(1920, 1080);
+
+        assert_eq!((settings.x, settings.y), (1020, 0));
+        assert_eq!((settings.width, settings.height), (900, 1080));
+
+        assert_eq!(settings.pane_sizes(700, 600), (505, 360));
+        assert_eq!(settings.pane_sizes(300, 600), (150, 360));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = temp_dir("window_settings");
+        let path = dir.join("settings.json");
+
+        let settings = WindowSettings {
+            x: 20,
+            file_columns: vec![200, 300, 80],
+            file_sort: Some(ViewSort {
+                column: 1,
+                descending: false,
+            }),
+            split_ratio: 0.4,
+            ..Default::default()
+        };
+        settings.save(&path).unwrap();
+
+        assert_eq!(WindowSettings::load(&path), settings);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(WindowSettings::load(&path), WindowSettings::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}